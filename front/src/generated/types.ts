@@ -77,20 +77,81 @@ export type Action =
     }
   | {
       ty: "closeProject";
+    }
+  | {
+      c: string;
+      ty: "runMacro";
     };
 
-export type Panel = "editorPanel" | "projectPanel" | "resourcePanel";
+export type Panel = "editorPanel" | "projectPanel" | "resourcePanel" | "viewportPanel";
 
 export interface ShiftPaneArgs {
   pane: Panel;
+  /**
+   * Percent of its split the pane grows by, shrinks by if negative
+   */
   shift: number;
 }
 
+/**
+ * Every action along with the default keymap, generated for the frontend
+ */
+export interface ActionRegistry {
+  actions: ActionInfo[];
+  /**
+   * Keybinds every user starts out with
+   */
+  defaultKeymap: {
+    [k: string]: FilteredAction;
+  };
+}
+
+/**
+ * Everything known about an action
+ */
+export interface ActionInfo {
+  /**
+   * Json schema of the action's argument, its `c`, if it takes one
+   */
+  args?: unknown;
+  category: ActionCategory;
+  /**
+   * Keybinds bound to the action in the default keymap
+   */
+  defaultKeybinds: string[];
+  description: string;
+  title: string;
+  /**
+   * Tag of the action, its `ty`
+   */
+  ty: string;
+}
+
+/**
+ * Group an action is listed under
+ */
+export type ActionCategory = "view" | "editor" | "run" | "file" | "project";
+
+export interface FilteredAction {
+  action: Action;
+  /**
+   * Context the keybind applies in, i.e. `editorFocus && !isPlaying`
+   */
+  condition?: string | null;
+}
+
 export type PerformanceLevel = "Default" | "PowerSaver";
 
 export interface Config {
+  /**
+   * Frame rate limit, or 0 for no limit
+   */
   limitFps?: number;
   perfLevel?: PerformanceLevel | null;
+  /**
+   * Multiplier applied to the canvas size in device pixels, i.e. 0.5 renders at half resolution
+   */
+  renderScale?: number;
 }
 
 export interface Credentials {
@@ -98,6 +159,98 @@ export interface Credentials {
   usernameOrEmail: string;
 }
 
+/**
+ * Event emitted by the gpu context, for the frontend to subscribe to
+ */
+export type Event =
+  | {
+      c: FrameEvent;
+      ty: "frame";
+    }
+  | {
+      c: BuildEvent;
+      ty: "build";
+    }
+  | {
+      c: GpuError;
+      ty: "error";
+    }
+  | {
+      c: ProfileSnapshot;
+      ty: "stats";
+    };
+export type GpuErrorKind = "validation" | "outOfMemory" | "deviceLost";
+/**
+ * What the gpu was doing when an error was raised
+ */
+export type GpuErrorPhase = "pipelineCreation" | "frame" | "uncaptured";
+export type PassKind = "render" | "compute";
+
+export interface FrameEvent {
+  /**
+   * Milliseconds spent on the cpu recording and submitting the frame
+   */
+  cpuTime: number;
+  /**
+   * Frames rendered since the project was first built or restarted, including this one
+   */
+  frame: number;
+  height: number;
+  /**
+   * Seconds since the project was first built or restarted
+   */
+  time: number;
+  /**
+   * Size of the rendered frame in pixels
+   */
+  width: number;
+}
+export interface BuildEvent {
+  /**
+   * Problems found while compiling, empty if the build failed on the gpu instead
+   */
+  diagnostics: BuildDiagnostic[];
+  success: boolean;
+}
+/**
+ * Problem found while compiling a project file
+ */
+export interface BuildDiagnostic {
+  /**
+   * 1-based column the problem starts at, if known
+   */
+  column?: number | null;
+  /**
+   * Path of the offending file, same as its key in `Files`
+   */
+  file: string;
+  /**
+   * 1-based line the problem starts at, if known
+   */
+  line?: number | null;
+  message: string;
+}
+/**
+ * Error raised by the gpu
+ */
+export interface GpuError {
+  /**
+   * Entry point the error was raised for
+   */
+  entryPoint?: string | null;
+  /**
+   * Shader file the error was raised for
+   */
+  file?: string | null;
+  kind: GpuErrorKind;
+  message: string;
+  /**
+   * Kind of pass the error was raised in, if it could be narrowed down to one
+   */
+  pass?: PassKind | null;
+  phase: GpuErrorPhase;
+}
+
 export interface LoginResponse {
   userId: string;
 }
@@ -112,22 +265,93 @@ export interface NewUserResponse {
   id: string;
 }
 
+/**
+ * How pass timings were measured
+ */
+export type TimingSource = "gpu" | "cpu";
+
+/**
+ * Snapshot of frame and pass timings since profiling was enabled or the project was last built
+ */
+export interface ProfileSnapshot {
+  /**
+   * Frames skipped because no surface texture could be acquired
+   */
+  droppedFrames: number;
+  frame: TimingStats;
+  /**
+   * Frames rendered
+   */
+  frames: number;
+  /**
+   * Frames that took more than 1.5x the frame budget
+   */
+  lateFrames: number;
+  /**
+   * Per pass timings, in the order passes are run
+   */
+  passes: PassTiming[];
+  source: TimingSource;
+}
+/**
+ * Timing statistics over the profiler's rolling history, in milliseconds
+ */
+export interface TimingStats {
+  avg: number;
+  min: number;
+  p95: number;
+}
+
+export interface PassTiming {
+  /**
+   * Entry point of pass
+   */
+  entryPoint: string;
+  /**
+   * Shader file of pass
+   */
+  file: string;
+  stats: TimingStats;
+}
+
+/**
+ * Contents of a file in the virtual directory.
+ *
+ * example: ```ts "// plain text" { "base64": "iVBORw0KGgo..." } { "id": "...", "hash": "9f86d08..." } ```
+ */
+export type FileData =
+  | string
+  | {
+      base64: string;
+    }
+  | AssetRef;
 export type SupportedExtension = "wgsl" | "glsl" | "txt" | "md" | "json" | "csv" | "png" | "jpeg" | "mp3";
 
 export interface Project {
   config?: Config | null;
   files: Files;
   layout?: Layout | null;
+  /**
+   * Version of the project format. Projects are migrated to the current version when loaded, see `load_project`.
+   */
+  schemaVersion?: number;
 }
 
 export interface Config {
+  /**
+   * Frame rate limit, or 0 for no limit
+   */
   limitFps?: number;
   perfLevel?: PerformanceLevel | null;
+  /**
+   * Multiplier applied to the canvas size in device pixels, i.e. 0.5 renders at half resolution
+   */
+  renderScale?: number;
 }
 /**
- * Gputoy virtual directory. Each file in the map has its path from root as key, including file name and extension
+ * Gputoy virtual directory. Each file in the map has its normalized path from root as key, including file name and extension. The other path fields of a file are derived from its key.
  *
- * example: ```ts map: { "shaders/main.wgsl": { "data": "...", "dir": "shaders/", "fileName": "main", "extension": "wgsl", } } ```
+ * example: ```ts map: { "/shaders/main.wgsl": { "data": "...", "dir": "shaders", "fileName": "main", "extension": "wgsl", } } ```
  */
 
 export interface Files {
@@ -141,11 +365,11 @@ export interface Files {
 
 export interface File {
   /**
-   * Contents of file in plain text
+   * Contents of file, either text or binary
    */
-  data: string;
+  data: FileData;
   /**
-   * File path starting at / (project root)
+   * Directory of file from project root, without leading or trailing slash. Empty if file is at the root.
    */
   dir: string;
   /**
@@ -161,38 +385,80 @@ export interface File {
    */
   fileName: string;
 }
-
-export interface Layout {
+/**
+ * Reference to bytes uploaded to gpu-back
+ */
+export interface AssetRef {
   /**
-   * Panel settings for editorPanel
+   * Hex encoded sha256 of the bytes
    */
-  editorPanel: PanelState;
+  hash: string;
   /**
-   * Currently opened file index within workspace
+   * Id of the asset, as used in `/asset/{id}`
    */
-  fileIndex?: number | null;
+  id: string;
+}
+
+export interface Layout {
   /**
    * Is the left side status panel open
    */
   isStatusOpen: boolean;
   /**
-   * Panel settings for projectPanel
-   */
-  projectPanel: PanelState;
-  /**
-   * Panel settings for resourcePanel
+   * Node taking up the whole editor
    */
-  resourcePanel: PanelState;
-  /**
-   * List of file identifiers which is open in workspace. Order of identifiers in vec is the order it is listed in the editor.
-   */
-  workspace: string[];
+  root: LayoutNode;
 }
 
-export interface PanelState {
-  show: boolean;
-  size: number;
-}
+export type LayoutNode =
+  | {
+      children: LayoutNode[];
+      direction: SplitDirection;
+      /**
+       * Percent of the parent split taken up
+       */
+      size: number;
+      ty: "split";
+    }
+  | {
+      show: boolean;
+      /**
+       * Percent of the parent split taken up while shown
+       */
+      size: number;
+      state: PanelState;
+      ty: "panel";
+    };
+
+/**
+ * Direction a split lays out its children in
+ */
+export type SplitDirection = "horizontal" | "vertical";
+
+/**
+ * Kind of a panel along with whatever it keeps track of
+ */
+export type PanelState =
+  | {
+      /**
+       * Currently opened file index within workspace
+       */
+      fileIndex?: number | null;
+      ty: "editorPanel";
+      /**
+       * List of file identifiers which is open in workspace. Order of identifiers in vec is the order it is listed in the editor.
+       */
+      workspace: string[];
+    }
+  | {
+      ty: "projectPanel";
+    }
+  | {
+      ty: "resourcePanel";
+    }
+  | {
+      ty: "viewportPanel";
+    };
 
 export interface ProjectResponse {
   authorId?: string | null;
@@ -204,6 +470,10 @@ export interface ProjectResponse {
   id: string;
   layout?: Layout | null;
   published: boolean;
+  /**
+   * Version of the project format, see `Project::schema_version`
+   */
+  schemaVersion?: number;
   title: string;
   updatedAt: string;
 }
@@ -216,6 +486,10 @@ export interface ProjectUpsert {
   layout?: Layout | null;
   published: boolean;
   title: string;
+  /**
+   * Revision of the project the update was made on. When given, the update is rejected if the project was updated since.
+   */
+  updatedAt?: string | null;
 }
 
 export type LineNumberCOnfig = "on" | "interval" | "relative" | "off";
@@ -233,6 +507,12 @@ export interface UserConfig {
   keybinds: {
     [k: string]: FilteredAction;
   };
+  /**
+   * Macros by name, run with `Action::RunMacro`
+   */
+  macros?: {
+    [k: string]: Macro;
+  };
   theme: {
     [k: string]: string;
   };
@@ -250,9 +530,19 @@ export interface UserGeneralConfig {
   resourcePanelSize: number;
 }
 
-export interface FilteredAction {
+/**
+ * Actions run one after the other, i.e. reset, switch param preset and play
+ */
+export interface Macro {
+  steps: MacroStep[];
+}
+
+export interface MacroStep {
   action: Action;
-  condition?: string | null;
+  /**
+   * Milliseconds to wait before running the action
+   */
+  delay?: number | null;
 }
 
 export interface UserInfoResponse {
@@ -267,4 +557,160 @@ export interface UserInfoResponse {
   image?: string | null;
   updatedAt: string;
   username: string;
-}
\ No newline at end of file
+  /**
+   * Keybinds of the saved config that conflict. They are saved anyway, as the user may be halfway through rebinding them.
+   */
+  warnings?: KeybindConflict[];
+}
+/**
+ * Two keybinds that trigger different actions on the same keys in some context, see [`find_conflicts`]
+ */
+export interface KeybindConflict {
+  /**
+   * Keybind as written in the config
+   */
+  keybind: string;
+  /**
+   * Keybind it conflicts with, as written in the config
+   */
+  other: string;
+  /**
+   * Platform the two only conflict on, if they don't on every platform
+   */
+  platform?: Platform | null;
+  /**
+   * Whether `other` starts with `keybind` or the other way around, rather than the two being the same
+   */
+  prefix: boolean;
+}
+/**
+ * Platform a keybind is pressed on, which decides what `mod` is
+ */
+export type Platform = "mac" | "other";
+/**
+ * Message posted from the main thread to the worker
+ */
+export type WorkerRequest =
+  | {
+      c: Project;
+      ty: "build";
+    }
+  | {
+      c: ResizeRequest;
+      ty: "resize";
+    }
+  | {
+      c: InputEvent;
+      ty: "input";
+    }
+  | {
+      c: ParamUpdate;
+      ty: "setParam";
+    }
+  | {
+      c: {
+        id: number;
+        options: CaptureOptions;
+      };
+      ty: "capture";
+    }
+  | {
+      ty: "start";
+    }
+  | {
+      ty: "stop";
+    }
+  | {
+      c: {
+        hidden: boolean;
+      };
+      ty: "visibility";
+    }
+  | {
+      c: boolean;
+      ty: "setProfiling";
+    }
+  | {
+      ty: "restart";
+    };
+/**
+ * Input forwarded from the canvas
+ */
+export type InputEvent = {
+  c: {
+    x: number;
+    y: number;
+  };
+  ty: "pointerMove";
+};
+
+export interface ResizeRequest {
+  height: number;
+  /**
+   * Device pixel ratio of the page, which workers have no access to
+   */
+  pixelRatio: number;
+  /**
+   * Size of the canvas in css pixels
+   */
+  width: number;
+}
+/**
+ * Values written directly into a resource, i.e. to tweak uniforms from the ui
+ */
+
+export interface ParamUpdate {
+  /**
+   * Offset into the resource in bytes
+   */
+  offset?: number;
+  /**
+   * Name the resource is declared with
+   */
+  resource: string;
+  values: number[];
+}
+
+export interface CaptureOptions {
+  /**
+   * Height of the captured image in pixels
+   */
+  height: number;
+  /**
+   * Time in seconds to render at, or the current time if not given
+   */
+  time?: number | null;
+  /**
+   * Width of the captured image in pixels
+   */
+  width: number;
+}
+
+/**
+ * Message posted from the worker to the main thread
+ */
+export type WorkerResponse =
+  | {
+      ty: "ready";
+    }
+  | {
+      c: Event;
+      ty: "event";
+    }
+  | {
+      c: {
+        id: number;
+        /**
+         * Encoded png, which arrives as a `Uint8Array`
+         */
+        png: Uint8Array;
+      };
+      ty: "captured";
+    }
+  | {
+      c: {
+        id?: number | null;
+        message: string;
+      };
+      ty: "failed";
+    };
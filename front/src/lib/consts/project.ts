@@ -5,22 +5,41 @@ import type { Config, Files, Layout } from "src/generated/types"
  */
 export const DEFAULT_LAYOUT: Layout = {
     isStatusOpen: true,
-    fileIndex: 0,
-    workspace: ["/shaders/main.wgsl", "/run.json"] as string[],
-    projectPanel: {
-        show: true,
-        size: 20,
+    root: {
+        ty: 'split',
+        size: 100,
+        direction: 'horizontal',
+        children: [
+            { ty: 'panel', size: 20, show: true, state: { ty: 'projectPanel' } },
+            {
+                ty: 'split',
+                size: 80,
+                direction: 'horizontal',
+                children: [
+                    {
+                        ty: 'split',
+                        size: 50,
+                        direction: 'vertical',
+                        children: [
+                            { ty: 'panel', size: 50, show: true, state: { ty: 'viewportPanel' } },
+                            { ty: 'panel', size: 50, show: true, state: { ty: 'resourcePanel' } },
+                        ]
+                    },
+                    {
+                        ty: 'panel',
+                        size: 50,
+                        show: true,
+                        state: {
+                            ty: 'editorPanel',
+                            fileIndex: 0,
+                            workspace: ["/shaders/main.wgsl", "/run.json"],
+                        }
+                    },
+                ]
+            },
+        ]
     },
-    editorPanel: {
-        show: true,
-        size: 50,
-    },
-    resourcePanel: {
-        show: true,
-        size: 50,
-    }
-
-} as const
+}
 export const DEFAULT_CONFIG: Config = {} as const
 export const DEFAULT_FILES: Files = {
     map: {
@@ -39,7 +58,7 @@ export const DEFAULT_FILES: Files = {
 
         "/shaders/extras/component.wgsl": {
             "data": "...",
-            "dir": "extras",
+            "dir": "shaders/extras",
             "fileName": "component",
             "extension": "wgsl",
         },
@@ -1,5 +1,6 @@
 import type { Keybinds } from '$lib/core/input'
-import type { UserEditorConfig, UserGeneralConfig } from 'src/generated/types'
+import actionRegistry from 'src/generated/actions.json'
+import type { ActionRegistry, UserEditorConfig, UserGeneralConfig } from 'src/generated/types'
 
 export const DEFAULT_USER_GENERAL_CONFIG: UserGeneralConfig = {
     projectPanelSize: 12,
@@ -13,68 +14,13 @@ export const DEFAULT_USER_EDITOR_CONFIG: UserEditorConfig = {
     fontSize: 12,
 } as const
 
-export const DEFAULT_USER_KEYBINDS: Keybinds = {
-
-    'C-g': {
-        action: { ty: 'toggleConsole' }
-    },
-    'C-q': {
-        action: {
-            ty: 'togglePanel',
-            c: 'projectPanel'
-        }
-    },
-    'C-e': {
-        action: {
-            ty: 'togglePanel',
-            c: 'editorPanel'
-        }
-    },
-    'C-r': {
-        action: {
-            ty: 'togglePanel',
-            c: 'resourcePanel'
-        }
-    },
-    'C-j': {
-        action: {
-            ty: 'previousDocument'
-        }
-    },
-    'C-k': {
-        action: {
-            ty: 'nextDocument'
-        }
-    },
-    'C-S-d': {
-        action: {
-            ty: 'toggleDebugPanel'
-        }
-    },
-    'C-s': {
-        action: {
-            ty: 'saveAllFiles'
-        }
-    },
-    'C-u': {
-        action: {
-            ty: 'closeFile'
-        }
-    },
-    'C-S-f': {
-        action: {
-            ty: 'fork'
-        }
-    },
-    'C-S-g': {
-        action: {
-            ty: 'publish'
-        },
-        condition: 'userLoggedIn'
-    },
-
+/**
+ * Every action with its title, description and default keybinds, generated
+ * from gpu-common by gpu-schemas
+ */
+export const ACTION_REGISTRY = actionRegistry as ActionRegistry
 
-} as const
+export const DEFAULT_USER_KEYBINDS: Keybinds = ACTION_REGISTRY.defaultKeymap
 
 /**
  * 
@@ -1,7 +1,10 @@
-import { clearProject, wLayout } from "$stores/project"
+import { build, restart } from "$lib/core/context"
+import { clearProject, getProject, wLayout } from "$stores/project"
 import { wDebugPanel } from "$stores/ui"
+import { wUserMacros } from "$stores/userConfig"
 import { toast } from "@zerodevx/svelte-toast"
 import { isEqual } from "lodash"
+import { get } from "svelte/store"
 import type { Action, FilteredAction, Panel, ShiftPaneArgs } from "src/generated/types"
 
 const actionHistory: Action[] = []
@@ -64,6 +67,7 @@ export function pushAction(action: Action) {
         case 'toggleDebugPanel': toggleDebugPanel(); break
         case 'closeFile': closeCurrentFile(); break
         case 'closeProject': closeProject(); break
+        case 'runMacro': runMacro(action.c); break
 
         /** @ts-ignore */
         // There may be a case in the future where a new variant is added
@@ -98,10 +102,19 @@ function closeCurrentFile() {
     wLayout.closeWorkspaceFile()
 }
 
+/**
+ * Builds are incremental, so only changed shaders are recreated
+ * and resources keep their contents
+ */
 function rebuildProject() {
+    build(getProject())
 }
 
+/**
+ * Time and frame count keep running across builds until reset
+ */
 function resetProject() {
+    restart()
 }
 
 function toggleConsole() {
@@ -116,11 +129,38 @@ function toggleDebugPanel() {
 }
 
 function shiftPanel(c: ShiftPaneArgs) {
+    wLayout.shiftPanel(c)
 }
 
-function focusPane(c: string) {
+function focusPane(panel: Panel) {
+    wLayout.togglePanel(panel, true)
+    document.querySelector<HTMLElement>(`[data-panel="${panel}"]`)?.focus()
 }
 
 function closeProject() {
     clearProject()
-}
\ No newline at end of file
+}
+
+/**
+ * Runs the steps of the user's macro in order, waiting out their delays.
+ * Nested macros finish before the next step starts.
+ * @param name name of the macro in the user config
+ * @param running macros already running, which are not run again
+ */
+async function runMacro(name: string, running: string[] = []) {
+    const userMacro = get(wUserMacros)[name]
+    if (!userMacro) {
+        toast.push('Macro not found: ' + name)
+        return
+    }
+    // Configs are checked for recursion when saved, but not when edited locally
+    if (running.includes(name)) {
+        toast.push('Macro runs itself: ' + [...running, name].join(' -> '))
+        return
+    }
+    for (const step of userMacro.steps) {
+        if (step.delay) await new Promise(resolve => setTimeout(resolve, step.delay!))
+        if (step.action.ty === 'runMacro') await runMacro(step.action.c, [...running, name])
+        else pushAction(step.action)
+    }
+}
@@ -188,4 +188,47 @@ export async function updateProject(args: types.ProjectUpsert): Response<types.P
         }
     }
     return project
-}
\ No newline at end of file
+}
+/**
+ * GET /project/{projectid}/archive
+ * @param projectid 
+ * @returns Zip archive of the project with its assets | error
+ */
+export async function getProjectArchive(projectid: string): Response<Blob> {
+    const archiveRes = await fetch(vars.API_PATH + 'project/' + projectid + '/archive', {
+        method: 'GET',
+        credentials: 'include'
+    })
+
+    if (archiveRes.status != 200) {
+        const error = await archiveRes.json()
+        return {
+            message: error.message,
+            status: archiveRes.status
+        }
+    }
+    return archiveRes.blob()
+}
+
+/**
+ * POST /project/archive
+ * @param archive Zip archive as returned by getProjectArchive
+ * @returns ProjectResponse of the new project | error
+ */
+export async function importProjectArchive(archive: Blob): Response<types.ProjectResponse> {
+    const projectRes = await fetch(vars.API_PATH + 'project/archive', {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/zip' },
+        credentials: 'include',
+        body: archive
+    })
+
+    const project = await projectRes.json()
+    if (projectRes.status != 200) {
+        return {
+            message: project.message,
+            status: projectRes.status
+        }
+    }
+    return project
+}
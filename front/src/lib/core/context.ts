@@ -1,43 +1,376 @@
 import { browser } from '$app/environment'
-import type { Project } from 'src/generated/types'
+import vars from '$lib/consts/vars'
+import { pushGpuError, wLastBuild, wLastFrame, wRunning, wStats } from '$stores/gpu'
+import { setLocalProject, updateLocalProject } from '$stores/project'
+import type {
+  Event,
+  GpuError,
+  InputEvent,
+  ParamUpdate,
+  ProfileSnapshot,
+  Project
+} from 'src/generated/types'
 
-import { Context, default as init_module } from '../../../pkg/gpu_wasm'
+import { Context, type WasmError } from '../../../pkg/gpu_wasm'
+import { GpuWorker, offscreenSupported } from './gpuWorker'
+import { initModule } from './wasm'
+import { toast } from '@zerodevx/svelte-toast'
+import {
+  NativeHost,
+  nativeSupported,
+  type OpenedProject,
+  type SyncError,
+  type SyncStatus
+} from './nativeHost'
 
+/**
+ * Context rendering on the main thread. Only used when the browser can not render
+ * to a canvas from a worker, in which case `worker` is set instead.
+ */
 var context: Context | undefined = undefined
+var worker: GpuWorker | undefined = undefined
+/** Set instead of either when hosted by the native app, which renders natively */
+var native: NativeHost | undefined = undefined
+
+/**
+ * Current main thread context, if initialized. Unlike the default export, this is
+ * never stale. Undefined while rendering in a worker.
+ */
+export function getContext(): Context | undefined {
+  return context
+}
+
+/** Whether the context renders in a worker */
+export function inWorker(): boolean {
+  return worker !== undefined
+}
+
+/** Whether the context renders natively in the desktop app */
+export function inNative(): boolean {
+  return native !== undefined
+}
+
+/** Whether there is anything to render with. The native app's webview has no webgpu. */
+function supported(): boolean {
+  return browser && (nativeSupported() || "gpu" in navigator)
+}
+
+/**
+ * Logs an error thrown by the wasm module. Gpu errors are also reported through
+ * the error event, so they are not pushed to the store again here.
+ */
+function logError(context: string, e: unknown) {
+  const error = e as WasmError
+  console.error(`js:context:${context}:error`, error.kind ?? 'unknown', error.message ?? e)
+}
+
+function handleError(error: GpuError) {
+  console.error("js:context:gpu:error", error)
+  pushGpuError(error)
+}
+
+function handleEvent(event: Event) {
+  switch (event.ty) {
+    case 'frame': return wLastFrame.set(event.c)
+    case 'build': return wLastBuild.set(event.c)
+    case 'error': return handleError(event.c)
+    case 'stats': return wStats.set(event.c)
+  }
+}
+
+function handleVisibilityChange() {
+  worker?.post({ ty: 'visibility', c: { hidden: document.hidden } })
+}
 
 export async function init() {
-  if (!browser || !("gpu" in navigator)) return
-  await init_module()
+  if (!supported()) return
+  if (nativeSupported()) {
+    await initNative()
+    return
+  }
+  if (offscreenSupported()) {
+    await initWorker()
+    return
+  }
+  await initModule()
   context = await new Context()
+  context.onFrame(wLastFrame.set)
+  context.onBuild(wLastBuild.set)
+  context.onError(handleError)
+  context.onStats(wStats.set)
   console.log("js:context:init", context)
 }
 
+async function initWorker() {
+  const canvas = document.createElement('canvas')
+  canvas.id = 'canvas'
+  document.getElementById('canvas-root')?.appendChild(canvas)
+  worker = new GpuWorker(canvas, handleEvent)
+  try {
+    await worker.ready
+  } catch (e) {
+    console.error("js:context:init:error", e)
+    worker.terminate()
+    worker = undefined
+    canvas.remove()
+    return
+  }
+  // Workers can not tell when the page is hidden, so that is passed along
+  document.addEventListener('visibilitychange', handleVisibilityChange)
+  console.log("js:context:init:worker")
+}
+
+async function initNative() {
+  const canvas = document.createElement('canvas')
+  canvas.id = 'canvas'
+  document.getElementById('canvas-root')?.appendChild(canvas)
+  try {
+    native = await NativeHost.connect(canvas, handleEvent, updateLocalProject)
+  } catch (e) {
+    logError('init', e)
+    canvas.remove()
+    return
+  }
+  console.log("js:context:init:native")
+  try {
+    const opened = await native.initialProject()
+    if (opened) setLocalProject(opened)
+  } catch (e) {
+    logError('openProject', e)
+  }
+}
+
+/**
+ * Opens the project in directory `path`, only possible in the native app. The
+ * project is rebuilt whenever its files change on disk.
+ */
+export async function openProject(path: string): Promise<OpenedProject | undefined> {
+  if (!native) return
+  try {
+    const opened = await native.openProject(path)
+    setLocalProject(opened)
+    return opened
+  } catch (e) {
+    logError('openProject', e)
+  }
+}
+
+/**
+ * Logs an error thrown by a sync command. Conflicts are shown along with the
+ * files changed on both sides, so nothing is overwritten unknowingly.
+ */
+function logSyncError(context: string, e: unknown) {
+  const error = e as SyncError
+  console.error(`js:context:sync:${context}:error`, error.message ?? e, error.status)
+  toast.push(error.message ?? `Could not ${context} project`)
+}
+
+/**
+ * Logs in to sync the open project with the website, only possible in the
+ * native app
+ */
+export async function syncLogin(usernameOrEmail: string, password: string) {
+  if (!native) return
+  try {
+    await native.syncLogin(vars.API_PATH, usernameOrEmail, password)
+  } catch (e) {
+    logSyncError('login', e)
+  }
+}
+
+/** Files changed locally and remotely since the open project was last synced */
+export async function syncStatus(): Promise<SyncStatus | undefined> {
+  if (!native) return
+  try {
+    return await native.syncStatus()
+  } catch (e) {
+    logSyncError('status', e)
+  }
+}
+
+/**
+ * Applies remote changes to the open project, linking it to project `id` if
+ * given. Stops if both sides changed the same files, unless `force` is set.
+ */
+export async function syncPull(id: string | null = null, force = false): Promise<SyncStatus | undefined> {
+  if (!native) return
+  try {
+    return await native.syncPull(id, force)
+  } catch (e) {
+    logSyncError('pull', e)
+  }
+}
+
+/**
+ * Uploads the open project. Stops if the remote project changed since the last
+ * sync, unless `force` is set.
+ */
+export async function syncPush(force = false): Promise<SyncStatus | undefined> {
+  if (!native) return
+  try {
+    return await native.syncPush(force)
+  } catch (e) {
+    logSyncError('push', e)
+  }
+}
+
 export async function build(project: Project) {
+  if (native) {
+    // Outcome arrives as a build event
+    await native.build(project).catch(e => logError('build', e))
+    return
+  }
+  if (worker) {
+    // Outcome arrives as a build event
+    worker.post({ ty: 'build', c: project })
+    return
+  }
   try {
     await context?.build(project)
   } catch (e) {
-    console.error("js:context:build:error", e)
+    logError('build', e)
     return
   }
   console.log("js:context:build", project)
 }
 
+/** Starts the context's render loop, which renders every animation frame until paused */
+export function play() {
+  if (native) {
+    native.start().catch(e => logError('play', e))
+    wRunning.set(true)
+    return
+  }
+  if (worker) {
+    worker.post({ ty: 'start' })
+    wRunning.set(true)
+    return
+  }
+  try {
+    context?.start()
+  } catch (e) {
+    logError('play', e)
+    return
+  }
+  wRunning.set(context?.isRunning() ?? false)
+}
+
+export function pause() {
+  native?.stop().catch(e => logError('pause', e))
+  worker?.post({ ty: 'stop' })
+  context?.stop()
+  wRunning.set(false)
+}
+
+/** Renders a single frame, i.e. to step through frames while paused */
 export async function render() {
-  await context?.render()
+  if (native) {
+    await native.render().catch(e => logError('render', e))
+    return
+  }
+  if (worker) {
+    console.warn("js:context:render:worker", "frames can only be rendered by the render loop in a worker")
+    return
+  }
+  try {
+    await context?.render()
+  } catch (e) {
+    logError('render', e)
+    return
+  }
   console.log("js:context:render")
 }
 
+/** Resizes the canvas to `width` by `height` css pixels */
+export function resize(width: number, height: number, pixelRatio: number) {
+  native?.resize({ width, height, pixelRatio }).catch(e => logError('resize', e))
+  worker?.post({ ty: 'resize', c: { width, height, pixelRatio } })
+  context?.resize(width, height, pixelRatio)
+}
+
+export function input(event: InputEvent) {
+  native?.input(event).catch(e => logError('input', e))
+  worker?.post({ ty: 'input', c: event })
+  context?.input(event)
+}
+
+export async function setParam(update: ParamUpdate) {
+  if (native) {
+    await native.setParam(update).catch(e => logError('setParam', e))
+    return
+  }
+  if (worker) {
+    worker.post({ ty: 'setParam', c: update })
+    return
+  }
+  try {
+    await context?.setParam(update)
+  } catch (e) {
+    logError('setParam', e)
+  }
+}
+
+export async function capture(width: number, height: number, time?: number): Promise<Blob | undefined> {
+  try {
+    if (native) return await native.capture({ width, height, time })
+    if (worker) return await worker.capture({ width, height, time })
+    return await context?.capture({ width, height, time })
+  } catch (e) {
+    logError('capture', e)
+  }
+}
+
+export async function setProfiling(enabled: boolean) {
+  await native?.setProfiling(enabled).catch(e => logError('setProfiling', e))
+  worker?.post({ ty: 'setProfiling', c: enabled })
+  await context?.setProfiling(enabled)
+}
+
+/** Restarts time and frame count from zero, which builds keep running */
+export async function restart() {
+  await native?.restart().catch(e => logError('restart', e))
+  worker?.post({ ty: 'restart' })
+  await context?.restart()
+}
+
+/** Profiler snapshot. Only available on the main thread, workers report stats as events */
+export async function profile(): Promise<ProfileSnapshot | undefined> {
+  try {
+    return await context?.profile()
+  } catch (e) {
+    logError('profile', e)
+  }
+}
+
+function free() {
+  pause()
+  context?.free()
+  context = undefined
+  if (native) {
+    native.disconnect()
+    native = undefined
+    document.getElementById('canvas')?.remove()
+  }
+  if (worker) {
+    document.removeEventListener('visibilitychange', handleVisibilityChange)
+    worker.terminate()
+    worker = undefined
+    // The canvas was transferred, so a new one is needed for the next worker
+    document.getElementById('canvas')?.remove()
+  }
+}
+
 export async function reset() {
-  if (!browser || !("gpu" in navigator)) return
+  if (!supported()) return
   console.log("js:context:reset")
-  context?.free()
+  free()
   await init()
 }
 
 export async function stop() {
-  if (!browser || !("gpu" in navigator)) return
+  if (!supported()) return
   console.log("js:context:stop")
-  context?.free()
+  free()
 }
 
-export default context
\ No newline at end of file
+export default context
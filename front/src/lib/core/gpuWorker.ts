@@ -0,0 +1,97 @@
+import type {
+  CaptureOptions,
+  Event,
+  WorkerRequest,
+  WorkerResponse
+} from 'src/generated/types'
+
+type PendingCapture = {
+  resolve: (png: Blob) => void
+  reject: (reason: Error) => void
+}
+
+/** Whether the browser can hand a canvas over to a worker */
+export function offscreenSupported(): boolean {
+  return 'transferControlToOffscreen' in HTMLCanvasElement.prototype
+}
+
+/**
+ * Main thread side of a gpu context running in a dedicated worker, so the editor
+ * stays responsive no matter how heavy the shaders are.
+ *
+ * Control of `canvas` is transferred to the worker, after which it can only be
+ * drawn to from there.
+ */
+export class GpuWorker {
+  /** Resolves once the worker created its context, rejects if that failed */
+  readonly ready: Promise<void>
+
+  private worker: Worker
+  private nextId = 0
+  private captures = new Map<number, PendingCapture>()
+
+  constructor(canvas: HTMLCanvasElement, onEvent: (event: Event) => void) {
+    this.worker = new Worker(new URL('./worker.ts', import.meta.url), { type: 'module' })
+    let isReady = false
+    let setReady: () => void
+    let setFailed: (reason: Error) => void
+    this.ready = new Promise((resolve, reject) => {
+      setReady = resolve
+      setFailed = reject
+    })
+
+    this.worker.onmessage = (e: MessageEvent<WorkerResponse>) => {
+      const response = e.data
+      switch (response.ty) {
+        case 'ready':
+          isReady = true
+          setReady()
+          break
+        case 'event':
+          onEvent(response.c)
+          break
+        case 'captured': {
+          const png = new Blob([response.c.png], { type: 'image/png' })
+          this.takeCapture(response.c.id)?.resolve(png)
+          break
+        }
+        case 'failed': {
+          const error = new Error(response.c.message)
+          if (response.c.id != null) this.takeCapture(response.c.id)?.reject(error)
+          else if (!isReady) setFailed(error)
+          else console.error("js:worker:error", error)
+          break
+        }
+      }
+    }
+
+    const offscreen = canvas.transferControlToOffscreen()
+    this.worker.postMessage({ canvas: offscreen }, [offscreen])
+  }
+
+  post(request: WorkerRequest) {
+    this.worker.postMessage(request)
+  }
+
+  capture(options: CaptureOptions): Promise<Blob> {
+    const id = this.nextId++
+    return new Promise((resolve, reject) => {
+      this.captures.set(id, { resolve, reject })
+      this.post({ ty: 'capture', c: { id, options } })
+    })
+  }
+
+  terminate() {
+    this.worker.terminate()
+    for (const capture of this.captures.values()) {
+      capture.reject(new Error('Worker was terminated'))
+    }
+    this.captures.clear()
+  }
+
+  private takeCapture(id: number): PendingCapture | undefined {
+    const capture = this.captures.get(id)
+    this.captures.delete(id)
+    return capture
+  }
+}
@@ -1,7 +1,7 @@
 import { isActionEqual, pushAction } from "$lib/core/actions"
 import { wLastInputAction } from '$stores/input'
 import { wUserConfigOpen, wUserModalOpen } from "$stores/ui"
-import { wUserKeybinds } from "$stores/userConfig"
+import { dPlatformKeymap } from "$stores/userConfig"
 import type { Action, FilteredAction } from "src/generated/types"
 import { get } from "svelte/store"
 
@@ -37,38 +37,48 @@ export function findActionBind(find: Action | undefined, keybinds: Keybinds): st
 /**
  * Transforms keyboard event to canonical keycode 
  * @param ev Keyboard event
- * @returns Keybind. ex. 'C-k' is Ctrl+k, 'C-S-x' is Ctrl+Shft+x 
+ * @returns Keybind. ex. 'ctrl+k' is Ctrl+k, 'ctrl+shift+x' is Ctrl+Shft+x 
  */
 export function toKeyIdx(ev: KeyboardEvent): string {
-    return (ev.ctrlKey ? 'C-' : '')
-        + (ev.shiftKey ? 'S-' : '')
-        + (ev.altKey ? 'A-' : '')
-        + ev.key.toLowerCase()
+    return (ev.ctrlKey ? 'ctrl+' : '')
+        + (ev.altKey ? 'alt+' : '')
+        + (ev.shiftKey ? 'shift+' : '')
+        + (ev.metaKey ? 'meta+' : '')
+        + (ev.key === ' ' ? 'space' : ev.key.toLowerCase())
 }
 
+/**
+ * Chords pressed so far of a keybind made of several, like 'ctrl+k ctrl+s'
+ */
+let pendingChords = ''
+
 /**
  * 'keydown' handler, attached to document on mount within (dev)/+layout.svelte
  * @param ev KeyboardEvent from listener
  * @returns void 
  */
 function onKeyDown(ev: KeyboardEvent) {
-    if (ev.key === 'Control' || ev.key === 'Shift' || ev.key === 'Alt') return
-    if (!(ev.ctrlKey || ev.shiftKey || ev.altKey)) return
+    if (['Control', 'Shift', 'Alt', 'Meta'].includes(ev.key)) return
+    if (!(ev.ctrlKey || ev.shiftKey || ev.altKey || ev.metaKey || pendingChords)) return
 
     if (ev.key === 'Escape') {
         wUserConfigOpen.set(false)
         wUserModalOpen.set(false)
     }
 
-    let keyidx = toKeyIdx(ev)
-    let filteredAction = get(wUserKeybinds)[keyidx]
+    const keymap = get(dPlatformKeymap)
+    let keyidx = (pendingChords ? pendingChords + ' ' : '') + toKeyIdx(ev)
+    let filteredAction = keymap[keyidx]
+    const chordStarted = filteredAction === undefined
+        && Object.keys(keymap).some(keybind => keybind.startsWith(keyidx + ' '))
+    pendingChords = chordStarted ? keyidx : ''
     wLastInputAction.set({
         code: keyidx,
         action: filteredAction?.action
     })
     // TODO: use filtered action conditional
-    if (filteredAction === undefined) return
-    pushAction(filteredAction.action)
+    if (filteredAction !== undefined) pushAction(filteredAction.action)
+    else if (!chordStarted) return
 
     ev.preventDefault()
     ev.stopImmediatePropagation()
@@ -0,0 +1,53 @@
+import { browser } from "$app/environment"
+import type { Keybinds } from "$lib/core/input"
+import { initModule } from "$lib/core/wasm"
+import {
+    keybindConflicts,
+    keymap,
+    normalizeKeybind,
+    type KeybindConflict,
+    type Platform
+} from '../../../pkg/gpu_wasm'
+
+/**
+ * Platform keybinds are pressed on, which decides whether 'mod' is ctrl or cmd
+ */
+export const PLATFORM: Platform = browser && /Mac|iPhone|iPad/.test(navigator.platform)
+    ? 'mac'
+    : 'other'
+
+/**
+ * Applies the user's keybinds over the default keymap
+ * @param overrides keybinds from the user config
+ * @param platform if set, keybinds are written as pressed on it, as toKeyIdx does
+ * @returns merged keymap
+ */
+export async function mergeKeymap(overrides: Keybinds, platform?: Platform): Promise<Keybinds> {
+    await initModule()
+    return keymap(overrides, platform)
+}
+
+/**
+ * Finds keybinds that trigger different actions on the same keys under
+ * overlapping conditions. Keybinds that do not parse are left out.
+ * @param keybinds keybinds to check, defaults included
+ * @returns pairs of conflicting keybinds, as written in keybinds
+ */
+export async function findConflicts(keybinds: Keybinds): Promise<KeybindConflict[]> {
+    await initModule()
+    return keybindConflicts(keybinds)
+}
+
+/**
+ * Writes keybind in canonical form, i.e. 'C-S-x' as 'ctrl+shift+x'
+ * @param keybind keybind as written in the config
+ * @returns canonical keybind, or null if it does not parse
+ */
+export async function normalize(keybind: string): Promise<string | null> {
+    await initModule()
+    try {
+        return normalizeKeybind(keybind)
+    } catch {
+        return null
+    }
+}
@@ -0,0 +1,93 @@
+// Upgrades projects saved by older releases to the current format.
+// Mirrors gpu-common/src/migrate.rs, keep the two in sync.
+
+/**
+ * Version of the project format written by this release
+ */
+export const SCHEMA_VERSION = 2
+
+type Json = Record<string, any>
+
+/**
+ * Steps that upgrade a project from the version at their index to the next one
+ */
+const MIGRATIONS: ((project: Json) => void)[] = [v0LeadingSlashAndBytes, v1SplitTreeLayout]
+
+const BINARY_EXTENSIONS = ['png', 'jpeg', 'jpg', 'mp3']
+
+/**
+ * Upgrades project in place to SCHEMA_VERSION. Works on any object with files,
+ * layout and config, so both Project and ProjectResponse.
+ *
+ * @param project Project parsed from json, of any version
+ * @returns The same project, migrated
+ * @throws If the project was saved by a newer release
+ */
+export function migrateProject<T extends Json>(project: T): T {
+    const version = project.schemaVersion ?? 0
+    if (!Number.isInteger(version) || version < 0)
+        throw new Error(`Invalid schema version: ${version}`)
+    if (version > SCHEMA_VERSION)
+        throw new Error(`Project was saved by a newer release (schema version ${version})`)
+    MIGRATIONS.slice(version).forEach(migration => migration(project))
+    return Object.assign(project, { schemaVersion: SCHEMA_VERSION })
+}
+
+/**
+ * Keys of files started with a '/' only by convention, and binary files held
+ * their bytes as a base64 string rather than in a { base64 } object.
+ * Files open in the layout are referred to by key, so they get a '/' too.
+ */
+function v0LeadingSlashAndBytes(project: Json) {
+    const map: Json = project.files?.map ?? {}
+    project.files = {
+        map: Object.fromEntries(Object.entries(map).map(([key, file]) => {
+            const binary = BINARY_EXTENSIONS.includes(file.extension?.toLowerCase())
+            if (binary && typeof file.data === 'string')
+                file.data = { base64: file.data }
+            return [leadingSlash(key), file]
+        }))
+    }
+    const workspace = project.layout?.workspace
+    if (Array.isArray(workspace))
+        project.layout.workspace = workspace.map(leadingSlash)
+}
+
+/**
+ * Layouts had a fixed set of panels, each with its own field, rather than a
+ * tree of splits. The tree built mirrors how the editor laid those out.
+ * Layouts that already are a tree are kept.
+ */
+function v1SplitTreeLayout(project: Json) {
+    const old = project.layout
+    if (old == null || typeof old !== 'object' || 'root' in old) return
+    const panel = (ty: string, defaultSize: number, state: Json = {}) => ({
+        ty: 'panel',
+        size: old[ty]?.size ?? defaultSize,
+        show: old[ty]?.show ?? true,
+        state: { ...state, ty }
+    })
+    const split = (size: number, direction: string, children: Json[]) =>
+        ({ ty: 'split', size, direction, children })
+    const projectPanel = panel('projectPanel', 20)
+    const resource = panel('resourcePanel', 50)
+    const viewport = panel('viewportPanel', 100 - resource.size)
+    const editor = panel('editorPanel', 50, {
+        workspace: old.workspace ?? [],
+        fileIndex: old.fileIndex ?? null
+    })
+    project.layout = {
+        isStatusOpen: old.isStatusOpen ?? true,
+        root: split(100, 'horizontal', [
+            projectPanel,
+            split(100 - projectPanel.size, 'horizontal', [
+                split(100 - editor.size, 'vertical', [viewport, resource]),
+                editor
+            ])
+        ])
+    }
+}
+
+function leadingSlash(key: string): string {
+    return key.startsWith('/') ? key : '/' + key
+}
@@ -0,0 +1,215 @@
+import type {
+  CaptureOptions,
+  Event,
+  InputEvent,
+  ParamUpdate,
+  Project,
+  ResizeRequest
+} from 'src/generated/types'
+
+/** Files that changed on disk, as keys of `Files.map` */
+export type FileChanges = {
+  added: string[]
+  removed: string[]
+  modified: string[]
+  /** Whether the manifest changed, i.e. the project's config */
+  manifest: boolean
+}
+
+/** Project after files in its directory changed, which was rebuilt already */
+export type ProjectUpdate = {
+  changes: FileChanges
+  project: Project
+}
+
+/** Project read from a directory */
+export type OpenedProject = {
+  title: string
+  description?: string
+  project: Project
+}
+
+export type FileChange = 'added' | 'removed' | 'modified'
+
+/** Files changed on one side since the project was last synced */
+export type SyncChanges = {
+  files: Record<string, FileChange>
+  /** Whether the title, description, config or layout changed */
+  manifest: boolean
+}
+
+/** Changes on both sides of a synced project */
+export type SyncStatus = {
+  /** Remote project, or null if it was not pushed yet */
+  projectId: string | null
+  local: SyncChanges
+  remote: SyncChanges
+  /** Files changed differently on both sides */
+  conflicts: string[]
+}
+
+/** Error thrown by sync commands, along with the status if both sides conflict */
+export type SyncError = {
+  message: string
+  status: SyncStatus | null
+}
+
+/** Subset of the api gpu-tauri injects into its webview as `window.__TAURI__` */
+interface TauriApi {
+  invoke<T>(command: string, args?: Record<string, unknown>): Promise<T>
+  event: {
+    listen<T>(event: string, handler: (event: { payload: T }) => void): Promise<() => void>
+  }
+}
+
+function tauri(): TauriApi | undefined {
+  return (window as unknown as { __TAURI__?: TauriApi }).__TAURI__
+}
+
+/** Whether the page is hosted by the native app */
+export function nativeSupported(): boolean {
+  return tauri() !== undefined
+}
+
+/** Url the native app serves the last rendered frame from, as raw rgba */
+function frameUrl(): string {
+  // Custom schemes are mapped onto https on windows
+  return navigator.userAgent.includes('Windows')
+    ? 'https://gputoy.localhost/frame'
+    : 'gputoy://localhost/frame'
+}
+
+/**
+ * Gpu context rendering natively in the gpu-tauri app.
+ *
+ * The app renders on its own thread without a surface, so each frame is fetched
+ * once its frame event arrives and drawn to `canvas`.
+ */
+export class NativeHost {
+  private api: TauriApi
+  private canvas: HTMLCanvasElement
+  private unlisten?: () => void
+  /** Whether a frame is being fetched, in which case newer frame events are skipped */
+  private fetching = false
+
+  private constructor(api: TauriApi, canvas: HTMLCanvasElement) {
+    this.api = api
+    this.canvas = canvas
+  }
+
+  static async connect(
+    canvas: HTMLCanvasElement,
+    onEvent: (event: Event) => void,
+    onProjectUpdate: (update: ProjectUpdate) => void
+  ): Promise<NativeHost> {
+    const api = tauri()
+    if (!api) throw new Error('Not hosted by the native app')
+    const host = new NativeHost(api, canvas)
+    const unlistenEvents = await api.event.listen<Event>('gpu:event', ({ payload }) => {
+      if (payload.ty === 'frame') host.drawFrame()
+      onEvent(payload)
+    })
+    const unlistenUpdates = await api.event.listen<ProjectUpdate>('project:update', ({ payload }) => {
+      onProjectUpdate(payload)
+    })
+    host.unlisten = () => {
+      unlistenEvents()
+      unlistenUpdates()
+    }
+    return host
+  }
+
+  /** Opens the project in directory `path`, which is rebuilt whenever its files change */
+  openProject(path: string): Promise<OpenedProject> {
+    return this.api.invoke('open_project', { path })
+  }
+
+  /** Opens the project directory the app was started with, if any */
+  initialProject(): Promise<OpenedProject | null> {
+    return this.api.invoke('initial_project')
+  }
+
+  /** Logs in to the api at `api`, which projects are synced with */
+  syncLogin(api: string, usernameOrEmail: string, password: string): Promise<void> {
+    return this.api.invoke('sync_login', { api, usernameOrEmail, password })
+  }
+
+  syncStatus(): Promise<SyncStatus> {
+    return this.api.invoke('sync_status')
+  }
+
+  /** Applies remote changes to the open project, linking it to project `id` if given */
+  syncPull(id: string | null, force: boolean): Promise<SyncStatus> {
+    return this.api.invoke('sync_pull', { id, force })
+  }
+
+  syncPush(force: boolean): Promise<SyncStatus> {
+    return this.api.invoke('sync_push', { force })
+  }
+
+  build(project: Project): Promise<void> {
+    return this.api.invoke('build', { project })
+  }
+
+  render(): Promise<void> {
+    return this.api.invoke('render')
+  }
+
+  setParam(update: ParamUpdate): Promise<void> {
+    return this.api.invoke('set_param', { update })
+  }
+
+  async capture(options: CaptureOptions): Promise<Blob> {
+    const png = await this.api.invoke<number[]>('capture', { options })
+    return new Blob([new Uint8Array(png)], { type: 'image/png' })
+  }
+
+  resize(request: ResizeRequest): Promise<void> {
+    return this.api.invoke('resize', { request })
+  }
+
+  input(event: InputEvent): Promise<void> {
+    return this.api.invoke('input', { event })
+  }
+
+  setProfiling(enabled: boolean): Promise<void> {
+    return this.api.invoke('set_profiling', { enabled })
+  }
+
+  restart(): Promise<void> {
+    return this.api.invoke('restart')
+  }
+
+  start(): Promise<void> {
+    return this.api.invoke('start')
+  }
+
+  stop(): Promise<void> {
+    return this.api.invoke('stop')
+  }
+
+  disconnect() {
+    this.unlisten?.()
+    this.unlisten = undefined
+  }
+
+  private async drawFrame() {
+    if (this.fetching) return
+    this.fetching = true
+    try {
+      const response = await fetch(frameUrl())
+      if (response.status !== 200) return
+      const width = Number(response.headers.get('x-width'))
+      const height = Number(response.headers.get('x-height'))
+      const rgba = new Uint8ClampedArray(await response.arrayBuffer())
+      if (rgba.length !== width * height * 4) return
+      if (this.canvas.width !== width) this.canvas.width = width
+      if (this.canvas.height !== height) this.canvas.height = height
+      this.canvas.getContext('2d')?.putImageData(new ImageData(rgba, width, height), 0, 0)
+    } catch (e) {
+      console.error("js:native:frame:error", e)
+    } finally {
+      this.fetching = false
+    }
+  }
+}
@@ -0,0 +1,108 @@
+import { wRunning } from '$stores/gpu'
+import { get } from 'svelte/store'
+import type { RecordFormat, RecordOptions, RecordProgress } from '../../../pkg/gpu_wasm'
+import { getContext, inNative, inWorker, pause, play } from './context'
+
+export type { RecordFormat, RecordOptions, RecordProgress }
+
+/** Seconds between keyframes of webm recordings */
+const KEYFRAME_INTERVAL = 2
+
+var cancelled = false
+
+/**
+ * Renders a time range of the project offline and returns the encoded recording,
+ * or undefined if it was cancelled. The render loop is paused while recording.
+ *
+ * Webm recordings are encoded to VP8 with WebCodecs, which must be supported by the browser.
+ */
+export async function record(
+  options: RecordOptions,
+  onProgress?: (progress: RecordProgress) => void
+): Promise<Blob | Blob[] | undefined> {
+  const context = getContext()
+  if (!context) {
+    // Recording reads frames back synchronously with the main thread context
+    if (inWorker()) console.error("js:record:error", "recording is not supported while rendering in a worker")
+    if (inNative()) console.error("js:record:error", "recording is not supported in the native app yet")
+    return
+  }
+  cancelled = false
+  const wasRunning = get(wRunning)
+  pause()
+  try {
+    let progress: RecordProgress = await context.startRecording(options)
+    const encoder = options.format === 'webm' ? webmEncoder(options) : undefined
+    while (progress.frame < progress.total) {
+      if (cancelled) {
+        encoder?.close()
+        await context.cancelRecording()
+        console.log("js:record:cancelled")
+        return
+      }
+      const frame = await context.recordFrame()
+      progress = await context.recordingProgress()
+      if (frame && encoder) encoder.encode(frame, progress.frame - 1)
+      onProgress?.(progress)
+    }
+    if (encoder) {
+      for (const chunk of await encoder.flush()) {
+        await context.pushEncodedFrame(chunk.data, chunk.keyframe)
+      }
+    }
+    return await context.finishRecording()
+  } catch (e) {
+    console.error("js:record:error", e)
+    await context.cancelRecording()
+  } finally {
+    if (wasRunning) play()
+  }
+}
+
+/** Stops the recording in progress after the frame being rendered */
+export function cancelRecord() {
+  cancelled = true
+}
+
+type EncodedChunk = { data: Uint8Array, keyframe: boolean }
+
+/** Wraps a WebCodecs VP8 encoder. WebCodecs is not in the dom typings yet. */
+function webmEncoder(options: RecordOptions) {
+  const w = window as any
+  if (!('VideoEncoder' in w)) throw new Error('WebCodecs is not supported, can not record webm')
+  const chunks: EncodedChunk[] = []
+  const encoder = new w.VideoEncoder({
+    output: (chunk: any) => {
+      const data = new Uint8Array(chunk.byteLength)
+      chunk.copyTo(data)
+      chunks.push({ data, keyframe: chunk.type === 'key' })
+    },
+    error: (e: any) => console.error("js:record:encoder:error", e),
+  })
+  encoder.configure({
+    codec: 'vp8',
+    width: options.width,
+    height: options.height,
+    framerate: options.fps,
+  })
+  return {
+    encode(rgba: Uint8Array, index: number) {
+      const frame = new w.VideoFrame(rgba, {
+        format: 'RGBA',
+        codedWidth: options.width,
+        codedHeight: options.height,
+        timestamp: Math.round(index * 1_000_000 / options.fps),
+      })
+      encoder.encode(frame, { keyFrame: index % (options.fps * KEYFRAME_INTERVAL) === 0 })
+      frame.close()
+    },
+    async flush(): Promise<EncodedChunk[]> {
+      await encoder.flush()
+      encoder.close()
+      return chunks
+    },
+    close() {
+      encoder.close()
+    },
+  }
+}
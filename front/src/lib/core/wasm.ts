@@ -0,0 +1,12 @@
+import init_module from '../../../pkg/gpu_wasm'
+
+var moduleReady: Promise<unknown> | undefined = undefined
+
+/**
+ * Loads the wasm module on the main thread, once. Needed before calling any of
+ * its exports, even when the context itself renders in a worker.
+ */
+export function initModule(): Promise<unknown> {
+  moduleReady ??= init_module()
+  return moduleReady
+}
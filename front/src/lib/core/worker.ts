@@ -0,0 +1,29 @@
+/**
+ * Entry point of the dedicated worker running the gpu context, see `GpuWorker`.
+ *
+ * The first message carries the canvas transferred from the main thread, every
+ * message after that is a WorkerRequest handled by the wasm module.
+ */
+import type { WorkerRequest, WorkerResponse } from 'src/generated/types'
+import { WorkerContext, default as init_module } from '../../../pkg/gpu_wasm'
+
+var context: WorkerContext | undefined = undefined
+/** Requests received while the context was being created */
+const pending: WorkerRequest[] = []
+
+self.onmessage = async (e: MessageEvent) => {
+  if ('canvas' in e.data) {
+    try {
+      await init_module()
+      context = await new WorkerContext(e.data.canvas)
+    } catch (err) {
+      const response: WorkerResponse = { ty: 'failed', c: { message: String(err) } }
+      self.postMessage(response)
+      return
+    }
+    for (const request of pending.splice(0)) context.handleMessage(request)
+    return
+  }
+  if (context) context.handleMessage(e.data)
+  else pending.push(e.data)
+}
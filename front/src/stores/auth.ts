@@ -1,4 +1,5 @@
 import * as api from '$lib/core/api'
+import { syncLogin } from '$lib/core/context'
 import { toast } from '@zerodevx/svelte-toast'
 import type { UpdateUserInfoArgs, UserInfoResponse } from 'src/generated/types'
 import { get, writable } from "svelte/store"
@@ -13,6 +14,8 @@ export async function login(username_or_email: string, password: string) {
     toast.push(`Recieved ${response.status} status on login response. Message: ${response.message}`)
     return
   }
+  // The native app syncs projects outside of the webview, so it logs in too
+  syncLogin(username_or_email, password)
   getSession()
 }
 
@@ -48,6 +51,11 @@ export async function updateUser() {
     toast.push(`Recieved ${response.status} status on updateUser response. Message: ${response.message}`)
     return
   }
-  wUser.set(response)
-  setUserConfig(response.config ?? undefined)
+  // Conflicting keybinds are saved anyway, so they are only pointed out
+  const { warnings, ...updated } = response
+  for (const { keybind, other } of warnings ?? []) {
+    toast.push(`Saved, but keybind ${keybind} conflicts with ${other}`)
+  }
+  wUser.set(updated)
+  setUserConfig(updated.config ?? undefined)
 }
\ No newline at end of file
@@ -1,4 +1,23 @@
-import { readable, type Subscriber } from "svelte/store"
+import type { BuildEvent, FrameEvent, GpuError, ProfileSnapshot } from "src/generated/types"
+import { readable, writable, type Subscriber } from "svelte/store"
+
+/** Errors kept in wGpuErrors, older ones are dropped first */
+const MAX_GPU_ERRORS = 50
+
+/** Last frame rendered by the context */
+export const wLastFrame = writable<FrameEvent | null>(null)
+/** Outcome of the last build, with its diagnostics */
+export const wLastBuild = writable<BuildEvent | null>(null)
+/** Most recent gpu errors, newest last */
+export const wGpuErrors = writable<GpuError[]>([])
+/** Whether the context's render loop is running */
+export const wRunning = writable<boolean>(false)
+/** Latest profiler stats, only updated while profiling is enabled */
+export const wStats = writable<ProfileSnapshot | null>(null)
+
+export function pushGpuError(error: GpuError) {
+  wGpuErrors.update(errors => [...errors, error].slice(-MAX_GPU_ERRORS))
+}
 
 export const gpu_avail = readable<boolean>(false, (set: Subscriber<boolean>) => {
   // console.log("is set: ", ('gpu' in navigator))
@@ -15,7 +15,8 @@ export type FilesExtras = {
 export default makeEnhanced<Files, FilesExtras>(DEFAULT_FILES, function (files) {
 
     function newFile(file: File): string {
-        const fileid = `${file.dir}/${file.fileName}.${file.extension}`
+        // Same key the backend derives, i.e. /shaders/main.wgsl, or /Readme.md at the root
+        const fileid = '/' + [file.dir, `${file.fileName}.${file.extension}`].filter(Boolean).join('/')
         files.update(({ map }) => {
             map[fileid] = file
             return { map }
@@ -2,6 +2,8 @@ import { browser } from '$app/environment'
 import { DEFAULT_CONFIG, DEFAULT_FILES, DEFAULT_LAYOUT } from '$lib/consts/project'
 import * as api from '$lib/core/api'
 import context, { init } from '$lib/core/context'
+import { migrateProject, SCHEMA_VERSION } from '$lib/core/migrate'
+import type { OpenedProject, ProjectUpdate } from '$lib/core/nativeHost'
 import { toast } from '@zerodevx/svelte-toast'
 import debounce from 'lodash/debounce'
 import generate from 'project-name-generator'
@@ -66,6 +68,7 @@ export const wProjectMeta = writable<ProjectMeta>({
  */
 export function getProject(): Project {
     return {
+        schemaVersion: SCHEMA_VERSION,
         files: get(wFiles),
         layout: get(wLayout),
         config: get(wConfig),
@@ -89,6 +92,7 @@ export const dProject = derived(
     ([files, config, layout, id, meta]): ProjectResponse | null => {
         if (!id) return null
         return {
+            schemaVersion: SCHEMA_VERSION,
             id,
             title: meta.title,
             description: meta.description,
@@ -163,7 +167,7 @@ export async function loadProject(projectId: string) {
             toast.push('Project not found in local storage: ' + projectId)
             return
         }
-        setProject(JSON.parse(project), true)
+        loadMigrated(JSON.parse(project))
         return
     }
     const response = await api.getProject(projectId)
@@ -172,7 +176,18 @@ export async function loadProject(projectId: string) {
         toast.push(`Recieved ${response.status} status on getProject response. Message: ${response.message}`)
         return
     }
-    setProject(response, true)
+    loadMigrated(response)
+}
+
+/**
+ * Sets project saved by any release, if it can be migrated
+ */
+function loadMigrated(project: ProjectResponse) {
+    try {
+        setProject(migrateProject(project), true)
+    } catch (e) {
+        toast.push(`Could not load project: ${(e as Error).message}`)
+    }
 }
 
 
@@ -199,6 +214,14 @@ export async function loadAllProjects(): Promise<ProjectResponse[]> {
         .map(k => localStorage.getItem(k))
         .filter(p => !!p)
         .map(k => JSON.parse(k!))
+        // projects saved by newer releases are left out
+        .flatMap(p => {
+            try {
+                return [migrateProject(p)]
+            } catch {
+                return []
+            }
+        })
     return [...remoteProjects, ...localProjects]
 }
 
@@ -275,6 +298,35 @@ export function setProject(project: ProjectResponse, resetContext: boolean = fal
         init()
     }
 }
+/**
+ * Sets a project opened from a directory by the native app, which has built it
+ * already
+ */
+export function setLocalProject(opened: OpenedProject) {
+    const { title, description, project } = opened
+    wFiles.set(project.files)
+    wConfig.set(project.config ?? DEFAULT_CONFIG)
+    wLayout.set(project.layout ?? DEFAULT_LAYOUT)
+    wProjectMeta.update(meta => ({ ...meta, title, description }))
+}
+
+/**
+ * Applies changes to the files of a project opened from a directory, reporting
+ * files added and removed on disk
+ */
+export function updateLocalProject(update: ProjectUpdate) {
+    const { changes, project } = update
+    wFiles.set(project.files)
+    if (changes.manifest) {
+        wConfig.set(project.config ?? DEFAULT_CONFIG)
+        wLayout.set(project.layout ?? DEFAULT_LAYOUT)
+    }
+    if (changes.added.length > 0)
+        toast.push(`Added ${changes.added.join(', ')}`)
+    if (changes.removed.length > 0)
+        toast.push(`Removed ${changes.removed.join(', ')}`)
+}
+
 export function clearProject() {
     // flush any pending saves to local storage
     writeToLocalStorage.flush()
@@ -1,16 +1,76 @@
-
 import { DEFAULT_LAYOUT } from "$lib/consts/project"
 import type { GeneralConfigKey } from "$lib/consts/userConfig"
 import { wUserGeneralConfig } from "$stores/userConfig"
-import type { Layout, Panel } from "src/generated/types"
+import type { Layout, LayoutNode, Panel, PanelState, ShiftPaneArgs } from "src/generated/types"
 import type { IPaneSizingEvent } from "svelte-splitpanes"
 import { get } from "svelte/store"
 import { makeEnhanced } from "../enhanced"
 
+// Layout methods mirror gpu-common/src/layout.rs, keep the two in sync.
+
+/**
+ * Panels smaller than this are opened at their default size when toggled
+ */
+const MIN_PANEL_SIZE = 12
+
+/**
+ * Size panels without one in the general config are opened at
+ */
+const FALLBACK_PANEL_SIZE = 50
+
+export type PanelNode = Extract<LayoutNode, { ty: 'panel' }>
+export type EditorState = Extract<PanelState, { ty: 'editorPanel' }>
+
+/**
+ * Whether any panel within node is shown
+ */
+export function isShown(node: LayoutNode): boolean {
+    return node.ty == 'panel' ? node.show : node.children.some(isShown)
+}
+
+/**
+ * Indices of the children leading to the first panel of kind panel, depth first
+ */
+function pathTo(node: LayoutNode, panel: Panel): number[] | null {
+    if (node.ty == 'panel') return node.state.ty == panel ? [] : null
+    for (const [i, child] of node.children.entries()) {
+        const path = pathTo(child, panel)
+        if (path) return [i, ...path]
+    }
+    return null
+}
+
+function nodeAt(node: LayoutNode, path: number[]): LayoutNode | null {
+    for (const i of path) {
+        if (node.ty != 'split' || !node.children[i]) return null
+        node = node.children[i]
+    }
+    return node
+}
+
+/**
+ * @returns First panel of kind panel, depth first
+ */
+export function findPanel(layout: Layout, panel: Panel): PanelNode | null {
+    const path = pathTo(layout.root, panel)
+    const node = path && nodeAt(layout.root, path)
+    return node?.ty == 'panel' ? node : null
+}
+
+/**
+ * @returns State of the first editor panel, which documents are opened in
+ */
+export function editorState(layout: Layout): EditorState | null {
+    const state = findPanel(layout, 'editorPanel')?.state
+    return state?.ty == 'editorPanel' ? state : null
+}
+
 export type LayoutExtras = {
     togglePanel: (panel: Panel, set?: boolean) => void
-    setPanelSize: (panel: Panel, event: IPaneSizingEvent) => void
+    shiftPanel: (args: ShiftPaneArgs) => void
+    setSplitSizes: (path: number[], events: IPaneSizingEvent[]) => void
     moveWorkspaceIdx: (shift: number) => void
+    setWorkspaceIdx: (idx: number) => void
     closeWorkspaceFile: (idx?: number) => void
     openDocument: (fileid: string) => void
 }
@@ -18,55 +78,116 @@ export default makeEnhanced<Layout, LayoutExtras>(DEFAULT_LAYOUT, function (layo
 
     function togglePanel(panel: Panel, set?: boolean) {
         layout.update(l => {
-            let tooSmallToBeOpen = l[panel].size < 12
-            let expand = tooSmallToBeOpen || ((set !== undefined) ? set : !l[panel].show)
-            if (tooSmallToBeOpen) l[panel].size = get(wUserGeneralConfig)[(panel + 'Size') as GeneralConfigKey]
-            l[panel].show = expand
-            console.log('toggling', panel, expand, l[panel])
+            const node = findPanel(l, panel)
+            if (!node) return l
+            const tooSmallToBeOpen = node.size < MIN_PANEL_SIZE
+            node.show = set ?? (!node.show || tooSmallToBeOpen)
+            if (node.show && tooSmallToBeOpen) {
+                const size = get(wUserGeneralConfig)[(panel + 'Size') as GeneralConfigKey]
+                node.size = size ?? FALLBACK_PANEL_SIZE
+            }
             return l
         })
     }
-    function setPanelSize(panel: Panel, event: IPaneSizingEvent) {
+
+    /**
+     * Grows the pane by taking from the closest shown node in its split,
+     * preferring the one after it. Panes alone in their split grow their
+     * split instead.
+     */
+    function shiftPanel({ pane, shift }: ShiftPaneArgs) {
         layout.update(l => {
-            l[panel].size = event.size
-            if (event.size < event.min) l[panel].show = false
-            console.log('updating: ', event)
+            const path = pathTo(l.root, pane)
+            const node = path && nodeAt(l.root, path)
+            if (!path || !node || !isShown(node)) return l
+            while (path.length > 0) {
+                const index = path.pop()!
+                const split = nodeAt(l.root, path)
+                if (split?.ty != 'split') return l
+                const children = split.children
+                const after = children.slice(index + 1).findIndex(isShown)
+                const before = children.slice(0, index).reverse().findIndex(isShown)
+                const sibling = after >= 0 ? index + 1 + after : before >= 0 ? index - 1 - before : -1
+                if (sibling < 0) continue
+                const clamped = Math.min(Math.max(shift, -children[index].size), children[sibling].size)
+                children[index].size += clamped
+                children[sibling].size -= clamped
+                return l
+            }
             return l
         })
     }
 
-    function moveWorkspaceIdx(shift: number) {
+    /**
+     * Stores sizes of the children of the split at path after it was dragged.
+     * Panels dragged below their minimum size are hidden.
+     */
+    function setSplitSizes(path: number[], events: IPaneSizingEvent[]) {
         layout.update(l => {
-            const len = l.workspace.length
-            if (len == 0) return l
-            l.fileIndex = ((((l.fileIndex ?? 0) + shift) % len) + len) % len
+            const split = nodeAt(l.root, path)
+            if (split?.ty != 'split') return l
+            split.children.forEach((child, i) => {
+                const event = events[i]
+                if (!event || !isShown(child)) return
+                child.size = event.size
+                if (child.ty == 'panel' && event.size < event.min) child.show = false
+            })
             return l
         })
     }
 
-    function closeWorkspaceFile(idx?: number) {
+    /**
+     * Runs f on the state of the first editor panel, if there is one
+     */
+    function updateEditor(f: (editor: EditorState) => void) {
         layout.update(l => {
-            console.log(l)
-            if (!idx && l.fileIndex == null) return l
-            let closeIdx = idx ?? l.fileIndex!
-            l.workspace.splice(closeIdx, 1)
-            console.log(l.workspace)
-            if (l.workspace.length > 0) l.fileIndex = Math.max(0, (l.fileIndex ?? 0) - 1)
-            else l.fileIndex = null
+            const editor = editorState(l)
+            if (editor) f(editor)
             return l
         })
     }
 
+    function moveWorkspaceIdx(shift: number) {
+        updateEditor(editor => {
+            const len = editor.workspace.length
+            if (len == 0) return
+            editor.fileIndex = ((((editor.fileIndex ?? 0) + shift) % len) + len) % len
+        })
+    }
+
+    function setWorkspaceIdx(idx: number) {
+        updateEditor(editor => {
+            if (editor.workspace[idx]) editor.fileIndex = idx
+        })
+    }
+
+    function closeWorkspaceFile(idx?: number) {
+        updateEditor(editor => {
+            if (!idx && editor.fileIndex == null) return
+            let closeIdx = idx ?? editor.fileIndex!
+            editor.workspace.splice(closeIdx, 1)
+            if (editor.workspace.length > 0) editor.fileIndex = Math.max(0, (editor.fileIndex ?? 0) - 1)
+            else editor.fileIndex = null
+        })
+    }
+
     function openDocument(fileid: string) {
-        layout.update(l => {
-            let maybeIndex = l.workspace.indexOf(fileid)
+        updateEditor(editor => {
+            let maybeIndex = editor.workspace.indexOf(fileid)
             // add file to workspace
             if (maybeIndex < 0) {
-                maybeIndex = l.workspace.push(fileid) - 1
+                maybeIndex = editor.workspace.push(fileid) - 1
             }
-            l.fileIndex = maybeIndex
-            return l
+            editor.fileIndex = maybeIndex
         })
     }
-    return { togglePanel, setPanelSize, moveWorkspaceIdx, closeWorkspaceFile, openDocument }
-}) 
\ No newline at end of file
+    return {
+        togglePanel,
+        shiftPanel,
+        setSplitSizes,
+        moveWorkspaceIdx,
+        setWorkspaceIdx,
+        closeWorkspaceFile,
+        openDocument
+    }
+})
@@ -1,25 +1,48 @@
 import { browser } from "$app/environment"
 import { DEFAULT_USER_EDITOR_CONFIG, DEFAULT_USER_GENERAL_CONFIG, DEFAULT_USER_KEYBINDS, USER_CONFIG_META, type ConfigItemMeta, type ConfigKey, type ConfigScope, type GeneralConfigKey } from "$lib/consts/userConfig"
 import type { Keybinds } from "$lib/core/input"
+import { mergeKeymap, PLATFORM } from "$lib/core/keymap"
 import debounce from "lodash/debounce"
-import type { UserConfig, UserEditorConfig, UserGeneralConfig } from "src/generated/types"
+import type { Macro, UserConfig, UserEditorConfig, UserGeneralConfig } from "src/generated/types"
 import { derived, writable } from "svelte/store"
 
 export const wUserGeneralConfig = writable<UserGeneralConfig>(DEFAULT_USER_GENERAL_CONFIG)
 export const wUserEditorConfig = writable<UserEditorConfig>(DEFAULT_USER_EDITOR_CONFIG)
-export const wUserKeybinds = writable<Keybinds>(DEFAULT_USER_KEYBINDS)
+/**
+ * Keybinds set by the user, applied over the default keymap
+ */
+export const wUserKeybinds = writable<Keybinds>({})
 export const wUserTheme = writable<any>({})
+/**
+ * Macros by name, run by the 'runMacro' action
+ */
+export const wUserMacros = writable<Record<string, Macro>>({})
 export const dUserConfig = derived(
-    [wUserGeneralConfig, wUserEditorConfig, wUserKeybinds, wUserTheme],
-    ([$general, $editor, $keybinds, $theme]): UserConfig => {
+    [wUserGeneralConfig, wUserEditorConfig, wUserKeybinds, wUserTheme, wUserMacros],
+    ([$general, $editor, $keybinds, $theme, $macros]): UserConfig => {
         return {
             general: $general,
             editor: $editor,
             keybinds: $keybinds,
             theme: $theme,
+            macros: $macros,
         }
     })
 
+/**
+ * Default keymap with the user's keybinds applied, keybinds as written
+ */
+export const dKeymap = derived(wUserKeybinds, ($keybinds, set: (keymap: Keybinds) => void) => {
+    if (browser) mergeKeymap($keybinds).then(set)
+}, DEFAULT_USER_KEYBINDS)
+
+/**
+ * dKeymap with keybinds as pressed on this platform, for looking up key events
+ */
+export const dPlatformKeymap = derived(wUserKeybinds, ($keybinds, set: (keymap: Keybinds) => void) => {
+    if (browser) mergeKeymap($keybinds, PLATFORM).then(set)
+}, DEFAULT_USER_KEYBINDS)
+
 /**
  * Writes to local storage only after the alloted amount of time after 
  * last edit has occured
@@ -37,8 +60,9 @@ export function setUserConfig(config?: Partial<UserConfig>) {
     if (!config) return
     wUserGeneralConfig.set({ ...DEFAULT_USER_GENERAL_CONFIG, ...config.general })
     wUserEditorConfig.set({ ...DEFAULT_USER_EDITOR_CONFIG, ...config.editor })
-    wUserKeybinds.set({ ...DEFAULT_USER_KEYBINDS, ...config.keybinds })
+    wUserKeybinds.set(config.keybinds ?? {})
     wUserTheme.set(config.theme)
+    wUserMacros.set(config.macros ?? {})
 }
 
 export function validate(scope: ConfigScope, configKey: ConfigKey, value: any): boolean {
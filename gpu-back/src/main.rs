@@ -8,15 +8,19 @@ use actix_session::storage::RedisSessionStore;
 use actix_session::SessionMiddleware;
 use actix_web::cookie::Key;
 use actix_web::middleware::Logger;
-use actix_web::web::Data;
+use actix_web::web::{Data, JsonConfig, PayloadConfig};
 use thiserror::Error;
 
 use std::sync::Arc;
 
+use crate::store::asset::AssetRepository;
 use crate::store::project::ProjectRepository;
 use crate::store::user::UserRepository;
 //use tracing_log::LogTracer;
 
+/// Largest project body accepted, including binary files
+const MAX_PROJECT_SIZE: usize = 32 * 1024 * 1024;
+
 #[derive(Error, Debug)]
 enum Error {
     #[error(transparent)]
@@ -49,6 +53,7 @@ async fn main() -> Result<(), Error> {
     let pool = Arc::new(pool);
     let user_repo = Arc::new(UserRepository::new(&pool));
     let project_repo = Arc::new(ProjectRepository::new(&pool));
+    let asset_repo = Arc::new(AssetRepository::new(&pool));
 
     log::info!("Connected to database");
 
@@ -71,6 +76,10 @@ async fn main() -> Result<(), Error> {
             .app_data(Data::new(pool.clone()))
             .app_data(Data::new(user_repo.clone()))
             .app_data(Data::new(project_repo.clone()))
+            .app_data(Data::new(asset_repo.clone()))
+            // Binary files are sent inline until they are stored as assets
+            .app_data(JsonConfig::default().limit(MAX_PROJECT_SIZE))
+            .app_data(PayloadConfig::new(MAX_PROJECT_SIZE))
             .service(crate::realm::user::sign_up)
             .service(crate::realm::user::login)
             .service(crate::realm::user::user_info)
@@ -80,6 +89,9 @@ async fn main() -> Result<(), Error> {
             .service(crate::realm::project::get_project)
             .service(crate::realm::project::delete_project)
             .service(crate::realm::project::get_user_projects)
+            .service(crate::realm::asset::get_asset)
+            .service(crate::realm::archive::get_project_archive)
+            .service(crate::realm::archive::post_project_archive)
     })
     .bind(("0.0.0.0", port))?
     .run()
@@ -0,0 +1,113 @@
+use std::{str::FromStr, sync::Arc};
+
+use actix_identity::Identity;
+use actix_web::{get, http::header, post, web, HttpResponse};
+use gpu_common::realm::{ProjectResponse, ProjectUpsert};
+use gpu_common::{ArchiveLimits, ProjectDir, ProjectManifest};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    realm::{error::ApiErrorType, ApiResult},
+    store::{asset::AssetRepository, project::ProjectRepository},
+    util::from_base64,
+    MAX_PROJECT_SIZE,
+};
+
+/// Project as a zip archive, with the bytes of its assets, see
+/// `gpu_common::archive`
+#[get("/project/{project_id}/archive")]
+pub async fn get_project_archive(
+    project_id: web::Path<String>,
+    project_repository: web::Data<Arc<ProjectRepository>>,
+    asset_repository: web::Data<Arc<AssetRepository>>,
+    identity: Option<Identity>,
+) -> ApiResult {
+    let project_id =
+        from_base64(&project_id).map_err(|_| ("Invalid id", ApiErrorType::InvalidArguments))?;
+    let project = project_repository
+        .find_by_id(&project_id)
+        .await
+        .map_err(|_| ("Project not found", ApiErrorType::NotFound))?;
+    if !project.published
+        && project.author_id.map(|id| id.to_string()) != identity.and_then(|i| i.id().ok())
+    {
+        return Err(("Project is private", ApiErrorType::Unauthorized).into());
+    }
+
+    let mut project = ProjectResponse::try_from(project)?;
+    asset_repository.download(&mut project.files).await?;
+    let file_name = archive_name(&project.title);
+    let dir = ProjectDir {
+        manifest: ProjectManifest {
+            schema_version: project.schema_version,
+            title: project.title,
+            description: project.description,
+            runner: None,
+            config: project.config,
+            layout: project.layout,
+        },
+        files: project.files,
+    };
+    let archive = dir
+        .to_archive()
+        .map_err(|err| (err.to_string(), ApiErrorType::InternalServerError))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{file_name}\""),
+        ))
+        .body(archive))
+}
+
+/// Creates an unpublished project from a zip archive, as a new project of the
+/// user even if it was exported from one of theirs
+#[post("/project/archive")]
+pub async fn post_project_archive(
+    archive: web::Bytes,
+    project_repository: web::Data<Arc<ProjectRepository>>,
+    asset_repository: web::Data<Arc<AssetRepository>>,
+    identity: Identity,
+) -> ApiResult {
+    let id = identity
+        .id()
+        .map_err(|_| ("Invalid indentity", ApiErrorType::InternalServerError))?;
+    let id = Uuid::from_str(&id).map_err(|_| ("Invalid id", ApiErrorType::InternalServerError))?;
+
+    let limits = ArchiveLimits {
+        max_size: MAX_PROJECT_SIZE as u64,
+        ..ArchiveLimits::default()
+    };
+    let ProjectDir {
+        manifest,
+        mut files,
+    } = ProjectDir::from_archive(&archive, &limits)
+        .map_err(|err| (err.to_string(), ApiErrorType::InvalidArguments))?;
+    let assets = asset_repository.extract(&id, &mut files).await?;
+    let project = ProjectUpsert {
+        id: None,
+        title: manifest.title,
+        description: manifest.description,
+        files,
+        layout: manifest.layout,
+        config: manifest.config,
+        published: false,
+        updated_at: None,
+    };
+    project.validate()?;
+    let project = project_repository.insert(&id, project).await?;
+    asset_repository.link(&project.id, &assets).await?;
+
+    Ok(HttpResponse::Ok().json(ProjectResponse::try_from(project)?))
+}
+
+/// File name of an archive from the title of its project
+fn archive_name(title: &str) -> String {
+    let name = title
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect::<String>();
+    format!("{}.zip", name.trim_matches('-'))
+}
@@ -0,0 +1,45 @@
+use std::{str::FromStr, sync::Arc};
+
+use actix_identity::Identity;
+use actix_web::{get, http::header, web, HttpResponse};
+
+use crate::{
+    realm::{error::ApiErrorType, ApiResult},
+    store::asset::AssetRepository,
+    util::from_base64,
+};
+use uuid::Uuid;
+
+/// Bytes of a binary file, for the authors of projects using it or anyone if
+/// one of them is published. Assets never change, since they are stored by hash.
+#[get("/asset/{asset_id}")]
+pub async fn get_asset(
+    asset_id: web::Path<String>,
+    asset_repository: web::Data<Arc<AssetRepository>>,
+    identity: Option<Identity>,
+) -> ApiResult {
+    let asset_id =
+        from_base64(&asset_id).map_err(|_| ("Invalid id", ApiErrorType::InvalidArguments))?;
+    let asset = asset_repository
+        .find_by_id(&asset_id)
+        .await
+        .map_err(|_| ("Asset not found", ApiErrorType::NotFound))?;
+    let user_id = identity
+        .and_then(|identity| identity.id().ok())
+        .and_then(|id| Uuid::from_str(&id).ok());
+    if !asset_repository
+        .is_visible(&asset, user_id.as_ref())
+        .await?
+    {
+        return Err(("Asset is private", ApiErrorType::Unauthorized).into());
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .insert_header((
+            header::CACHE_CONTROL,
+            "private, max-age=31536000, immutable",
+        ))
+        .insert_header((header::ETAG, format!("\"{}\"", asset.hash)))
+        .body(asset.data))
+}
@@ -1,6 +1,7 @@
 use std::fmt::Display;
 
 use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use gpu_common::MigrationError;
 use serde::Serialize;
 use thiserror::Error;
 use validator::ValidationErrors;
@@ -16,6 +17,8 @@ pub enum ApiErrorType {
     InvalidArguments,
     #[error("Unauthorized")]
     Unauthorized,
+    #[error("Conflict")]
+    Conflict,
 }
 
 #[derive(Debug)]
@@ -45,6 +48,18 @@ impl From<ValidationErrors> for ApiError {
     }
 }
 
+/// Stored projects that fail to migrate are broken, not the request
+impl From<MigrationError> for ApiError {
+    fn from(err: MigrationError) -> Self {
+        (
+            "Could not read project",
+            err.to_string().as_str(),
+            ApiErrorType::InternalServerError,
+        )
+            .into()
+    }
+}
+
 impl<T: AsRef<str>> From<(T, ApiErrorType)> for ApiError {
     fn from((message, error_type): (T, ApiErrorType)) -> Self {
         Self {
@@ -94,6 +109,7 @@ impl ResponseError for ApiError {
             ApiErrorType::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
             ApiErrorType::InvalidArguments => StatusCode::BAD_REQUEST,
             ApiErrorType::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiErrorType::Conflict => StatusCode::CONFLICT,
         }
     }
 }
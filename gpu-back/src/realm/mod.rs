@@ -1,3 +1,5 @@
+pub mod archive;
+pub mod asset;
 pub mod error;
 pub mod project;
 pub mod user;
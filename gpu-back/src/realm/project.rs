@@ -3,42 +3,59 @@ use std::{str::FromStr, sync::Arc};
 use actix_identity::Identity;
 use actix_web::{delete, get, post, web, HttpResponse};
 use gpu_common::realm::{ProjectResponse, ProjectUpsert};
+use gpu_common::{load_project, migrate_project, MigrationError, SCHEMA_VERSION};
 use uuid::Uuid;
 
 use crate::{
     realm::{error::ApiErrorType, ApiResult},
-    store::{model::ProjectRow, project::ProjectRepository},
+    store::{asset::AssetRepository, model::ProjectRow, project::ProjectRepository},
     util::{from_base64, to_base64},
 };
 
-impl From<ProjectRow> for ProjectResponse {
-    fn from(project: ProjectRow) -> Self {
-        Self {
+/// Projects are stored in the schema they were saved in, so they are migrated
+/// on the way out
+impl TryFrom<ProjectRow> for ProjectResponse {
+    type Error = MigrationError;
+
+    fn try_from(project: ProjectRow) -> Result<Self, Self::Error> {
+        let mut content = serde_json::json!({
+            "schemaVersion": project.schema_version,
+            "files": project.files.0,
+            "layout": project.layout.map(|c| c.0),
+            "config": project.config.map(|c| c.0),
+        });
+        migrate_project(&mut content)?;
+        Ok(Self {
+            schema_version: SCHEMA_VERSION,
             id: to_base64(&project.id),
             title: project.title,
             description: project.description,
-            files: project.files.0,
-            layout: project.layout.map(|c| c.0),
-            config: project.config.map(|c| c.0),
+            files: serde_json::from_value(content["files"].take())?,
+            layout: serde_json::from_value(content["layout"].take())?,
+            config: serde_json::from_value(content["config"].take())?,
             published: project.published,
             created_at: project.created_at,
             updated_at: project.updated_at,
             author_id: project.author_id.as_ref().map(to_base64),
             forked_from_id: project.forked_from_id.as_ref().map(to_base64),
-        }
+        })
     }
 }
 
 #[post("/project")]
 pub async fn post_project(
-    web::Json(project): web::Json<ProjectUpsert>,
+    web::Json(project): web::Json<serde_json::Value>,
     project_repository: web::Data<Arc<ProjectRepository>>,
+    asset_repository: web::Data<Arc<AssetRepository>>,
     identity: Identity,
 ) -> ApiResult {
     let id = identity
         .id()
         .map_err(|_| ("Invalid indentity", ApiErrorType::InternalServerError))?;
     let id = Uuid::from_str(&id).map_err(|_| ("Invalid id", ApiErrorType::InternalServerError))?;
+    // Clients of older releases send projects in the schema they know
+    let mut project: ProjectUpsert =
+        load_project(project).map_err(|err| (err.to_string(), ApiErrorType::InvalidArguments))?;
 
     let mut decoded_project_id = None;
 
@@ -58,11 +75,14 @@ pub async fn post_project(
             }
         }
     }
+    // Binary files are stored as assets rather than in the project row
+    let assets = asset_repository.extract(&id, &mut project.files).await?;
     let project = project_repository
         .upsert(&id, decoded_project_id, project)
         .await?;
+    asset_repository.link(&project.id, &assets).await?;
 
-    Ok(HttpResponse::Ok().json(ProjectResponse::from(project)))
+    Ok(HttpResponse::Ok().json(ProjectResponse::try_from(project)?))
 }
 
 #[get("/project/{project_id}")]
@@ -83,7 +103,7 @@ pub async fn get_project(
     {
         Err(("Project is private", ApiErrorType::Unauthorized).into())
     } else {
-        Ok(HttpResponse::Ok().json(ProjectResponse::from(project)))
+        Ok(HttpResponse::Ok().json(ProjectResponse::try_from(project)?))
     }
 }
 
@@ -109,8 +129,8 @@ pub async fn get_user_projects(
         .await?
         .into_iter()
         .filter(|proj| proj.published || is_user)
-        .map(ProjectResponse::from)
-        .collect::<Vec<_>>();
+        .map(ProjectResponse::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
     Ok(HttpResponse::Ok().json(projects))
 }
 
@@ -29,6 +29,7 @@ impl From<UserRow> for UserInfoResponse {
             active: user.active,
             created_at: user.created_at,
             updated_at: user.updated_at,
+            warnings: Vec::new(),
         }
     }
 }
@@ -101,8 +102,24 @@ pub async fn update_user_info(
     let id = ident
         .id()
         .map_err(|_| ("Invalid indentity", ApiErrorType::InternalServerError))?;
+    let mut warnings = Vec::new();
+    if let Some(ref config) = update_user.config {
+        config
+            .check_conditions()
+            .map_err(|err| (err.to_string(), ApiErrorType::InvalidArguments))?;
+        config
+            .check_keybinds()
+            .map_err(|err| (err.to_string(), ApiErrorType::InvalidArguments))?;
+        config
+            .check_macros()
+            .map_err(|err| (err.to_string(), ApiErrorType::InvalidArguments))?;
+        warnings = config.keybind_conflicts();
+    }
     let updated_user = user_repository.update_user(&id, update_user).await?;
-    Ok(HttpResponse::Ok().json(UserInfoResponse::from(updated_user)))
+    Ok(HttpResponse::Ok().json(UserInfoResponse {
+        warnings,
+        ..UserInfoResponse::from(updated_user)
+    }))
 }
 
 #[post("/logout")]
@@ -0,0 +1,155 @@
+use std::sync::Arc;
+use uuid::Uuid;
+
+use gpu_common::{content_hash, AssetRef, FileData, Files};
+
+use crate::store::model::AssetRow;
+use crate::util::{from_base64, to_base64};
+
+use sqlx::PgPool;
+
+use super::Error;
+
+/// Identical bytes are stored once, so an existing row is returned as is
+const INSERT_QUERY: &str = r#"
+    INSERT INTO assets (hash, data) VALUES ($1, $2)
+    ON CONFLICT (hash) DO UPDATE SET hash = EXCLUDED.hash
+    RETURNING id
+"#;
+
+const FIND_BY_ID_QUERY: &str = r#"
+    SELECT * FROM assets WHERE id = $1
+"#;
+
+/// Assets can be seen by the author of a project using them, or by anyone if
+/// the project is published
+const VISIBLE_QUERY: &str = r#"
+    SELECT EXISTS(
+        SELECT 1 FROM assets
+        JOIN project_assets ON project_assets.asset_id = assets.id
+        JOIN projects ON projects.id = project_assets.project_id
+        WHERE assets.id = $1 AND assets.hash = $2
+            AND (projects.published OR projects.author_id = $3)
+    )
+"#;
+
+const UNLINK_QUERY: &str = r#"
+    DELETE FROM project_assets WHERE project_id = $1
+"#;
+
+const LINK_QUERY: &str = r#"
+    INSERT INTO project_assets (project_id, asset_id)
+    SELECT $1, UNNEST($2::uuid[])
+    ON CONFLICT DO NOTHING
+"#;
+
+/// Bytes of binary files, kept out of the `files` column of projects
+pub struct AssetRepository {
+    pool: Arc<PgPool>,
+}
+
+impl AssetRepository {
+    pub fn new(pool: &Arc<PgPool>) -> Self {
+        Self { pool: pool.clone() }
+    }
+
+    /// Stores `bytes` with `hash`, their `content_hash`
+    pub async fn insert(&self, hash: &str, bytes: &[u8]) -> Result<Uuid, Error> {
+        sqlx::query_scalar(INSERT_QUERY)
+            .bind(hash)
+            .bind(bytes)
+            .fetch_one(&*self.pool)
+            .await
+            .map_err(From::from)
+    }
+
+    pub async fn find_by_id(&self, asset_id: &Uuid) -> Result<AssetRow, Error> {
+        sqlx::query_as(FIND_BY_ID_QUERY)
+            .bind(asset_id)
+            .fetch_one(&*self.pool)
+            .await
+            .map_err(From::from)
+    }
+
+    /// Whether the user, if any, may see the asset, see `VISIBLE_QUERY`
+    pub async fn is_visible(
+        &self,
+        asset: &AssetRow,
+        user_id: Option<&Uuid>,
+    ) -> Result<bool, Error> {
+        sqlx::query_scalar(VISIBLE_QUERY)
+            .bind(asset.id)
+            .bind(&asset.hash)
+            .bind(user_id)
+            .fetch_one(&*self.pool)
+            .await
+            .map_err(From::from)
+    }
+
+    /// Makes `assets` the ones used by the project, replacing those it used before
+    pub async fn link(&self, project_id: &Uuid, assets: &[Uuid]) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(UNLINK_QUERY)
+            .bind(project_id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query(LINK_QUERY)
+            .bind(project_id)
+            .bind(assets)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await.map_err(From::from)
+    }
+
+    /// Replaces every reference to an asset with its bytes
+    pub async fn download(&self, files: &mut Files) -> Result<(), Error> {
+        for (_, file) in files.iter_mut() {
+            if let FileData::Asset(ref asset) = file.data {
+                let missing = || Error::MissingAsset(asset.id.clone());
+                let id = from_base64(&asset.id).map_err(|_| missing())?;
+                let row = self.find_by_id(&id).await.map_err(|_| missing())?;
+                file.data = FileData::from(row.data);
+            }
+        }
+        Ok(())
+    }
+
+    /// Stores the bytes of every binary file as an asset, replacing them with a
+    /// reference to it. References sent along must point to assets `user_id`
+    /// can see, so ids of other users' private assets can't be taken over.
+    ///
+    /// Returns the ids of every asset the files use, to `link` them to their
+    /// project once it is saved.
+    pub async fn extract(&self, user_id: &Uuid, files: &mut Files) -> Result<Vec<Uuid>, Error> {
+        let mut assets = Vec::new();
+        for (_, file) in files.iter_mut() {
+            match file.data {
+                FileData::Text(_) => {}
+                FileData::Bytes { ref bytes } => {
+                    let hash = content_hash(bytes);
+                    let id = self.insert(&hash, bytes).await?;
+                    assets.push(id);
+                    file.data = FileData::Asset(AssetRef {
+                        id: to_base64(&id),
+                        hash,
+                    });
+                }
+                FileData::Asset(ref asset) => {
+                    let missing = || Error::MissingAsset(asset.id.clone());
+                    let id = from_base64(&asset.id).map_err(|_| missing())?;
+                    let visible: bool = sqlx::query_scalar(VISIBLE_QUERY)
+                        .bind(id)
+                        .bind(&asset.hash)
+                        .bind(user_id)
+                        .fetch_one(&*self.pool)
+                        .await?;
+                    if !visible {
+                        return Err(missing());
+                    }
+                    assets.push(id);
+                }
+            }
+        }
+        Ok(assets)
+    }
+}
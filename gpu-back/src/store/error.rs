@@ -12,6 +12,12 @@ pub enum Error {
     Env(#[from] std::env::VarError),
     #[error("Error parsing uuid")]
     Uuid(#[from] uuid::Error),
+    #[error("Project was updated since the revision the update was made on")]
+    Conflict,
+    #[error("Project not found")]
+    NotFound,
+    #[error("File references asset {0}, which does not exist")]
+    MissingAsset(String),
 }
 
 impl From<Error> for ApiError {
@@ -20,6 +26,9 @@ impl From<Error> for ApiError {
             Error::Db(_) | Error::Migrate(_) | Error::Env(_) | Error::Uuid(_) => {
                 ApiErrorType::InternalServerError.into()
             }
+            Error::Conflict => (err.to_string(), ApiErrorType::Conflict).into(),
+            Error::NotFound => (err.to_string(), ApiErrorType::NotFound).into(),
+            Error::MissingAsset(_) => (err.to_string(), ApiErrorType::InvalidArguments).into(),
         }
     }
 }
@@ -3,6 +3,7 @@ use std::time::Duration;
 use error::Error;
 use sqlx::{postgres::PgPoolOptions, PgPool};
 
+pub mod asset;
 pub mod error;
 pub mod model;
 pub mod project;
@@ -0,0 +1,18 @@
+use chrono::NaiveDateTime;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+// CREATE TABLE assets (
+//     id uuid default uuid_generate_v4() PRIMARY KEY,
+//     hash CHAR(64) UNIQUE NOT NULL,
+//     data BYTEA NOT NULL,
+//     created_at TIMESTAMP(3) NOT NULL DEFAULT current_timestamp
+// );
+#[derive(Debug, FromRow)]
+pub struct AssetRow {
+    pub id: Uuid,
+    /// Hex encoded sha256 of `data`
+    pub hash: String,
+    pub data: Vec<u8>,
+    pub created_at: NaiveDateTime,
+}
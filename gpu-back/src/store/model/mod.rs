@@ -1,5 +1,7 @@
+mod asset;
 mod project;
 mod user;
 
+pub use asset::*;
 pub use project::*;
 pub use user::*;
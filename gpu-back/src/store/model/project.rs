@@ -1,6 +1,6 @@
 use chrono::NaiveDateTime;
-use gpu_common::{Config, Files, Layout};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use sqlx::{types::Json, FromRow};
 use uuid::Uuid;
 
@@ -15,21 +15,26 @@ use uuid::Uuid;
 //   created_at TIMESTAMP(3) NOT NULL DEFAULT current_timestamp,
 //   updated_at TIMESTAMP(3) NOT NULL DEFAULT current_timestamp,
 //   CONSTRAINT author_id FOREIGN KEY(id) REFERENCES users(id) ON DELETE SET NULL,
-//   CONSTRAINT forked_from_id FOREIGN KEY(id) REFERENCES projects(id)
+//   CONSTRAINT forked_from_id FOREIGN KEY(id) REFERENCES projects(id),
+//   schema_version INT NOT NULL DEFAULT 0
 // );
+//
+// Files, layout and config are kept as they were saved, and migrated to the
+// current schema when read, see `gpu_common::migrate_project`.
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct ProjectRow {
     pub id: Uuid,
     pub title: String,
     pub description: Option<String>,
-    pub files: Json<Files>,
+    pub files: Json<Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub layout: Option<Json<Layout>>,
+    pub layout: Option<Json<Value>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub config: Option<Json<Config>>,
+    pub config: Option<Json<Value>>,
     pub published: bool,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
     pub author_id: Option<Uuid>,
     pub forked_from_id: Option<Uuid>,
+    pub schema_version: i32,
 }
@@ -2,6 +2,7 @@ use std::sync::Arc;
 use uuid::Uuid;
 
 use gpu_common::realm::ProjectUpsert;
+use gpu_common::SCHEMA_VERSION;
 
 use crate::store::model::ProjectRow;
 
@@ -12,16 +13,16 @@ use super::Error;
 
 const UPDATE_QUERY: &str = r#"
     UPDATE projects SET 
-        (title, description, files, layout, config, published) =
-        ($1, $2, $3, $4, $5, $6)
-    WHERE id = $7
+        (title, description, files, layout, config, published, schema_version, updated_at) =
+        ($1, $2, $3, $4, $5, $6, $7, current_timestamp)
+    WHERE id = $8 AND ($9::timestamp IS NULL OR updated_at = $9)
     RETURNING *
 "#;
 
 const INSERT_QUERY: &str = r#"
     Insert INTO projects 
-        (author_id, title, description, files, layout, config, published)
-    VALUES ($1, $2, $3, $4, $5, $6, $7) 
+        (author_id, title, description, files, layout, config, published, schema_version)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8) 
     RETURNING *
 "#;
 
@@ -77,6 +78,7 @@ impl ProjectRepository {
             .bind(layout)
             .bind(config)
             .bind(project.published)
+            .bind(SCHEMA_VERSION as i32)
             .fetch_one(&*self.pool)
             .await
             .map_err(From::from)
@@ -90,17 +92,32 @@ impl ProjectRepository {
         let files = Json(project.files);
         let config = project.config.map(Json);
         let layout = project.layout.map(Json);
-        sqlx::query_as(UPDATE_QUERY)
+        let updated: Option<ProjectRow> = sqlx::query_as(UPDATE_QUERY)
             .bind(project.title)
             .bind(project.description)
             .bind(files)
             .bind(layout)
             .bind(config)
             .bind(project.published)
+            .bind(SCHEMA_VERSION as i32)
             .bind(project_id)
-            .fetch_one(&*self.pool)
-            .await
-            .map_err(From::from)
+            .bind(project.updated_at)
+            .fetch_optional(&*self.pool)
+            .await?;
+        if let Some(updated) = updated {
+            return Ok(updated);
+        }
+        // Nothing was updated, either because the project does not exist or
+        // because it changed since the revision the update was made on
+        let current: Option<ProjectRow> = sqlx::query_as(FIND_BY_ID_QUERY)
+            .bind(project_id)
+            .fetch_optional(&*self.pool)
+            .await?;
+        match current {
+            None => Err(Error::NotFound),
+            // The project exists, so only its revision could have differed
+            Some(_) => Err(Error::Conflict),
+        }
     }
 
     pub async fn find_by_id(&self, project_id: &Uuid) -> Result<ProjectRow, Error> {
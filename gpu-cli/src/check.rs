@@ -0,0 +1,109 @@
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+use gpu_common::{BuildDiagnostic, BuildEvent, Project};
+use gpu_compiler::{CompiledProject, Diagnostic};
+
+/// Outcome of compiling every shader of a project
+#[derive(Debug)]
+pub struct Report {
+    /// Number of shaders that were compiled
+    pub shaders: usize,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Report {
+    /// Compiles `project` with naga alone, no gpu is needed
+    pub fn check(project: &Project) -> Self {
+        let shaders = project
+            .files
+            .iter()
+            .filter(|(_, file)| file.extension().is_shader())
+            .count();
+        let diagnostics = match CompiledProject::compile(&project.files) {
+            Ok(_) => Vec::new(),
+            Err(err) => err.0,
+        };
+        Self {
+            shaders,
+            diagnostics,
+        }
+    }
+
+    pub fn success(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    /// Report in the shape of the build events emitted by clients
+    pub fn to_json(&self) -> serde_json::Value {
+        let event = BuildEvent {
+            success: self.success(),
+            diagnostics: self.diagnostics.iter().map(BuildDiagnostic::from).collect(),
+        };
+        serde_json::to_value(event).expect("build events serialize to json")
+    }
+}
+
+/// Every diagnostic on its own line, followed by a summary
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for diagnostic in self.diagnostics.iter() {
+            writeln!(f, "error: {diagnostic}")?;
+        }
+        if self.success() {
+            return write!(f, "ok: {} shaders checked", self.shaders);
+        }
+        let files = self
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.file.as_str())
+            .collect::<BTreeSet<_>>();
+        write!(
+            f,
+            "failed: {} problems in {} of {} shaders",
+            self.diagnostics.len(),
+            files.len(),
+            self.shaders
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gpu_common::Files;
+
+    use super::Report;
+
+    #[test]
+    fn test_check() {
+        let good = "@compute @workgroup_size(1) fn main() {}";
+        let report = Report::check(
+            &Files::from_entries([("/a.wgsl", good), ("/b.wgsl", good)])
+                .unwrap()
+                .into(),
+        );
+        assert!(report.success());
+        assert_eq!(report.to_string(), "ok: 2 shaders checked");
+        assert_eq!(
+            report.to_json(),
+            serde_json::json!({ "success": true, "diagnostics": [] })
+        );
+
+        let report = Report::check(
+            &Files::from_entries([("/a.wgsl", good), ("/b.wgsl", "fn main( {}")])
+                .unwrap()
+                .into(),
+        );
+        assert!(!report.success());
+        let human = report.to_string();
+        assert!(human.starts_with("error: /b.wgsl:1:"), "{human}");
+        assert!(
+            human.ends_with("failed: 1 problems in 1 of 2 shaders"),
+            "{human}"
+        );
+        let json = report.to_json();
+        assert_eq!(json["success"], false);
+        assert_eq!(json["diagnostics"][0]["file"], "/b.wgsl");
+        assert_eq!(json["diagnostics"][0]["line"], 1);
+    }
+}
@@ -0,0 +1,122 @@
+use std::path::{Path, PathBuf};
+
+use gpu_common::{import_shadertoy, ProjectDir, ProjectDirError, ShadertoyError, Unmapped};
+use serde::Serialize;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Could not read {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error(transparent)]
+    Shadertoy(#[from] ShadertoyError),
+    #[error(transparent)]
+    Dir(#[from] ProjectDirError),
+    #[error("{0} already exists")]
+    Exists(PathBuf),
+}
+
+/// Project directory written for an imported shader
+#[derive(Debug, Serialize)]
+pub struct Imported {
+    pub title: String,
+    pub path: PathBuf,
+    pub report: Vec<Unmapped>,
+}
+
+/// Imports every shader of the Shadertoy export at `path` into a project
+/// directory within `out`, named after its title. Existing directories are
+/// left alone.
+pub fn import(path: &Path, out: &Path) -> Result<Vec<Imported>, Error> {
+    let json = std::fs::read_to_string(path).map_err(|err| Error::Io(path.to_owned(), err))?;
+    let imports = import_shadertoy(&json)?;
+    let mut dirs = Vec::with_capacity(imports.len());
+    for import in imports.iter() {
+        let name = dir_name(&import.title);
+        let dir = out.join(&name);
+        if dir.exists() || dirs.contains(&dir) {
+            return Err(Error::Exists(dir));
+        }
+        dirs.push(dir);
+    }
+
+    let mut imported = Vec::with_capacity(imports.len());
+    for (import, dir) in imports.into_iter().zip(dirs) {
+        let mut project = ProjectDir::from_project(import.title.clone(), import.project);
+        project.manifest.description = import.description;
+        project.write(&dir)?;
+        imported.push(Imported {
+            title: import.title,
+            path: dir,
+            report: import.report,
+        });
+    }
+    Ok(imported)
+}
+
+/// Lowercase name of a directory from a title, i.e. `sea-of-clouds`
+fn dir_name(title: &str) -> String {
+    let name = title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-");
+    if name.is_empty() {
+        "shadertoy".to_owned()
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gpu_common::ProjectDir;
+
+    use crate::check::Report;
+
+    use super::{dir_name, import, Error};
+
+    #[test]
+    fn test_import() {
+        let root = std::env::temp_dir().join(format!("gputoy-cli-import-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let export = root.join("export.json");
+        let shader = serde_json::json!({
+            "info": { "id": "abcd12", "name": "Sea of Clouds", "username": "someone" },
+            "renderpass": [
+                {
+                    "code": "void mainImage(out vec4 c, in vec2 p) { c = vec4(p / iResolution.xy, wave(iTime), 1.0); }",
+                    "name": "Image",
+                    "type": "image"
+                },
+                {
+                    "code": "float wave(float t) { return 0.5 + 0.5 * sin(t); }",
+                    "name": "Common",
+                    "type": "common"
+                }
+            ]
+        });
+        std::fs::write(&export, serde_json::json!([shader]).to_string()).unwrap();
+
+        let imported = import(&export, &root).unwrap();
+        let again = import(&export, &root);
+        let project = ProjectDir::read(&imported[0].path).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(imported[0].path, root.join("sea-of-clouds"));
+        assert!(imported[0].report.is_empty());
+        assert!(matches!(again, Err(Error::Exists(_))));
+        assert_eq!(project.manifest.title, "Sea of Clouds");
+        // The generated glsl compiles as is
+        let report = Report::check(&project.into_project());
+        assert!(report.success(), "{report}");
+        assert_eq!(report.shaders, 1);
+    }
+
+    #[test]
+    fn test_dir_name() {
+        assert_eq!(dir_name("Sea of Clouds!"), "sea-of-clouds");
+        assert_eq!(dir_name("???"), "shadertoy");
+    }
+}
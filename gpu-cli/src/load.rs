@@ -0,0 +1,89 @@
+use std::path::{Path, PathBuf};
+
+use gpu_common::{
+    load_project, MigrationError, Project, ProjectDir, ProjectDirError, MANIFEST_FILE,
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Could not read {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error(transparent)]
+    Dir(#[from] ProjectDirError),
+    #[error("Could not parse project export {0}: {1}")]
+    Json(PathBuf, MigrationError),
+}
+
+/// Loads the project at `path`, which is either a project directory, its
+/// manifest, or a project exported as json.
+///
+/// Exports of projects fetched from the api load as well, everything besides
+/// files, layout and config is ignored. Exports saved by older releases are
+/// migrated to the current schema.
+pub fn load(path: &Path) -> Result<Project, Error> {
+    if path.is_dir() {
+        return Ok(ProjectDir::read(path)?.into_project());
+    }
+    if path.file_name().is_some_and(|name| name == MANIFEST_FILE) {
+        let root = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        return Ok(ProjectDir::read(root)?.into_project());
+    }
+    let json = std::fs::read_to_string(path).map_err(|err| Error::Io(path.to_owned(), err))?;
+    serde_json::from_str(&json)
+        .map_err(MigrationError::from)
+        .and_then(load_project)
+        .map_err(|err| Error::Json(path.to_owned(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use gpu_common::{Files, Project, ProjectDir, MANIFEST_FILE};
+
+    use super::{load, Error};
+
+    fn project() -> Project {
+        Files::from_entries([(
+            "/shaders/main.wgsl",
+            "@fragment fn main() -> @location(0) vec4<f32> { return vec4<f32>(1.0); }",
+        )])
+        .unwrap()
+        .into()
+    }
+
+    #[test]
+    fn test_load() {
+        let root = std::env::temp_dir().join(format!("gputoy-cli-load-{}", std::process::id()));
+        ProjectDir::from_project("Loaded", project())
+            .write(&root.join("dir"))
+            .unwrap();
+        let export = root.join("export.json");
+        std::fs::write(&export, serde_json::to_string(&project()).unwrap()).unwrap();
+        let legacy = root.join("legacy.json");
+        let mut json = serde_json::to_value(project()).unwrap();
+        json.as_object_mut().unwrap().remove("schemaVersion");
+        let map = json["files"]["map"].as_object_mut().unwrap();
+        let file = map.remove("/shaders/main.wgsl").unwrap();
+        map.insert("shaders/main.wgsl".to_owned(), file);
+        std::fs::write(&legacy, json.to_string()).unwrap();
+        let broken = root.join("broken.json");
+        std::fs::write(&broken, "{\"files\": 4}").unwrap();
+
+        let from_dir = load(&root.join("dir")).unwrap();
+        let from_manifest = load(&root.join("dir").join(MANIFEST_FILE)).unwrap();
+        let from_export = load(&export).unwrap();
+        let from_legacy = load(&legacy).unwrap();
+        let broken = load(&broken);
+        let missing = load(&root.join("missing.json"));
+        std::fs::remove_dir_all(&root).unwrap();
+
+        for project in [from_dir, from_manifest, from_export, from_legacy] {
+            assert!(project.files.contains("/shaders/main.wgsl"));
+        }
+        assert!(matches!(broken, Err(Error::Json(..))));
+        assert!(matches!(missing, Err(Error::Io(..))));
+    }
+}
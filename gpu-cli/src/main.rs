@@ -0,0 +1,235 @@
+//! `gputoy` command-line runner, validating projects and rendering them without
+//! the editor, i.e. in CI.
+//!
+//! Exit codes:
+//!   * 0 - project is valid, and every frame was rendered
+//!   * 1 - project failed to compile
+//!   * 2 - project could not be loaded or synced, or the arguments were invalid
+//!   * 3 - project compiled but could not be rendered, i.e. without an adapter
+//!   * 4 - project changed both locally and remotely, nothing was synced
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+mod check;
+mod import;
+mod load;
+#[cfg(feature = "render")]
+mod render;
+mod sync;
+
+use check::Report;
+use sync::SyncCommand;
+
+/// Exit code once the project failed to compile
+const EXIT_INVALID: u8 = 1;
+/// Exit code once the project could not be loaded, same as for invalid arguments
+const EXIT_LOAD: u8 = 2;
+/// Exit code once rendering failed
+#[cfg(feature = "render")]
+const EXIT_RENDER: u8 = 3;
+/// Exit code once both sides changed and sync stopped
+const EXIT_CONFLICT: u8 = 4;
+
+#[derive(Parser, Debug)]
+#[command(name = "gputoy", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compiles every shader in a project and reports problems, needs no gpu
+    Check {
+        /// Project directory, its gputoy.toml, or a project exported as json
+        path: PathBuf,
+        #[arg(long, value_enum, default_value_t = Format::Human)]
+        format: Format,
+    },
+    /// Renders frames of a project to png, needs a gpu adapter
+    #[cfg(feature = "render")]
+    Render {
+        /// Project directory, its gputoy.toml, or a project exported as json
+        path: PathBuf,
+        /// Times in seconds to render frames at
+        #[arg(long = "time", value_delimiter = ',', default_value = "0")]
+        times: Vec<f32>,
+        #[arg(long, default_value_t = 800)]
+        width: u32,
+        #[arg(long, default_value_t = 450)]
+        height: u32,
+        /// Directory frames are written to
+        #[arg(long, short, default_value = ".")]
+        out: PathBuf,
+        #[arg(long, value_enum, default_value_t = Format::Human)]
+        format: Format,
+    },
+    /// Creates project directories from shaders exported from Shadertoy as json
+    Import {
+        /// Json of a single shader, or of the list Shadertoy exports
+        path: PathBuf,
+        /// Directory the projects are created in, one per shader
+        #[arg(long, short, default_value = ".")]
+        out: PathBuf,
+        #[arg(long, value_enum, default_value_t = Format::Human)]
+        format: Format,
+    },
+    #[command(flatten)]
+    Sync(SyncCommand),
+}
+
+/// How results are printed
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Diagnostics on stderr, one per line
+    Human,
+    /// Single json object on stdout
+    Json,
+}
+
+fn main() -> ExitCode {
+    pretty_env_logger::init();
+    let cli = Cli::parse();
+    match cli.command {
+        Command::Check { path, format } => {
+            let project = match load::load(&path) {
+                Ok(project) => project,
+                Err(err) => return fail(format, EXIT_LOAD, &err),
+            };
+            let report = Report::check(&project);
+            print_report(format, &report, None);
+            exit_code(&report)
+        }
+        #[cfg(feature = "render")]
+        Command::Render {
+            path,
+            times,
+            width,
+            height,
+            out,
+            format,
+        } => {
+            let project = match load::load(&path) {
+                Ok(project) => project,
+                Err(err) => return fail(format, EXIT_LOAD, &err),
+            };
+            // Checked first so diagnostics look the same as with `check`
+            let report = Report::check(&project);
+            if !report.success() {
+                print_report(format, &report, None);
+                return exit_code(&report);
+            }
+            let options = render::RenderOptions {
+                width,
+                height,
+                times,
+                out,
+            };
+            match render::render(&project, &options) {
+                Ok(frames) => {
+                    print_report(format, &report, Some(&frames));
+                    ExitCode::SUCCESS
+                }
+                Err(err) => fail(format, EXIT_RENDER, &err),
+            }
+        }
+        Command::Import { path, out, format } => {
+            let imported = match import::import(&path, &out) {
+                Ok(imported) => imported,
+                Err(err) => return fail(format, EXIT_LOAD, &err),
+            };
+            match format {
+                Format::Human => {
+                    for project in imported.iter() {
+                        for unmapped in project.report.iter() {
+                            let channel = unmapped
+                                .channel
+                                .map(|channel| format!(" iChannel{channel}"))
+                                .unwrap_or_default();
+                            eprintln!(
+                                "warning: {} ({}{channel}): {}",
+                                project.title, unmapped.pass, unmapped.message
+                            );
+                        }
+                        println!("{}", project.path.display());
+                    }
+                }
+                Format::Json => println!(
+                    "{}",
+                    serde_json::json!({ "success": true, "projects": imported })
+                ),
+            }
+            ExitCode::SUCCESS
+        }
+        Command::Sync(command) => {
+            let format = command.format();
+            match command.run() {
+                Ok(status) => {
+                    match format {
+                        Format::Human => print!("{status}"),
+                        Format::Json => println!(
+                            "{}",
+                            serde_json::json!({ "success": true, "status": status })
+                        ),
+                    }
+                    ExitCode::SUCCESS
+                }
+                Err(gpu_sync::Error::Conflict(status)) if format == Format::Json => {
+                    let json = serde_json::json!({
+                        "success": false,
+                        "error": "Both sides changed since the last sync",
+                        "status": status,
+                    });
+                    println!("{json}");
+                    ExitCode::from(EXIT_CONFLICT)
+                }
+                Err(err @ gpu_sync::Error::Conflict(_)) => fail(format, EXIT_CONFLICT, &err),
+                Err(err) => fail(format, EXIT_LOAD, &err),
+            }
+        }
+    }
+}
+
+fn exit_code(report: &Report) -> ExitCode {
+    if report.success() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_INVALID)
+    }
+}
+
+fn print_report(format: Format, report: &Report, frames: Option<&[PathBuf]>) {
+    match format {
+        Format::Human => {
+            eprintln!("{report}");
+            for frame in frames.unwrap_or_default() {
+                println!("{}", frame.display());
+            }
+        }
+        Format::Json => {
+            let mut json = report.to_json();
+            if let Some(frames) = frames {
+                json["frames"] = frames
+                    .iter()
+                    .map(|frame| frame.display().to_string())
+                    .collect();
+            }
+            println!("{json}");
+        }
+    }
+}
+
+/// Reports an error that stopped the command before it finished
+fn fail(format: Format, code: u8, err: &dyn std::error::Error) -> ExitCode {
+    match format {
+        Format::Human => eprintln!("error: {err}"),
+        Format::Json => println!(
+            "{}",
+            serde_json::json!({ "success": false, "error": err.to_string() })
+        ),
+    }
+    ExitCode::from(code)
+}
@@ -0,0 +1,125 @@
+use std::path::{Path, PathBuf};
+
+use gpu_client::context::{Context, Error as ContextError};
+use gpu_client::record::{RecordFormat, RecordOptions, Recording};
+use gpu_common::Project;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Context(#[from] ContextError),
+    #[error("Could not write {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+}
+
+/// Frames to render, one png per time
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub width: u32,
+    pub height: u32,
+    /// Times in seconds to render at
+    pub times: Vec<f32>,
+    /// Directory the frames are written to
+    pub out: PathBuf,
+}
+
+/// Builds `project` on the default adapter and renders a png for every time,
+/// returning the paths written.
+///
+/// Every frame is recorded on its own, with fresh resources, so compute passes run
+/// before render passes just like in the editor and frames do not depend on each
+/// other. Frames are rendered as the first one, i.e. with a frame count of 0.
+pub fn render(project: &Project, options: &RenderOptions) -> Result<Vec<PathBuf>, Error> {
+    pollster::block_on(async {
+        let mut context = Context::new_headless(options.width, options.height).await?;
+        context.build(project).await?;
+        std::fs::create_dir_all(&options.out).map_err(|err| Error::Io(options.out.clone(), err))?;
+        let mut written = Vec::new();
+        for &time in options.times.iter() {
+            let record = RecordOptions {
+                format: RecordFormat::PngSequence,
+                width: options.width,
+                height: options.height,
+                fps: 1,
+                start: time,
+                duration: 1.0,
+            };
+            let mut recorder = context.start_recording(record).await?;
+            context.record_frame(&mut recorder).await?;
+            let png = match recorder.finish()? {
+                Recording::PngSequence(mut frames) => frames.remove(0),
+                _ => unreachable!("recorded as a png sequence"),
+            };
+            let path = frame_path(&options.out, time);
+            std::fs::write(&path, png).map_err(|err| Error::Io(path.clone(), err))?;
+            written.push(path);
+        }
+        Ok(written)
+    })
+}
+
+/// Path of the frame rendered at `time`, named so frames sort by time
+fn frame_path(out: &Path, time: f32) -> PathBuf {
+    out.join(format!("frame-{:08.3}.png", time))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use gpu_client::context::Error as ContextError;
+    use gpu_common::{Files, Project};
+
+    use super::{frame_path, render, Error, RenderOptions};
+
+    #[test]
+    fn test_frame_path() {
+        let out = Path::new("frames");
+        assert_eq!(frame_path(out, 0.0), out.join("frame-0000.000.png"));
+        assert_eq!(frame_path(out, 1.5), out.join("frame-0001.500.png"));
+        assert_eq!(frame_path(out, 120.25), out.join("frame-0120.250.png"));
+    }
+
+    #[test]
+    fn test_render_compute() {
+        let decl = "struct State { color: vec4<f32> }\n";
+        let files = Files::from_entries([
+            (
+                "/fill.wgsl",
+                format!(
+                    "{decl}@group(0) @binding(0) var<storage, read_write> state: State;\n\
+                     @compute @workgroup_size(1) fn main() {{ state.color = vec4<f32>(0.0, 1.0, 0.0, 1.0); }}"
+                ),
+            ),
+            (
+                "/show.wgsl",
+                format!(
+                    "{decl}@group(0) @binding(0) var<storage, read> state: State;\n\
+                     @fragment fn main() -> @location(0) vec4<f32> {{ return state.color; }}"
+                ),
+            ),
+        ])
+        .unwrap();
+        let out = std::env::temp_dir().join(format!("gputoy-cli-render-{}", std::process::id()));
+        let options = RenderOptions {
+            width: 4,
+            height: 4,
+            times: vec![0.0, 1.0],
+            out: out.clone(),
+        };
+        let frames = match render(&Project::from(files), &options) {
+            Err(Error::Context(ContextError::NoAdapter)) => return,
+            res => res.unwrap(),
+        };
+        assert_eq!(frames.len(), 2);
+        for frame in frames {
+            let png = std::fs::read(&frame).unwrap();
+            let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+            let mut rgba = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut rgba).unwrap();
+            assert_eq!(rgba[..4], [0, 255, 0, 255]);
+        }
+        std::fs::remove_dir_all(out).unwrap();
+    }
+}
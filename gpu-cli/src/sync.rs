@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand};
+use gpu_sync::{Error, HttpRemote, LocalProject, Status};
+
+use crate::Format;
+
+#[derive(Subcommand, Debug)]
+pub enum SyncCommand {
+    /// Lists files changed locally and remotely since the last sync
+    Status {
+        #[command(flatten)]
+        args: SyncArgs,
+    },
+    /// Applies remote changes to the project directory
+    Pull {
+        #[command(flatten)]
+        args: SyncArgs,
+        /// Remote project to link the directory to, needed on the first pull
+        #[arg(long)]
+        id: Option<String>,
+        /// Replaces the directory with the remote project, even if both changed
+        #[arg(long)]
+        force: bool,
+    },
+    /// Uploads the project directory, creating the remote project on first push
+    Push {
+        #[command(flatten)]
+        args: SyncArgs,
+        /// Overwrites the remote project, even if both changed
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct SyncArgs {
+    /// Project directory
+    #[arg(default_value = ".")]
+    path: PathBuf,
+    /// Url of the gputoy api, defaults to the one the project was last synced with
+    #[arg(long, env = "GPUTOY_API")]
+    api: Option<String>,
+    /// Username or email to log in with
+    #[arg(long, env = "GPUTOY_USER")]
+    user: Option<String>,
+    #[arg(long, env = "GPUTOY_PASSWORD", hide_env_values = true)]
+    password: Option<String>,
+    #[arg(long, value_enum, default_value_t = Format::Human)]
+    format: Format,
+}
+
+impl SyncCommand {
+    pub fn format(&self) -> Format {
+        match self {
+            SyncCommand::Status { args }
+            | SyncCommand::Pull { args, .. }
+            | SyncCommand::Push { args, .. } => args.format,
+        }
+    }
+
+    /// Runs the command, returning the status of the project before it ran
+    pub fn run(self) -> Result<Status, Error> {
+        match self {
+            SyncCommand::Status { args } => {
+                let (project, remote) = args.open()?;
+                project.status(&remote)
+            }
+            SyncCommand::Pull { args, id, force } => {
+                let (mut project, remote) = args.open()?;
+                project.pull(&remote, id.as_deref(), force)
+            }
+            SyncCommand::Push { args, force } => {
+                let (mut project, remote) = args.open()?;
+                project.push(&remote, force)
+            }
+        }
+    }
+}
+
+impl SyncArgs {
+    fn open(self) -> Result<(LocalProject, HttpRemote), Error> {
+        let project = LocalProject::open(&self.path)?;
+        let api = self
+            .api
+            .or_else(|| project.state().map(|state| state.api.clone()))
+            .ok_or(Error::NoApi)?;
+        let remote = HttpRemote::new(&api);
+        if let Some(user) = self.user {
+            remote.login(&user, self.password.as_deref().unwrap_or_default())?;
+        }
+        Ok((project, remote))
+    }
+}
@@ -0,0 +1,314 @@
+pub use gpu_common::CaptureOptions;
+
+use crate::context::Error;
+use crate::pass::{PassKind, Passes};
+use crate::profiler::Profiler;
+use crate::resource::{Resources, SystemUniforms, SYSTEM_RESOURCE};
+use crate::scope;
+
+/// Largest width or height of a capture, which keeps the decoded image within
+/// what a wasm module can reasonably allocate
+pub const MAX_CAPTURE_SIZE: u32 = 16384;
+/// Largest width or height of a single tile, which bounds the size of the
+/// readback buffer regardless of the device's texture limit
+const MAX_TILE_SIZE: u32 = 4096;
+
+/// Region of the captured image rendered in one go
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Tile {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+/// Splits an image into tiles no larger than `tile_size` in either dimension,
+/// row by row
+fn tiles(width: u32, height: u32, tile_size: u32) -> impl Iterator<Item = Tile> {
+    (0..height).step_by(tile_size as usize).flat_map(move |y| {
+        (0..width).step_by(tile_size as usize).map(move |x| Tile {
+            x,
+            y,
+            width: tile_size.min(width - x),
+            height: tile_size.min(height - y),
+        })
+    })
+}
+
+/// Render target whose contents can be read back to the cpu
+#[derive(Debug)]
+pub(crate) struct Offscreen {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    readback: wgpu::Buffer,
+    /// Bytes per row of `readback`, which must be aligned
+    padded_row: u32,
+    size: (u32, u32),
+    /// Whether the target format is bgra, which is converted to rgba on read back
+    swap_red_blue: bool,
+}
+
+impl Offscreen {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, Error> {
+        let swap_red_blue = match format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => return Err(Error::CaptureFormat(format)),
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("capture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let padded_row = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("capture readback"),
+            size: padded_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Ok(Self {
+            texture,
+            view,
+            readback,
+            padded_row,
+            size: (width, height),
+            swap_red_blue,
+        })
+    }
+
+    pub(crate) fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub(crate) fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Submits `encoder`, then reads back the top left `tile.width` by `tile.height`
+    /// pixels of the target into `image` at the position of `tile`. Gpu errors are
+    /// left to the error scope of the caller, which knows what was encoded.
+    async fn read(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mut encoder: wgpu::CommandEncoder,
+        tile: Tile,
+        image: &mut Image,
+    ) -> Result<(), Error> {
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &self.readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(self.padded_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: tile.width,
+                height: tile.height,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let slice = self.readback.slice(..);
+        let (sender, receiver) = futures_channel::oneshot::channel();
+        slice.map_async(wgpu::MapMode::Read, move |res| {
+            let _ = sender.send(res);
+        });
+        // Drives the mapping on native, does nothing on the web
+        device.poll(wgpu::Maintain::Wait);
+        receiver.await.unwrap_or(Err(wgpu::BufferAsyncError))?;
+        {
+            let data = slice.get_mapped_range();
+            let row_len = tile.width as usize * 4;
+            for row in 0..tile.height as usize {
+                let src = &data[row * self.padded_row as usize..][..row_len];
+                let start = ((tile.y as usize + row) * image.width as usize + tile.x as usize) * 4;
+                let dst = &mut image.data[start..start + row_len];
+                dst.copy_from_slice(src);
+                if self.swap_red_blue {
+                    dst.chunks_exact_mut(4).for_each(|pixel| pixel.swap(0, 2));
+                }
+            }
+        }
+        self.readback.unmap();
+        Ok(())
+    }
+
+    /// Submits `encoder` and reads back the whole target as rgba
+    pub(crate) async fn read_all(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: wgpu::CommandEncoder,
+    ) -> Result<Vec<u8>, Error> {
+        let (width, height) = self.size;
+        let mut image = Image::new(width, height);
+        let tile = Tile {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        };
+        self.read(device, queue, encoder, tile, &mut image).await?;
+        Ok(image.data)
+    }
+}
+
+/// Rgba image with 8 bits per channel
+struct Image {
+    width: u32,
+    data: Vec<u8>,
+}
+
+impl Image {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            data: vec![0; width as usize * height as usize * 4],
+        }
+    }
+}
+
+/// Renders every render pass into an offscreen target and encodes the result as png.
+///
+/// Compute passes are not run, so capturing leaves resources as they are. Images
+/// larger than the device's texture limit are rendered in tiles, with `system.offset`
+/// set to the position of each tile. Tiles are only seamless if shaders add it to
+/// their fragment position, so every render pass must declare it to be tiled.
+pub(crate) async fn capture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    passes: &Passes,
+    resources: &Resources,
+    format: wgpu::TextureFormat,
+    mut system: SystemUniforms,
+    options: &CaptureOptions,
+) -> Result<Vec<u8>, Error> {
+    let CaptureOptions { width, height, .. } = *options;
+    if width == 0 || height == 0 || width > MAX_CAPTURE_SIZE || height > MAX_CAPTURE_SIZE {
+        return Err(Error::CaptureSize(width, height));
+    }
+    let tile_size = device.limits().max_texture_dimension_2d.min(MAX_TILE_SIZE);
+    // Fragment positions are relative to the tile, so shaders have to add the
+    // offset themselves or every tile would show the top left of the image
+    if width > tile_size || height > tile_size {
+        // Shaders agree on the layout of system uniforms, so if it lacks the
+        // offset every pass does
+        let unaware =
+            passes.render_pass_without(resources.system_has_offset().then_some(SYSTEM_RESOURCE));
+        if let Some(key) = unaware {
+            return Err(Error::CaptureTiles(width, height, key.file.clone()));
+        }
+    }
+    let target = Offscreen::new(device, format, width.min(tile_size), height.min(tile_size))?;
+
+    if let Some(time) = options.time {
+        system.time = time;
+    }
+    system.resolution = [width as f32, height as f32];
+
+    let mut image = Image::new(width, height);
+    for tile in tiles(width, height, tile_size) {
+        system.offset = [tile.x as f32, tile.y as f32];
+        resources.write_system(queue, &system);
+
+        scope::push(device);
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        passes.encode_kind(
+            PassKind::Render,
+            &mut encoder,
+            target.view(),
+            target.size(),
+            &mut Profiler::default(),
+        );
+        let read = target.read(device, queue, encoder, tile, &mut image).await;
+        if let Some(err) = scope::pop(device).await {
+            let err = passes
+                .attribute(
+                    err,
+                    &[PassKind::Render],
+                    device,
+                    target.view(),
+                    target.size(),
+                )
+                .await;
+            return Err(err.into());
+        }
+        read?;
+    }
+
+    Ok(encode_png(width, height, &image.data)?)
+}
+
+pub(crate) fn encode_png(
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+) -> Result<Vec<u8>, png::EncodingError> {
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_srgb(png::SrgbRenderingIntent::Perceptual);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgba)?;
+    writer.finish()?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_png, tiles, Tile};
+
+    #[test]
+    fn test_tiles_cover_image() {
+        let tiles: Vec<_> = tiles(10, 5, 4).collect();
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(
+            tiles[2],
+            Tile {
+                x: 8,
+                y: 0,
+                width: 2,
+                height: 4
+            }
+        );
+        assert_eq!(
+            tiles[5],
+            Tile {
+                x: 8,
+                y: 4,
+                width: 2,
+                height: 1
+            }
+        );
+        let area: u32 = tiles.iter().map(|tile| tile.width * tile.height).sum();
+        assert_eq!(area, 50);
+    }
+
+    #[test]
+    fn test_encode_png() {
+        let bytes = encode_png(2, 1, &[255, 0, 0, 255, 0, 255, 0, 255]).unwrap();
+        assert_eq!(&bytes[..8], b"\x89PNG\r\n\x1a\n");
+    }
+}
@@ -1,31 +1,205 @@
 use thiserror::Error;
 
+pub use gpu_common::{GpuError, GpuErrorKind, GpuErrorPhase};
+
 use winit::event_loop::EventLoop;
 use winit::window::WindowBuilder;
 
+use gpu_common::{
+    BuildDiagnostic, BuildEvent, Event, FrameEvent, InputEvent, ParamUpdate, PerformanceLevel,
+};
+
+use crate::capture::{CaptureOptions, Offscreen};
+use crate::event::Events;
+use crate::pass::{PassKind, Passes};
+use crate::profiler::Profiler;
+use crate::record::{RecordFormat, RecordOptions, Recorder};
+use crate::resource::{Resources, SystemUniforms};
+use crate::scope;
+
+#[cfg(target_arch = "wasm32")]
+const BACKENDS: wgpu::Backends = wgpu::Backends::BROWSER_WEBGPU;
+/// Vulkan, Metal or DX12, whichever the platform has
+#[cfg(not(target_arch = "wasm32"))]
+const BACKENDS: wgpu::Backends = wgpu::Backends::PRIMARY;
+/// Format frames of a headless context are rendered in, which needs no conversion
+/// when read back
+const HEADLESS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+/// Time to wait after the last resize before reconfiguring the surface, so that
+/// dragging a panel does not reallocate the surface every frame
+const RESIZE_DEBOUNCE_MS: f64 = 100.0;
+/// Range `Config.render_scale` is clamped to
+const RENDER_SCALE_RANGE: std::ops::RangeInclusive<f32> = 0.1..=4.0;
+/// Time between stats events while profiling
+const STATS_INTERVAL_MS: f64 = 1000.0;
+/// Frame rate cap with `PerformanceLevel::PowerSaver`
+const POWER_SAVER_FPS: u32 = 30;
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("Placeholder error!")]
     Placeholder,
     #[error("Could not create window: {0}")]
     WindowCreation(winit::error::OsError),
+    #[error("Offscreen canvases can only be rendered to on wasm")]
+    OffscreenUnsupported,
+    #[error("Frames can only be read back from a headless context")]
+    NotHeadless,
     #[error("Failed to obtain adapter")]
     NoAdapter,
     #[error(transparent)]
     DeviceCreationFailed(#[from] wgpu::RequestDeviceError),
+    #[error("Failed to compile project:\n{0}")]
+    Compile(#[from] gpu_compiler::Error),
+    #[error("Could not acquire frame: {0}")]
+    Surface(#[from] wgpu::SurfaceError),
+    #[error(transparent)]
+    Gpu(#[from] GpuError),
+    #[error("Can not capture image of size {0}x{1}")]
+    CaptureSize(u32, u32),
+    #[error("Can not capture from surface with format {0:?}")]
+    CaptureFormat(wgpu::TextureFormat),
+    #[error(
+        "Can not capture image of size {0}x{1} in tiles, as `{2}` does not declare `system.offset`"
+    )]
+    CaptureTiles(u32, u32, String),
+    #[error("Could not read back captured image")]
+    CaptureReadback(#[from] wgpu::BufferAsyncError),
+    #[error("Could not encode png: {0}")]
+    PngEncoding(#[from] png::EncodingError),
+    #[error("Could not encode gif: {0}")]
+    GifEncoding(#[from] gif::EncodingError),
+    #[error("Can not record {1} seconds at {0} fps")]
+    RecordRange(u32, f32),
+    #[error("Recordings in {0:?} format are not encoded by the caller")]
+    RecordEncoded(RecordFormat),
+    #[error("Nothing to record, project has not been built")]
+    RecordNoProject,
+    #[error("No resource named `{0}`")]
+    UnknownResource(String),
+    #[error("Can not write bytes {1}..{2} of resource `{0}`")]
+    ParamRange(String, u64, u64),
+}
+
+/// What the surface presents to
+#[allow(dead_code, clippy::large_enum_variant)]
+#[derive(Debug)]
+enum Canvas {
+    /// Canvas of a winit window, appended to the document on wasm
+    Window {
+        window: winit::window::Window,
+        event_loop: EventLoop<()>,
+    },
+    /// Canvas without a window, i.e. one transferred to a worker
+    Offscreen(web_sys::OffscreenCanvas),
+    /// No canvas at all, frames are read back by the host instead
+    Headless,
+}
+
+/// What frames are rendered to
+#[derive(Debug)]
+enum Target {
+    Surface(wgpu::Surface),
+    /// Texture the host reads frames back from with [`Context::read_frame`]
+    Headless(Offscreen),
+}
+
+impl Target {
+    /// Resizes the target to match `config`
+    fn configure(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        match self {
+            Target::Surface(surface) => surface.configure(device, config),
+            Target::Headless(target) => {
+                match Offscreen::new(device, config.format, config.width, config.height) {
+                    Ok(resized) => *target = resized,
+                    Err(err) => log::error!("Could not resize headless target: {err}"),
+                }
+            }
+        }
+    }
+
+    /// Frame to render to next, or `None` if this frame should be skipped
+    fn acquire(
+        &self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> Result<Option<TargetFrame<'_>>, wgpu::SurfaceError> {
+        let surface = match self {
+            Target::Surface(surface) => surface,
+            Target::Headless(target) => return Ok(Some(TargetFrame::Headless(target.view()))),
+        };
+        match surface.get_current_texture() {
+            Ok(texture) => {
+                let view = texture
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                Ok(Some(TargetFrame::Surface(texture, view)))
+            }
+            // Surface needs to be reconfigured, or gpu is busy. Either way, skip this frame
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                surface.configure(device, config);
+                Ok(None)
+            }
+            Err(wgpu::SurfaceError::Timeout) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+enum TargetFrame<'a> {
+    Surface(wgpu::SurfaceTexture, wgpu::TextureView),
+    Headless(&'a wgpu::TextureView),
+}
+
+impl TargetFrame<'_> {
+    fn view(&self) -> &wgpu::TextureView {
+        match self {
+            TargetFrame::Surface(_, view) => view,
+            TargetFrame::Headless(view) => view,
+        }
+    }
+
+    fn present(self) {
+        if let TargetFrame::Surface(texture, _) = self {
+            texture.present();
+        }
+    }
 }
 
 #[allow(dead_code)]
 #[derive(Debug)]
 pub struct Context {
-    window: winit::window::Window,
-    event_loop: EventLoop<()>,
+    canvas: Canvas,
     instance: wgpu::Instance,
+    /// Size of surface in pixels, after render scale and clamping
     size: winit::dpi::PhysicalSize<u32>,
-    surface: wgpu::Surface,
+    /// Size of canvas in css pixels along with the device pixel ratio, as last passed to `resize`
+    viewport: Option<(winit::dpi::LogicalSize<f64>, f64)>,
+    /// Size the surface will be reconfigured to, and the time it was requested
+    pending_resize: Option<(winit::dpi::PhysicalSize<u32>, f64)>,
+    render_scale: f32,
+    /// Minimum time between frames in milliseconds, from the project config
+    frame_interval: f64,
+    target: Target,
     adapter: wgpu::Adapter,
     device: wgpu::Device,
     queue: wgpu::Queue,
+    surface_config: wgpu::SurfaceConfiguration,
+    resources: Resources,
+    passes: Passes,
+    system: SystemUniforms,
+    profiler: Profiler,
+    /// Time in milliseconds that the project started running. Kept across builds,
+    /// see [`Context::restart`].
+    build_time: f64,
+    /// Last project that built successfully, used to rebuild after device loss
+    last_good: Option<gpu_compiler::CompiledProject>,
+    /// Set once a failed frame finds the device lost, see [`scope::is_lost`], until
+    /// [`Context::recover`] succeeds
+    device_lost: bool,
+    events: Events,
+    /// Time stats were last emitted in milliseconds
+    last_stats: f64,
 }
 
 impl Context {
@@ -37,8 +211,7 @@ impl Context {
             .build(&event_loop)
             .map_err(Error::WindowCreation)?;
 
-        let backend = wgpu::Backends::BROWSER_WEBGPU;
-        let instance = wgpu::Instance::new(backend);
+        let instance = wgpu::Instance::new(BACKENDS);
 
         #[cfg(target_arch = "wasm32")]
         {
@@ -60,50 +233,645 @@ impl Context {
         }
 
         log::info!("Initializing the surface...");
-        #[cfg(not(target_arch = "wasm32"))]
         let surface = unsafe { instance.create_surface(&window) };
-        #[cfg(target_arch = "wasm32")]
-        let surface = unsafe { instance.create_surface(&window) };
-
         let size = window.inner_size();
+        Self::with_target(
+            instance,
+            Some(surface),
+            size,
+            Canvas::Window { window, event_loop },
+        )
+        .await
+    }
 
-        let adapter = wgpu::util::initialize_adapter_from_env_or_default(&instance, backend, None)
-            .await
-            .ok_or(Error::NoAdapter)?;
-
-        let features = adapter.features();
-        log::debug!("Adapter features: {features:?}");
+    /// Creates a context rendering to `canvas`, which may have been transferred to
+    /// a worker so that rendering does not block the page
+    pub async fn new_offscreen(canvas: web_sys::OffscreenCanvas) -> Result<Context, Error> {
+        log::info!("Calling new offscreen context from rust");
+        let instance = wgpu::Instance::new(BACKENDS);
+        let surface = offscreen_surface(&instance, &canvas)?;
+        let size = winit::dpi::PhysicalSize::new(canvas.width(), canvas.height());
+        Self::with_target(instance, Some(surface), size, Canvas::Offscreen(canvas)).await
+    }
 
-        let downlevel_capabilities = adapter.get_downlevel_capabilities();
-        log::debug!("Adapter downlevel capabilities: {downlevel_capabilities:?}");
+    /// Creates a context without a surface, rendering frames of `width` by `height`
+    /// pixels that are read back with [`Context::read_frame`].
+    ///
+    /// Used by native hosts whose windows wgpu can not present to directly.
+    pub async fn new_headless(width: u32, height: u32) -> Result<Context, Error> {
+        log::info!("Calling new headless context from rust");
+        let instance = wgpu::Instance::new(BACKENDS);
+        let size = winit::dpi::PhysicalSize::new(width, height);
+        Self::with_target(instance, None, size, Canvas::Headless).await
+    }
 
-        let limits = adapter.limits();
-        log::debug!("Adapter limits: {limits:?}");
+    /// Creates a context presenting to `surface`, or a headless one without it
+    async fn with_target(
+        instance: wgpu::Instance,
+        surface: Option<wgpu::Surface>,
+        size: winit::dpi::PhysicalSize<u32>,
+        canvas: Canvas,
+    ) -> Result<Context, Error> {
+        let events = Events::default();
+        let (adapter, device, queue) = request_device(&instance, &events).await?;
 
-        let desc = wgpu::DeviceDescriptor {
-            label: None,
-            features,
-            limits,
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: surface.as_ref().map_or(HEADLESS_FORMAT, |surface| {
+                surface.get_supported_formats(&adapter)[0]
+            }),
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
         };
-        let (device, queue) = adapter.request_device(&desc, None).await?;
+        let target = match surface {
+            Some(surface) => {
+                surface.configure(&device, &surface_config);
+                Target::Surface(surface)
+            }
+            None => Target::Headless(Offscreen::new(
+                &device,
+                surface_config.format,
+                surface_config.width,
+                surface_config.height,
+            )?),
+        };
+        let profiler = Profiler::new(&device, &queue);
 
         Ok(Context {
-            event_loop,
-            window,
+            canvas,
             adapter,
             device,
             queue,
             instance,
             size,
-            surface,
+            viewport: None,
+            pending_resize: None,
+            render_scale: 1.0,
+            frame_interval: 0.0,
+            target,
+            surface_config,
+            resources: Resources::default(),
+            passes: Passes::default(),
+            system: SystemUniforms::default(),
+            profiler,
+            build_time: crate::time::now(),
+            last_good: None,
+            device_lost: false,
+            events,
+            last_stats: 0.0,
         })
     }
 
-    pub async fn build(&mut self, _project: &gpu_common::Project) -> Result<(), Error> {
+    /// Builds project incrementally on top of the current one.
+    ///
+    /// Only pipelines of changed shaders and bind groups of changed resources are
+    /// recreated, so resources keep their contents across rebuilds. If the project
+    /// fails to compile, the current passes are left in place.
+    ///
+    /// Emits a build event with the outcome, along with an error event if the gpu
+    /// failed to create a pipeline.
+    pub async fn build(&mut self, project: &gpu_common::Project) -> Result<(), Error> {
+        let res = self.build_project(project).await;
+        let diagnostics = match res {
+            Err(Error::Compile(ref err)) => err.0.iter().map(BuildDiagnostic::from).collect(),
+            _ => Vec::new(),
+        };
+        self.events.push(Event::Build(BuildEvent {
+            success: res.is_ok(),
+            diagnostics,
+        }));
+        if let Err(Error::Gpu(ref err)) = res {
+            self.events.push(Event::Error(err.clone()));
+        }
+        res
+    }
+
+    /// Events emitted since the last call, oldest first
+    pub fn drain_events(&self) -> Vec<Event> {
+        self.events.drain()
+    }
+
+    async fn build_project(&mut self, project: &gpu_common::Project) -> Result<(), Error> {
+        let compiled = gpu_compiler::CompiledProject::compile(&project.files)?;
+        self.apply(compiled).await?;
+        let config = project.config.as_ref();
+        let limit_fps = config.map_or(0, limit_fps);
+        self.profiler.set_limit_fps(limit_fps);
+        self.frame_interval = match limit_fps {
+            0 => 0.0,
+            fps => 1000.0 / fps as f64,
+        };
+        let render_scale = config.map_or(1.0, |config| config.render_scale);
+        self.render_scale =
+            render_scale.clamp(*RENDER_SCALE_RANGE.start(), *RENDER_SCALE_RANGE.end());
+        self.request_resize();
+        Ok(())
+    }
+
+    /// Resizes the surface to a canvas of `width` by `height` css pixels.
+    ///
+    /// The surface is only reconfigured once no resize was requested for a short
+    /// while, until then frames are rendered at the previous size.
+    pub fn resize(&mut self, width: f64, height: f64, scale_factor: f64) {
+        self.viewport = Some((winit::dpi::LogicalSize::new(width, height), scale_factor));
+        self.request_resize();
+    }
+
+    fn request_resize(&mut self) {
+        let (logical, scale_factor) = match self.viewport {
+            Some(viewport) => viewport,
+            None => return,
+        };
+        let size = surface_size(
+            logical,
+            scale_factor * self.render_scale as f64,
+            self.device.limits().max_texture_dimension_2d,
+        );
+        self.pending_resize = (size != self.size).then(|| (size, crate::time::now()));
+    }
+
+    /// Reconfigures the surface if a resize was requested long enough ago, and
+    /// recreates resources sized by the frame
+    async fn apply_resize(&mut self) -> Result<(), Error> {
+        let size = match self.pending_resize {
+            Some((size, requested)) if crate::time::now() - requested >= RESIZE_DEBOUNCE_MS => size,
+            _ => return Ok(()),
+        };
+        self.pending_resize = None;
+        log::info!("Resizing surface to {}x{}", size.width, size.height);
+        self.size = size;
+        self.surface_config.width = size.width;
+        self.surface_config.height = size.height;
+        self.target.configure(&self.device, &self.surface_config);
+
+        #[cfg(target_arch = "wasm32")]
+        match self.canvas {
+            Canvas::Window { ref window, .. } => {
+                use winit::platform::web::WindowExtWebSys;
+                let canvas = window.canvas();
+                canvas.set_width(size.width);
+                canvas.set_height(size.height);
+            }
+            Canvas::Offscreen(ref canvas) => {
+                canvas.set_width(size.width);
+                canvas.set_height(size.height);
+            }
+            Canvas::Headless => {}
+        }
+
+        if let Some(compiled) = self.last_good.clone() {
+            self.update_passes(&compiled).await?;
+        }
         Ok(())
     }
 
-    pub async fn render(&self) -> Result<(), Error> {
+    /// Handles input on the canvas, in css pixels
+    pub fn input(&mut self, event: InputEvent) {
+        match event {
+            InputEvent::PointerMove { x, y } => {
+                // Relative to the rendered image, which may be scaled from the canvas
+                let scale = match self.viewport {
+                    Some((logical, _)) if logical.width > 0.0 => {
+                        self.size.width as f64 / logical.width
+                    }
+                    _ => 1.0,
+                };
+                self.system.mouse = [(x * scale) as f32, (y * scale) as f32];
+            }
+        }
+    }
+
+    /// Writes `values` into a resource at a byte offset, i.e. to tweak uniforms
+    pub fn set_param(&mut self, update: &ParamUpdate) -> Result<(), Error> {
+        let resource = self
+            .resources
+            .get(&update.resource)
+            .ok_or_else(|| Error::UnknownResource(update.resource.clone()))?;
+        let data: &[u8] = bytemuck::cast_slice(&update.values);
+        let end = update.offset + data.len() as u64;
+        if !update.offset.is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT) || end > resource.size {
+            return Err(Error::ParamRange(
+                update.resource.clone(),
+                update.offset,
+                end,
+            ));
+        }
+        self.queue
+            .write_buffer(&resource.buffer, update.offset, data);
         Ok(())
     }
+
+    /// Restarts time and frame count from zero, i.e. for the Reset action, as
+    /// builds keep both running
+    pub fn restart(&mut self) {
+        self.build_time = crate::time::now();
+        self.system.frame = 0;
+    }
+
+    /// Minimum time between frames in milliseconds, or 0 if the frame rate is not
+    /// limited. Pacing frames is up to whoever calls `render`.
+    pub fn frame_interval(&self) -> f64 {
+        self.frame_interval
+    }
+
+    /// Starts or stops collecting pass and frame timings
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profiler.set_enabled(enabled);
+    }
+
+    /// Timings collected since profiling was enabled or the project was last built
+    pub fn profile(&self) -> gpu_common::ProfileSnapshot {
+        self.profiler.snapshot()
+    }
+
+    pub async fn render(&mut self) -> Result<(), Error> {
+        if self.device_lost {
+            return self.recover().await;
+        }
+        let mut res = self.render_frame().await;
+        if let Err(Error::Gpu(ref mut err)) = res {
+            if scope::is_lost(&self.device).await {
+                self.device_lost = true;
+                err.kind = GpuErrorKind::DeviceLost;
+            }
+            self.events.push(Event::Error(err.clone()));
+        }
+        if self.device_lost {
+            return self.recover().await;
+        }
+        res
+    }
+
+    /// Renders the current project to a png image, independent of the surface size
+    pub async fn capture(&mut self, options: &CaptureOptions) -> Result<Vec<u8>, Error> {
+        let mut system = self.system;
+        system.time = self.elapsed_secs();
+        crate::capture::capture(
+            &self.device,
+            &self.queue,
+            &self.passes,
+            &self.resources,
+            self.surface_config.format,
+            system,
+            options,
+        )
+        .await
+    }
+
+    /// Starts recording the current project offline.
+    ///
+    /// The recording gets fresh resources and passes of its own, so that every
+    /// recording of the same project starts from the same state and the live
+    /// project keeps running as it was. Frames are then rendered one by one with
+    /// [`Context::record_frame`].
+    pub async fn start_recording(&mut self, options: RecordOptions) -> Result<Recorder, Error> {
+        let max_size = self.device.limits().max_texture_dimension_2d;
+        if options.width == 0
+            || options.height == 0
+            || options.width > max_size
+            || options.height > max_size
+        {
+            return Err(Error::CaptureSize(options.width, options.height));
+        }
+        let target = Offscreen::new(
+            &self.device,
+            self.surface_config.format,
+            options.width,
+            options.height,
+        )?;
+        let compiled = self.last_good.as_ref().ok_or(Error::RecordNoProject)?;
+        let mut resources = Resources::default();
+        let created = resources.update(&self.device, compiled, (options.width, options.height));
+        let mut passes = Passes::default();
+        passes
+            .update(
+                &self.device,
+                compiled,
+                &resources,
+                &created,
+                self.surface_config.format,
+            )
+            .await?;
+        Recorder::new(options, target, resources, passes)
+    }
+
+    /// Renders the next frame of `recorder`, running every pass once.
+    ///
+    /// Returns the raw rgba frame when the recording format is encoded by the caller,
+    /// see [`RecordFormat::Webm`]. Does nothing once every frame is rendered.
+    pub async fn record_frame(
+        &mut self,
+        recorder: &mut Recorder,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let (frame, time) = match recorder.next_frame() {
+            Some(next) => next,
+            None => return Ok(None),
+        };
+        let size = recorder.target.size();
+        let system = SystemUniforms {
+            time,
+            frame,
+            resolution: [size.0 as f32, size.1 as f32],
+            ..Default::default()
+        };
+        recorder.resources.write_system(&self.queue, &system);
+
+        scope::push(&self.device);
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let view = recorder.target.view();
+        recorder
+            .passes
+            .encode(&mut encoder, view, size, &mut Profiler::default());
+        let rgba = recorder
+            .target
+            .read_all(&self.device, &self.queue, encoder)
+            .await;
+        if let Some(err) = scope::pop(&self.device).await {
+            let kinds = [PassKind::Compute, PassKind::Render];
+            let err = recorder
+                .passes
+                .attribute(err, &kinds, &self.device, view, size)
+                .await;
+            return Err(err.into());
+        }
+        recorder.push_frame(rgba?)
+    }
+
+    /// Reads back the last frame rendered by a headless context as rgba, row by row
+    pub async fn read_frame(&self) -> Result<Vec<u8>, Error> {
+        let target = match self.target {
+            Target::Headless(ref target) => target,
+            Target::Surface(_) => return Err(Error::NotHeadless),
+        };
+        scope::push(&self.device);
+        let encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let rgba = target.read_all(&self.device, &self.queue, encoder).await;
+        if let Some(err) = scope::pop(&self.device).await {
+            return Err(scope::gpu_error(err, GpuErrorPhase::Frame).into());
+        }
+        rgba
+    }
+
+    /// Size of frames in pixels, after render scale and clamping
+    pub fn frame_size(&self) -> (u32, u32) {
+        (self.surface_config.width, self.surface_config.height)
+    }
+
+    /// Seconds since the project was first built or last restarted
+    fn elapsed_secs(&self) -> f32 {
+        ((crate::time::now() - self.build_time) / 1000.0) as f32
+    }
+
+    /// Updates resources and passes to match `compiled`, which becomes the last
+    /// project that built successfully
+    async fn apply(&mut self, compiled: gpu_compiler::CompiledProject) -> Result<(), Error> {
+        self.update_passes(&compiled).await?;
+        self.profiler.clear();
+        if self.last_good.is_none() {
+            self.restart();
+        }
+        self.last_good = Some(compiled);
+        Ok(())
+    }
+
+    /// Updates resources and passes to match `compiled` for frames of the current
+    /// size.
+    ///
+    /// Both are updated on copies that share unchanged buffers and pipelines, and
+    /// only replace the current ones once every pass was created against the new
+    /// resources. On failure, passes stay bound to resources as they were.
+    async fn update_passes(
+        &mut self,
+        compiled: &gpu_compiler::CompiledProject,
+    ) -> Result<(), Error> {
+        let mut resources = self.resources.clone();
+        let changed = resources.update(&self.device, compiled, self.frame_size());
+        let mut passes = self.passes.clone();
+        passes
+            .update(
+                &self.device,
+                compiled,
+                &resources,
+                &changed,
+                self.surface_config.format,
+            )
+            .await?;
+        self.resources = resources;
+        self.passes = passes;
+        Ok(())
+    }
+
+    async fn render_frame(&mut self) -> Result<(), Error> {
+        let start = crate::time::now();
+        self.apply_resize().await?;
+        let frame = match self.target.acquire(&self.device, &self.surface_config)? {
+            Some(frame) => frame,
+            None => {
+                self.profiler.drop_frame();
+                return Ok(());
+            }
+        };
+        let size = (self.surface_config.width, self.surface_config.height);
+
+        self.system.time = self.elapsed_secs();
+        self.system.resolution = [size.0 as f32, size.1 as f32];
+        self.resources.write_system(&self.queue, &self.system);
+        self.profiler.begin_frame(&self.device);
+
+        // Scoped as a whole, as a scope per pass would wait on the gpu for each
+        scope::push(&self.device);
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.passes
+            .encode(&mut encoder, frame.view(), size, &mut self.profiler);
+        self.profiler.resolve(&mut encoder);
+        self.queue.submit(Some(encoder.finish()));
+        if let Some(err) = scope::pop(&self.device).await {
+            let kinds = [PassKind::Compute, PassKind::Render];
+            let err = self
+                .passes
+                .attribute(err, &kinds, &self.device, frame.view(), size)
+                .await;
+            return Err(err.into());
+        }
+        self.profiler.end_frame();
+        frame.present();
+
+        self.system.frame += 1;
+        let now = crate::time::now();
+        self.events.push(Event::Frame(FrameEvent {
+            frame: self.system.frame,
+            time: self.system.time,
+            cpu_time: now - start,
+            width: size.0,
+            height: size.1,
+        }));
+        if self.profiler.enabled() && now - self.last_stats >= STATS_INTERVAL_MS {
+            self.last_stats = now;
+            self.events.push(Event::Stats(self.profiler.snapshot()));
+        }
+        Ok(())
+    }
+
+    /// Recreates device after it was lost, and rebuilds the last project that built
+    /// successfully. Resource contents can not be recovered.
+    ///
+    /// The device stays marked as lost until both succeed, so that the next frame
+    /// tries again.
+    async fn recover(&mut self) -> Result<(), Error> {
+        log::warn!("Gpu device lost, recreating device");
+
+        let (adapter, device, queue) = request_device(&self.instance, &self.events).await?;
+        self.adapter = adapter;
+        self.device = device;
+        self.queue = queue;
+        self.target.configure(&self.device, &self.surface_config);
+
+        self.resources = Resources::default();
+        self.passes = Passes::default();
+        let mut profiler = Profiler::new(&self.device, &self.queue);
+        profiler.set_enabled(self.profiler.enabled());
+        self.profiler = profiler;
+        // Kept until a rebuild succeeds, should this one fail
+        if let Some(compiled) = self.last_good.clone() {
+            self.apply(compiled).await?;
+        }
+        self.device_lost = false;
+        log::info!("Gpu device recreated");
+        Ok(())
+    }
+}
+
+/// Size of surface for a canvas of `logical` css pixels, scaled by `scale_factor`.
+/// Sizes over `max_dimension` are scaled down, keeping their aspect ratio.
+fn surface_size(
+    logical: winit::dpi::LogicalSize<f64>,
+    scale_factor: f64,
+    max_dimension: u32,
+) -> winit::dpi::PhysicalSize<u32> {
+    let width = logical.width * scale_factor;
+    let height = logical.height * scale_factor;
+    let fit = (max_dimension as f64 / width.max(height)).min(1.0);
+    winit::dpi::PhysicalSize::new(
+        ((width * fit).round() as u32).clamp(1, max_dimension),
+        ((height * fit).round() as u32).clamp(1, max_dimension),
+    )
+}
+
+async fn request_device(
+    instance: &wgpu::Instance,
+    events: &Events,
+) -> Result<(wgpu::Adapter, wgpu::Device, wgpu::Queue), Error> {
+    let adapter = wgpu::util::initialize_adapter_from_env_or_default(instance, BACKENDS, None)
+        .await
+        .ok_or(Error::NoAdapter)?;
+
+    let features = adapter.features();
+    log::debug!("Adapter features: {features:?}");
+
+    let downlevel_capabilities = adapter.get_downlevel_capabilities();
+    log::debug!("Adapter downlevel capabilities: {downlevel_capabilities:?}");
+
+    let limits = adapter.limits();
+    log::debug!("Adapter limits: {limits:?}");
+
+    let desc = wgpu::DeviceDescriptor {
+        label: None,
+        features,
+        limits,
+    };
+    let (device, queue) = adapter.request_device(&desc, None).await?;
+
+    // Errors are captured with error scopes wherever they can be attributed to
+    // something in the project. Anything else is logged instead of panicking.
+    let events = events.clone();
+    device.on_uncaptured_error(move |err| {
+        log::error!("Uncaptured gpu error: {err}");
+        events.push(Event::Error(scope::gpu_error(
+            err,
+            GpuErrorPhase::Uncaptured,
+        )));
+    });
+
+    Ok((adapter, device, queue))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn offscreen_surface(
+    instance: &wgpu::Instance,
+    canvas: &web_sys::OffscreenCanvas,
+) -> Result<wgpu::Surface, Error> {
+    Ok(instance.create_surface_from_offscreen_canvas(canvas))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn offscreen_surface(
+    _instance: &wgpu::Instance,
+    _canvas: &web_sys::OffscreenCanvas,
+) -> Result<wgpu::Surface, Error> {
+    Err(Error::OffscreenUnsupported)
+}
+
+/// Effective frame rate limit of a project, or 0 for no limit
+fn limit_fps(config: &gpu_common::Config) -> u32 {
+    let power_saver = matches!(config.perf_level, Some(PerformanceLevel::PowerSaver));
+    match (config.limit_fps, power_saver) {
+        (0, true) => POWER_SAVER_FPS,
+        (fps, true) => fps.min(POWER_SAVER_FPS),
+        (fps, false) => fps,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gpu_common::{Config, PerformanceLevel};
+    use winit::dpi::{LogicalSize, PhysicalSize};
+
+    use super::{limit_fps, surface_size};
+
+    #[test]
+    fn test_surface_size() {
+        let size = surface_size(LogicalSize::new(800.0, 600.0), 2.0, 8192);
+        assert_eq!(size, PhysicalSize::new(1600, 1200));
+
+        // Clamped to the texture limit, keeping aspect ratio
+        let size = surface_size(LogicalSize::new(8000.0, 2000.0), 2.0, 8192);
+        assert_eq!(size, PhysicalSize::new(8192, 2048));
+
+        // Never empty
+        let size = surface_size(LogicalSize::new(0.0, 100.0), 0.5, 8192);
+        assert_eq!(size, PhysicalSize::new(1, 50));
+    }
+
+    #[test]
+    fn test_limit_fps() {
+        let config = |perf_level, limit_fps| Config {
+            perf_level,
+            limit_fps,
+            render_scale: 1.0,
+        };
+        assert_eq!(limit_fps(&config(None, 0)), 0);
+        assert_eq!(
+            limit_fps(&config(Some(PerformanceLevel::Default), 144)),
+            144
+        );
+        assert_eq!(
+            limit_fps(&config(Some(PerformanceLevel::PowerSaver), 0)),
+            30
+        );
+        assert_eq!(
+            limit_fps(&config(Some(PerformanceLevel::PowerSaver), 24)),
+            24
+        );
+        assert_eq!(
+            limit_fps(&config(Some(PerformanceLevel::PowerSaver), 60)),
+            30
+        );
+    }
 }
@@ -0,0 +1,54 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use gpu_common::Event;
+
+/// Events kept when nobody drains the queue, older ones are dropped first
+const MAX_QUEUED_EVENTS: usize = 256;
+
+/// Queue of events emitted by the context, waiting to be handed to subscribers.
+///
+/// Shared with the uncaptured error handler, which may be called from another thread.
+#[derive(Debug, Clone, Default)]
+pub struct Events(Arc<Mutex<VecDeque<Event>>>);
+
+impl Events {
+    pub fn push(&self, event: Event) {
+        let mut queue = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        if queue.len() == MAX_QUEUED_EVENTS {
+            queue.pop_front();
+        }
+        queue.push_back(event);
+    }
+
+    /// Takes every queued event, oldest first
+    pub fn drain(&self) -> Vec<Event> {
+        let mut queue = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        queue.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gpu_common::{BuildEvent, Event};
+
+    use super::{Events, MAX_QUEUED_EVENTS};
+
+    #[test]
+    fn test_drops_oldest_events() {
+        let events = Events::default();
+        for i in 0..MAX_QUEUED_EVENTS + 1 {
+            events.push(Event::Build(BuildEvent {
+                success: i % 2 == 0,
+                diagnostics: Vec::new(),
+            }));
+        }
+        let drained = events.drain();
+        assert_eq!(drained.len(), MAX_QUEUED_EVENTS);
+        assert!(matches!(
+            drained[0],
+            Event::Build(BuildEvent { success: false, .. })
+        ));
+        assert!(events.drain().is_empty());
+    }
+}
@@ -1 +1,10 @@
+pub mod capture;
 pub mod context;
+pub mod event;
+pub mod pass;
+pub mod profiler;
+pub mod record;
+pub mod resource;
+mod scope;
+mod time;
+pub mod webm;
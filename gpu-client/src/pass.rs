@@ -0,0 +1,404 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+use gpu_compiler::{CompiledProject, CompiledShader, EntryPoint, Stage};
+
+pub use gpu_common::PassKind;
+
+use crate::context::{GpuError, GpuErrorPhase};
+use crate::profiler::Profiler;
+use crate::resource::Resources;
+use crate::scope;
+
+/// Vertex shader used by fragment passes whose file has no vertex entry point.
+/// Draws a single triangle covering the whole render target.
+const FULLSCREEN_VERTEX: &str = r#"
+@vertex
+fn main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+"#;
+
+/// Identifies a pass by shader file and entry point
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PassKey {
+    pub file: String,
+    pub entry_point: String,
+}
+
+#[derive(Debug)]
+enum Pipeline {
+    Render(wgpu::RenderPipeline),
+    Compute {
+        pipeline: wgpu::ComputePipeline,
+        workgroup_size: [u32; 3],
+    },
+}
+
+/// Pass of a shader entry point. Clones share the pipeline and bind groups, so
+/// that passes can be updated on a copy, see [`Passes::update`].
+#[derive(Debug, Clone)]
+pub struct Pass {
+    /// Hash of the shader this pass was created from
+    shader_hash: u64,
+    pipeline: Arc<Pipeline>,
+    bind_groups: Arc<Vec<(u32, wgpu::BindGroup)>>,
+    /// Names of resources bound to this pass
+    resources: Vec<String>,
+}
+
+impl Pass {
+    fn new(
+        device: &wgpu::Device,
+        shader: &CompiledShader,
+        entry_point: &EntryPoint,
+        module: &wgpu::ShaderModule,
+        fullscreen: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        resources: &Resources,
+    ) -> Self {
+        let label = format!("{}:{}", shader.path, entry_point.name);
+        let pipeline = match entry_point.stage {
+            Stage::Compute => Pipeline::Compute {
+                pipeline: device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some(&label),
+                    layout: None,
+                    module,
+                    entry_point: &entry_point.name,
+                }),
+                workgroup_size: entry_point.workgroup_size,
+            },
+            _ => {
+                let vertex = shader.entry_point(Stage::Vertex);
+                let vertex = wgpu::VertexState {
+                    module: vertex.map(|_| module).unwrap_or(fullscreen),
+                    entry_point: vertex.map(|ep| ep.name.as_str()).unwrap_or("main"),
+                    buffers: &[],
+                };
+                Pipeline::Render(
+                    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                        label: Some(&label),
+                        layout: None,
+                        vertex,
+                        primitive: wgpu::PrimitiveState::default(),
+                        depth_stencil: None,
+                        multisample: wgpu::MultisampleState::default(),
+                        fragment: Some(wgpu::FragmentState {
+                            module,
+                            entry_point: &entry_point.name,
+                            targets: &[Some(wgpu::ColorTargetState {
+                                format,
+                                blend: None,
+                                write_mask: wgpu::ColorWrites::ALL,
+                            })],
+                        }),
+                        multiview: None,
+                    }),
+                )
+            }
+        };
+
+        let mut pass = Self {
+            shader_hash: shader.hash,
+            pipeline: Arc::new(pipeline),
+            bind_groups: Arc::default(),
+            resources: shader.bindings.iter().map(|b| b.name.clone()).collect(),
+        };
+        pass.bind(device, shader, resources);
+        pass
+    }
+
+    /// Recreates bind groups against current resources, keeping the pipeline
+    fn bind(&mut self, device: &wgpu::Device, shader: &CompiledShader, resources: &Resources) {
+        let mut groups: BTreeMap<u32, Vec<wgpu::BindGroupEntry>> = BTreeMap::new();
+        for binding in shader.bindings.iter() {
+            if let Some(resource) = resources.get(&binding.name) {
+                groups
+                    .entry(binding.group)
+                    .or_default()
+                    .push(wgpu::BindGroupEntry {
+                        binding: binding.binding,
+                        resource: resource.buffer.as_entire_binding(),
+                    });
+            }
+        }
+
+        let bind_groups = groups
+            .into_iter()
+            .map(|(group, entries)| {
+                let layout = match *self.pipeline {
+                    Pipeline::Render(ref pipeline) => pipeline.get_bind_group_layout(group),
+                    Pipeline::Compute { ref pipeline, .. } => pipeline.get_bind_group_layout(group),
+                };
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: None,
+                    layout: &layout,
+                    entries: &entries,
+                });
+                (group, bind_group)
+            })
+            .collect();
+        self.bind_groups = Arc::new(bind_groups);
+    }
+
+    fn is_bound_to_any(&self, names: &HashSet<String>) -> bool {
+        self.resources.iter().any(|name| names.contains(name))
+    }
+
+    pub fn kind(&self) -> PassKind {
+        match *self.pipeline {
+            Pipeline::Render(_) => PassKind::Render,
+            Pipeline::Compute { .. } => PassKind::Compute,
+        }
+    }
+
+    fn encode(
+        &self,
+        key: &PassKey,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        size: (u32, u32),
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) {
+        match *self.pipeline {
+            Pipeline::Compute {
+                ref pipeline,
+                workgroup_size: [x, y, _],
+            } => {
+                let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some(&key.entry_point),
+                });
+                cpass.set_pipeline(pipeline);
+                for (group, bind_group) in self.bind_groups.iter() {
+                    cpass.set_bind_group(*group, bind_group, &[]);
+                }
+                cpass.dispatch_workgroups(size.0.div_ceil(x), size.1.div_ceil(y), 1);
+            }
+            Pipeline::Render(ref pipeline) => {
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some(&key.entry_point),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view,
+                        resolve_target: None,
+                        ops: wgpu::Operations { load, store: true },
+                    })],
+                    depth_stencil_attachment: None,
+                });
+                rpass.set_pipeline(pipeline);
+                for (group, bind_group) in self.bind_groups.iter() {
+                    rpass.set_bind_group(*group, bind_group, &[]);
+                }
+                rpass.draw(0..3, 0..1);
+            }
+        }
+    }
+}
+
+/// Every pass in the project. Compute passes run before render passes, and passes
+/// of the same kind run in order of file path.
+///
+/// Clones are cheap, as they share the gpu objects of every pass.
+#[derive(Debug, Default, Clone)]
+pub struct Passes {
+    map: BTreeMap<PassKey, Pass>,
+    /// Shader modules keyed by file path, along with the hash of their source
+    modules: HashMap<String, (u64, Arc<wgpu::ShaderModule>)>,
+    fullscreen: Option<Arc<wgpu::ShaderModule>>,
+}
+
+impl Passes {
+    /// Brings passes in line with `project`.
+    ///
+    /// Pipelines are only recreated for shaders whose source changed, and bind groups
+    /// are only recreated for passes bound to a resource within `changed_resources`.
+    /// If a pipeline fails to be created, the pass it would have replaced is kept and
+    /// the first error is returned once every other pass is updated.
+    pub async fn update(
+        &mut self,
+        device: &wgpu::Device,
+        project: &CompiledProject,
+        resources: &Resources,
+        changed_resources: &HashSet<String>,
+        format: wgpu::TextureFormat,
+    ) -> Result<(), GpuError> {
+        let fullscreen = self.fullscreen.get_or_insert_with(|| {
+            Arc::new(device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("fullscreen"),
+                source: wgpu::ShaderSource::Wgsl(FULLSCREEN_VERTEX.into()),
+            }))
+        });
+
+        let mut errors = Vec::new();
+        let mut old = std::mem::take(&mut self.map);
+        for (path, shader) in project.shaders.iter() {
+            if !matches!(self.modules.get(path), Some((hash, _)) if *hash == shader.hash) {
+                log::info!("Creating shader module for {path}");
+                scope::push(device);
+                let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some(path),
+                    source: wgpu::ShaderSource::Wgsl(shader.source.as_str().into()),
+                });
+                if let Some(err) = scope::pop(device).await {
+                    errors
+                        .push(scope::gpu_error(err, GpuErrorPhase::PipelineCreation).in_file(path));
+                    // Passes of the file are kept as they were, along with their module
+                    let (kept, rest) = std::mem::take(&mut old)
+                        .into_iter()
+                        .partition(|(key, _)| key.file == *path);
+                    old = rest;
+                    self.map.extend::<BTreeMap<_, _>>(kept);
+                    continue;
+                }
+                self.modules
+                    .insert(path.clone(), (shader.hash, Arc::new(module)));
+            }
+            let (_, module) = &self.modules[path];
+
+            let entry_points = shader
+                .entry_points
+                .iter()
+                .filter(|ep| matches!(ep.stage, Stage::Fragment | Stage::Compute));
+            for entry_point in entry_points {
+                let key = PassKey {
+                    file: path.clone(),
+                    entry_point: entry_point.name.clone(),
+                };
+                let pass =
+                    match old.remove(&key) {
+                        Some(mut pass) if pass.shader_hash == shader.hash => {
+                            if pass.is_bound_to_any(changed_resources) {
+                                log::info!("Rebinding pass {path}:{}", entry_point.name);
+                                scope::push(device);
+                                pass.bind(device, shader, resources);
+                                if let Some(err) = scope::pop(device).await {
+                                    errors.push(
+                                        scope::gpu_error(err, GpuErrorPhase::PipelineCreation)
+                                            .in_pass(&key.file, &key.entry_point, pass.kind()),
+                                    );
+                                }
+                            }
+                            Some(pass)
+                        }
+                        old_pass => {
+                            log::info!("Creating pass {path}:{}", entry_point.name);
+                            scope::push(device);
+                            let pass = Pass::new(
+                                device,
+                                shader,
+                                entry_point,
+                                module,
+                                fullscreen,
+                                format,
+                                resources,
+                            );
+                            match scope::pop(device).await {
+                                Some(err) => {
+                                    errors.push(
+                                        scope::gpu_error(err, GpuErrorPhase::PipelineCreation)
+                                            .in_pass(&key.file, &key.entry_point, pass.kind()),
+                                    );
+                                    old_pass
+                                }
+                                None => Some(pass),
+                            }
+                        }
+                    };
+                if let Some(pass) = pass {
+                    self.map.insert(key, pass);
+                }
+            }
+        }
+        self.modules
+            .retain(|path, _| project.shaders.contains_key(path));
+
+        let mut errors = errors.into_iter();
+        match errors.next() {
+            Some(err) => {
+                errors.for_each(|err| log::error!("{err}"));
+                Err(err)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Records every pass into `encoder`, rendering into `view`, timed by
+    /// `profiler` if enabled.
+    ///
+    /// Errors are not captured here, as a scope per pass would wait on the gpu
+    /// for every pass of every frame. Callers scope the frame as a whole, then
+    /// find the pass that failed with [`Passes::attribute`].
+    pub fn encode(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        size: (u32, u32),
+        profiler: &mut Profiler,
+    ) {
+        self.encode_kind(PassKind::Compute, encoder, view, size, profiler);
+        self.encode_kind(PassKind::Render, encoder, view, size, profiler);
+    }
+
+    /// Records only passes of the given kind, see [`Passes::encode`]
+    pub fn encode_kind(
+        &self,
+        kind: PassKind,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        size: (u32, u32),
+        profiler: &mut Profiler,
+    ) {
+        let mut load = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+        for (key, pass) in self.of_kind(kind) {
+            profiler.begin_pass(encoder, key);
+            pass.encode(key, encoder, view, size, load);
+            profiler.end_pass(encoder, key);
+            if kind == PassKind::Render {
+                load = wgpu::LoadOp::Load;
+            }
+        }
+    }
+
+    /// Attributes `err`, raised by a frame of passes of `kinds`, to the first of
+    /// them that fails on its own.
+    ///
+    /// Each pass is recorded again within its own error scope, but not submitted.
+    /// Errors no single pass raises, i.e. on submit, are attributed to the frame.
+    pub async fn attribute(
+        &self,
+        err: wgpu::Error,
+        kinds: &[PassKind],
+        device: &wgpu::Device,
+        view: &wgpu::TextureView,
+        size: (u32, u32),
+    ) -> GpuError {
+        for kind in kinds {
+            for (key, pass) in self.of_kind(*kind) {
+                scope::push(device);
+                let mut encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+                pass.encode(key, &mut encoder, view, size, wgpu::LoadOp::Load);
+                drop(encoder.finish());
+                if let Some(err) = scope::pop(device).await {
+                    return scope::gpu_error(err, GpuErrorPhase::Frame).in_pass(
+                        &key.file,
+                        &key.entry_point,
+                        *kind,
+                    );
+                }
+            }
+        }
+        scope::gpu_error(err, GpuErrorPhase::Frame)
+    }
+
+    /// First render pass, or the first one not bound to the resource `name` if set
+    pub fn render_pass_without(&self, name: Option<&str>) -> Option<&PassKey> {
+        self.of_kind(PassKind::Render)
+            .find(|(_, pass)| name.is_none_or(|name| !pass.resources.iter().any(|r| r == name)))
+            .map(|(key, _)| key)
+    }
+
+    fn of_kind(&self, kind: PassKind) -> impl Iterator<Item = (&PassKey, &Pass)> {
+        self.map.iter().filter(move |(_, pass)| pass.kind() == kind)
+    }
+}
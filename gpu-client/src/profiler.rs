@@ -0,0 +1,454 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use gpu_common::{PassTiming, ProfileSnapshot, TimingSource, TimingStats};
+
+use crate::pass::PassKey;
+
+/// Number of samples kept per pass and for frame times
+const HISTORY_LEN: usize = 240;
+/// Maximum number of passes timed with timestamp queries per frame
+const MAX_TIMED_PASSES: u32 = 64;
+/// Frame budget used when the project does not limit its frame rate
+const DEFAULT_FRAME_BUDGET_MS: f64 = 1000.0 / 60.0;
+
+/// Rolling window of samples, in milliseconds
+#[derive(Debug, Default)]
+struct History {
+    samples: VecDeque<f64>,
+}
+
+impl History {
+    fn push(&mut self, sample: f64) {
+        if self.samples.len() == HISTORY_LEN {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    fn stats(&self) -> TimingStats {
+        if self.samples.is_empty() {
+            return TimingStats::default();
+        }
+        let mut sorted: Vec<f64> = self.samples.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let p95 = ((sorted.len() as f64 * 0.95).ceil() as usize).clamp(1, sorted.len()) - 1;
+        TimingStats {
+            min: sorted[0],
+            avg: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            p95: sorted[p95],
+        }
+    }
+}
+
+/// Gpu side of the profiler, only available when the device supports timestamp queries
+struct Timestamps {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    /// Passes timed in the current frame, in order of their queries
+    recorded: Vec<PassKey>,
+    /// Passes whose timestamps are being read back
+    in_flight: Vec<PassKey>,
+    /// Set while the readback buffer has a map pending or is mapped, as it may
+    /// only be mapped once at a time
+    map_requested: bool,
+    /// Set once the readback buffer is mapped
+    mapped: Arc<AtomicBool>,
+}
+
+impl std::fmt::Debug for Timestamps {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Timestamps")
+            .field("period", &self.period)
+            .field("recorded", &self.recorded)
+            .field("in_flight", &self.in_flight)
+            .field("map_requested", &self.map_requested)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Collects per pass timings, along with frame pacing.
+///
+/// Pass timings come from timestamp queries when `wgpu::Features::TIMESTAMP_QUERY`
+/// is supported, and are read back a frame or more later. Otherwise the time taken
+/// to record each pass on the cpu is used instead.
+#[derive(Debug)]
+pub struct Profiler {
+    enabled: bool,
+    timestamps: Option<Timestamps>,
+    passes: BTreeMap<PassKey, History>,
+    /// Order in which passes were last run
+    order: Vec<PassKey>,
+    frame: History,
+    last_frame_start: Option<f64>,
+    cpu_pass_start: f64,
+    frame_budget: f64,
+    frames: u64,
+    dropped_frames: u64,
+    late_frames: u64,
+}
+
+/// Disabled profiler that only ever uses cpu timing
+impl Default for Profiler {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timestamps: None,
+            passes: BTreeMap::new(),
+            order: Vec::new(),
+            frame: History::default(),
+            last_frame_start: None,
+            cpu_pass_start: 0.0,
+            frame_budget: DEFAULT_FRAME_BUDGET_MS,
+            frames: 0,
+            dropped_frames: 0,
+            late_frames: 0,
+        }
+    }
+}
+
+impl Profiler {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let timestamps = device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| {
+                let count = MAX_TIMED_PASSES * 2;
+                let size = (count * wgpu::QUERY_SIZE) as u64;
+                Timestamps {
+                    query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                        label: Some("profiler"),
+                        ty: wgpu::QueryType::Timestamp,
+                        count,
+                    }),
+                    resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("profiler resolve"),
+                        size,
+                        usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    }),
+                    readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("profiler readback"),
+                        size,
+                        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    }),
+                    period: queue.get_timestamp_period(),
+                    recorded: Vec::new(),
+                    in_flight: Vec::new(),
+                    map_requested: false,
+                    mapped: Arc::new(AtomicBool::new(false)),
+                }
+            });
+        Self {
+            timestamps,
+            ..Self::default()
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if enabled && !self.enabled {
+            self.clear();
+        }
+        self.enabled = enabled;
+    }
+
+    /// Sets the frame budget from a frame rate limit, where 0 means no limit
+    pub fn set_limit_fps(&mut self, limit_fps: u32) {
+        self.frame_budget = match limit_fps {
+            0 => DEFAULT_FRAME_BUDGET_MS,
+            fps => 1000.0 / fps as f64,
+        };
+    }
+
+    /// Discards all collected timings, i.e. after the project is rebuilt
+    pub fn clear(&mut self) {
+        self.passes.clear();
+        self.order.clear();
+        self.frame = History::default();
+        self.last_frame_start = None;
+        self.frames = 0;
+        self.dropped_frames = 0;
+        self.late_frames = 0;
+        // Timestamps still being read back belong to the old history,
+        // unmapping aborts a map that is still pending
+        if let Some(ref mut ts) = self.timestamps {
+            if ts.map_requested {
+                ts.readback_buffer.unmap();
+                ts.map_requested = false;
+            }
+            ts.mapped.store(false, Ordering::Release);
+            ts.recorded.clear();
+            ts.in_flight.clear();
+        }
+    }
+
+    pub fn begin_frame(&mut self, device: &wgpu::Device) {
+        if !self.enabled {
+            return;
+        }
+        let now = crate::time::now();
+        if let Some(last) = self.last_frame_start.replace(now) {
+            let elapsed = now - last;
+            self.frame.push(elapsed);
+            if elapsed > self.frame_budget * 1.5 {
+                self.late_frames += 1;
+            }
+        }
+        self.frames += 1;
+        self.order.clear();
+        // Queries of a frame that failed part way through were never resolved
+        if let Some(ref mut ts) = self.timestamps {
+            ts.recorded.clear();
+        }
+        self.read_timestamps(device);
+    }
+
+    pub fn drop_frame(&mut self) {
+        if self.enabled {
+            self.dropped_frames += 1;
+        }
+    }
+
+    pub fn begin_pass(&mut self, encoder: &mut wgpu::CommandEncoder, key: &PassKey) {
+        if !self.enabled {
+            return;
+        }
+        self.order.push(key.clone());
+        match self.timestamps {
+            Some(ref mut ts) if ts.in_flight.is_empty() => {
+                let index = ts.recorded.len() as u32;
+                if index < MAX_TIMED_PASSES {
+                    encoder.write_timestamp(&ts.query_set, index * 2);
+                    ts.recorded.push(key.clone());
+                }
+            }
+            Some(_) => (),
+            None => self.cpu_pass_start = crate::time::now(),
+        }
+    }
+
+    pub fn end_pass(&mut self, encoder: &mut wgpu::CommandEncoder, key: &PassKey) {
+        if !self.enabled {
+            return;
+        }
+        match self.timestamps {
+            Some(ref mut ts) if ts.in_flight.is_empty() => {
+                if ts.recorded.last() == Some(key) {
+                    let index = ts.recorded.len() as u32 - 1;
+                    encoder.write_timestamp(&ts.query_set, index * 2 + 1);
+                }
+            }
+            Some(_) => (),
+            None => {
+                let elapsed = crate::time::now() - self.cpu_pass_start;
+                self.passes.entry(key.clone()).or_default().push(elapsed);
+            }
+        }
+    }
+
+    /// Copies this frame's timestamps to the readback buffer. Must be called after
+    /// every pass is recorded.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let ts = match self.timestamps {
+            Some(ref mut ts) if self.enabled && !ts.recorded.is_empty() => ts,
+            _ => return,
+        };
+        let count = ts.recorded.len() as u32 * 2;
+        let size = (count * wgpu::QUERY_SIZE) as u64;
+        encoder.resolve_query_set(&ts.query_set, 0..count, &ts.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&ts.resolve_buffer, 0, &ts.readback_buffer, 0, size);
+        ts.in_flight = std::mem::take(&mut ts.recorded);
+    }
+
+    /// Starts reading back resolved timestamps. Must be called after the frame is submitted.
+    pub fn end_frame(&mut self) {
+        let ts = match self.timestamps {
+            Some(ref mut ts) if !ts.in_flight.is_empty() && ts.recorded.is_empty() => ts,
+            _ => return,
+        };
+        // Only map once per readback, frames submitted while it is pending
+        // are not timed
+        if ts.map_requested {
+            return;
+        }
+        ts.map_requested = true;
+        let mapped = ts.mapped.clone();
+        let size = (ts.in_flight.len() as u32 * 2 * wgpu::QUERY_SIZE) as u64;
+        ts.readback_buffer
+            .slice(..size)
+            .map_async(wgpu::MapMode::Read, move |res| match res {
+                Ok(()) => mapped.store(true, Ordering::Release),
+                Err(err) => log::warn!("Could not read back timestamps: {err}"),
+            });
+    }
+
+    fn read_timestamps(&mut self, device: &wgpu::Device) {
+        let ts = match self.timestamps {
+            Some(ref mut ts) if !ts.in_flight.is_empty() => ts,
+            _ => return,
+        };
+        device.poll(wgpu::Maintain::Poll);
+        if !ts.mapped.swap(false, Ordering::AcqRel) {
+            return;
+        }
+        let size = (ts.in_flight.len() as u32 * 2 * wgpu::QUERY_SIZE) as u64;
+        {
+            let view = ts.readback_buffer.slice(..size).get_mapped_range();
+            let ticks: &[u64] = bytemuck::cast_slice(&view);
+            for (key, pair) in ts.in_flight.iter().zip(ticks.chunks_exact(2)) {
+                let elapsed_ns = pair[1].saturating_sub(pair[0]) as f64 * ts.period as f64;
+                self.passes
+                    .entry(key.clone())
+                    .or_default()
+                    .push(elapsed_ns / 1_000_000.0);
+            }
+        }
+        ts.readback_buffer.unmap();
+        ts.map_requested = false;
+        ts.in_flight.clear();
+    }
+
+    pub fn snapshot(&self) -> ProfileSnapshot {
+        let passes = self
+            .order
+            .iter()
+            .filter_map(|key| {
+                self.passes.get(key).map(|history| PassTiming {
+                    file: key.file.clone(),
+                    entry_point: key.entry_point.clone(),
+                    stats: history.stats(),
+                })
+            })
+            .collect();
+        ProfileSnapshot {
+            source: match self.timestamps {
+                Some(_) => TimingSource::Gpu,
+                None => TimingSource::Cpu,
+            },
+            frame: self.frame.stats(),
+            passes,
+            frames: self.frames,
+            dropped_frames: self.dropped_frames,
+            late_frames: self.late_frames,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pass::PassKey;
+
+    use super::{History, Profiler, HISTORY_LEN};
+
+    #[test]
+    fn test_history_stats() {
+        let mut history = History::default();
+        for sample in 1..=100 {
+            history.push(sample as f64);
+        }
+        let stats = history.stats();
+        assert_eq!(stats.min, 1.0);
+        assert_eq!(stats.avg, 50.5);
+        assert_eq!(stats.p95, 95.0);
+    }
+
+    #[test]
+    fn test_history_rolls_over() {
+        let mut history = History::default();
+        for sample in 0..HISTORY_LEN + 10 {
+            history.push(sample as f64);
+        }
+        assert_eq!(history.samples.len(), HISTORY_LEN);
+        assert_eq!(history.stats().min, 10.0);
+    }
+
+    /// Device with timestamp queries, if this machine has one
+    fn timestamp_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+        let instance = wgpu::Instance::new(wgpu::Backends::PRIMARY);
+        let adapter = pollster::block_on(instance.request_adapter(&Default::default()))?;
+        if !adapter.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+        let desc = wgpu::DeviceDescriptor {
+            label: None,
+            features: wgpu::Features::TIMESTAMP_QUERY,
+            limits: adapter.limits(),
+        };
+        pollster::block_on(adapter.request_device(&desc, None)).ok()
+    }
+
+    #[test]
+    fn test_end_frame_maps_once() {
+        let Some((device, queue)) = timestamp_device() else {
+            return;
+        };
+        let mut profiler = Profiler::new(&device, &queue);
+        profiler.set_enabled(true);
+        let key = PassKey {
+            file: "/shaders/main.wgsl".to_owned(),
+            entry_point: "main".to_owned(),
+        };
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        for _ in 0..2 {
+            profiler.begin_frame(&device);
+            let mut encoder = device.create_command_encoder(&Default::default());
+            profiler.begin_pass(&mut encoder, &key);
+            profiler.end_pass(&mut encoder, &key);
+            profiler.resolve(&mut encoder);
+            queue.submit(Some(encoder.finish()));
+            // The readback of the first frame is still pending
+            profiler.end_frame();
+            profiler.end_frame();
+        }
+        let error = pollster::block_on(device.pop_error_scope());
+        assert!(error.is_none(), "{error:?}");
+
+        device.poll(wgpu::Maintain::Wait);
+        profiler.begin_frame(&device);
+        assert_eq!(profiler.passes[&key].samples.len(), 1);
+        let ts = profiler.timestamps.as_ref().unwrap();
+        assert!(!ts.map_requested);
+        assert!(ts.in_flight.is_empty());
+    }
+
+    #[test]
+    fn test_clear_discards_readback() {
+        let Some((device, queue)) = timestamp_device() else {
+            return;
+        };
+        let mut profiler = Profiler::new(&device, &queue);
+        profiler.set_enabled(true);
+        let key = PassKey {
+            file: "/shaders/main.wgsl".to_owned(),
+            entry_point: "main".to_owned(),
+        };
+
+        profiler.begin_frame(&device);
+        let mut encoder = device.create_command_encoder(&Default::default());
+        profiler.begin_pass(&mut encoder, &key);
+        profiler.end_pass(&mut encoder, &key);
+        profiler.resolve(&mut encoder);
+        queue.submit(Some(encoder.finish()));
+        profiler.end_frame();
+
+        // Disabling while the readback is pending then enabling again clears
+        profiler.set_enabled(false);
+        profiler.set_enabled(true);
+        device.poll(wgpu::Maintain::Wait);
+        profiler.begin_frame(&device);
+        assert!(!profiler.passes.contains_key(&key));
+        let ts = profiler.timestamps.as_ref().unwrap();
+        assert!(!ts.map_requested);
+        assert!(ts.in_flight.is_empty());
+    }
+}
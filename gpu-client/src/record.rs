@@ -0,0 +1,200 @@
+pub use gpu_common::{RecordFormat, RecordOptions, RecordProgress};
+
+use crate::capture::{encode_png, Offscreen};
+use crate::context::Error;
+use crate::pass::Passes;
+use crate::resource::Resources;
+use crate::webm::WebmMuxer;
+
+/// Speed of gif palette quantization, from 1 for best quality to 30 for fastest
+const GIF_QUANTIZE_SPEED: i32 = 10;
+/// Largest gif dimension
+const MAX_GIF_SIZE: u32 = u16::MAX as u32;
+
+/// Finished recording
+#[derive(Debug)]
+pub enum Recording {
+    PngSequence(Vec<Vec<u8>>),
+    Gif(Vec<u8>),
+    /// Frames encoded to VP8 by the caller, as there is no VP8 encoder in the
+    /// client. Hosts without one, i.e. outside of browsers with WebCodecs, can not
+    /// record webm.
+    Webm(Vec<u8>),
+}
+
+enum Output {
+    PngSequence(Vec<Vec<u8>>),
+    Gif(gif::Encoder<Vec<u8>>),
+    Webm(WebmMuxer),
+}
+
+impl std::fmt::Debug for Output {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PngSequence(frames) => write!(f, "PngSequence({} frames)", frames.len()),
+            Self::Gif(encoder) => write!(f, "Gif({} bytes)", encoder.get_ref().len()),
+            Self::Webm(muxer) => muxer.fmt(f),
+        }
+    }
+}
+
+/// Renders a time range of the project offline, one frame at a time.
+///
+/// Frames are spaced exactly `1 / fps` apart regardless of how long each one takes
+/// to render, so recordings stay smooth on slow hardware. Created with
+/// [`Context::start_recording`](crate::context::Context::start_recording).
+///
+/// Recordings render with resources and passes of their own, so they are
+/// reproducible and leave the live project as it is.
+#[derive(Debug)]
+pub struct Recorder {
+    options: RecordOptions,
+    total: u32,
+    /// Index of the next frame to be rendered
+    frame: u32,
+    /// Index of the next frame expected by `push_encoded`
+    encoded: u32,
+    pub(crate) target: Offscreen,
+    pub(crate) resources: Resources,
+    pub(crate) passes: Passes,
+    output: Output,
+}
+
+impl Recorder {
+    pub(crate) fn new(
+        options: RecordOptions,
+        target: Offscreen,
+        resources: Resources,
+        passes: Passes,
+    ) -> Result<Self, Error> {
+        let RecordOptions {
+            format,
+            width,
+            height,
+            fps,
+            duration,
+            ..
+        } = options;
+        if fps == 0 || !duration.is_finite() || duration <= 0.0 {
+            return Err(Error::RecordRange(fps, duration));
+        }
+        let output = match format {
+            RecordFormat::PngSequence => Output::PngSequence(Vec::new()),
+            RecordFormat::Gif => {
+                if width > MAX_GIF_SIZE || height > MAX_GIF_SIZE {
+                    return Err(Error::CaptureSize(width, height));
+                }
+                let mut encoder = gif::Encoder::new(Vec::new(), width as u16, height as u16, &[])?;
+                encoder.set_repeat(gif::Repeat::Infinite)?;
+                Output::Gif(encoder)
+            }
+            RecordFormat::Webm => Output::Webm(WebmMuxer::new(width, height)),
+        };
+        Ok(Self {
+            total: (duration * fps as f32).ceil() as u32,
+            frame: 0,
+            encoded: 0,
+            options,
+            target,
+            resources,
+            passes,
+            output,
+        })
+    }
+
+    pub fn options(&self) -> &RecordOptions {
+        &self.options
+    }
+
+    pub fn progress(&self) -> RecordProgress {
+        RecordProgress {
+            frame: self.frame,
+            total: self.total,
+        }
+    }
+
+    /// Whether every frame was rendered
+    pub fn is_done(&self) -> bool {
+        self.frame >= self.total
+    }
+
+    /// Index and time in seconds of the next frame to render
+    pub(crate) fn next_frame(&self) -> Option<(u32, f32)> {
+        (!self.is_done()).then(|| {
+            let time = self.options.start + self.frame as f32 / self.options.fps as f32;
+            (self.frame, time)
+        })
+    }
+
+    /// Consumes a rendered rgba frame, returning it if it must be encoded by the caller
+    pub(crate) fn push_frame(&mut self, mut rgba: Vec<u8>) -> Result<Option<Vec<u8>>, Error> {
+        self.frame += 1;
+        let (width, height) = self.target.size();
+        match self.output {
+            Output::PngSequence(ref mut frames) => {
+                frames.push(encode_png(width, height, &rgba)?);
+                Ok(None)
+            }
+            Output::Gif(ref mut encoder) => {
+                let mut frame = gif::Frame::from_rgba_speed(
+                    width as u16,
+                    height as u16,
+                    &mut rgba,
+                    GIF_QUANTIZE_SPEED,
+                );
+                frame.delay = gif_delay(self.frame - 1, self.options.fps);
+                encoder.write_frame(&frame)?;
+                Ok(None)
+            }
+            Output::Webm(_) => Ok(Some(rgba)),
+        }
+    }
+
+    /// Adds the next frame after it was encoded to VP8 by the caller
+    pub fn push_encoded(&mut self, data: &[u8], keyframe: bool) -> Result<(), Error> {
+        let muxer = match self.output {
+            Output::Webm(ref mut muxer) => muxer,
+            _ => return Err(Error::RecordEncoded(self.options.format)),
+        };
+        let timestamp = self.encoded as u64 * 1000 / self.options.fps as u64;
+        muxer.push(data, timestamp, keyframe);
+        self.encoded += 1;
+        Ok(())
+    }
+
+    /// Finishes the recording. Can be called before every frame is rendered, in
+    /// which case the recording ends at the last rendered frame.
+    pub fn finish(self) -> Result<Recording, Error> {
+        Ok(match self.output {
+            Output::PngSequence(frames) => Recording::PngSequence(frames),
+            Output::Gif(encoder) => {
+                Recording::Gif(encoder.into_inner().map_err(gif::EncodingError::from)?)
+            }
+            Output::Webm(muxer) => Recording::Webm(muxer.finish(1000 / self.options.fps as u64)),
+        })
+    }
+}
+
+/// Delay of `frame` in hundredths of a second, which gifs are timed in. Frames
+/// end at the hundredth closest to their exact end rather than each delay being
+/// rounded, so that rounding errors don't add up, i.e. 30 fps takes 3, 4 and 3.
+fn gif_delay(frame: u32, fps: u32) -> u16 {
+    let end = |frame: u32| (frame as u64 * 100 + fps as u64 / 2) / fps as u64;
+    (end(frame + 1) - end(frame)) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::gif_delay;
+
+    #[test]
+    fn test_gif_delay() {
+        let delays: Vec<_> = (0..6).map(|frame| gif_delay(frame, 30)).collect();
+        assert_eq!(delays, [3, 4, 3, 3, 4, 3]);
+        for fps in [24, 30, 60] {
+            let total: u32 = (0..fps).map(|frame| gif_delay(frame, fps) as u32).sum();
+            assert_eq!(total, 100, "{fps} fps");
+        }
+        assert!((0..10).all(|frame| gif_delay(frame, 25) == 4));
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use gpu_compiler::{CompiledProject, ResourceDecl, RUNTIME_ARRAY_LEN};
+
+/// Name of the uniform that is written by the client every frame.
+///
+/// Shaders may declare it with the layout of [`SystemUniforms`], or any prefix of it:
+/// ```wgsl
+/// struct System {
+///     time: f32,
+///     frame: u32,
+///     resolution: vec2<f32>,
+///     mouse: vec2<f32>,
+///     offset: vec2<f32>,
+/// }
+/// @group(0) @binding(0) var<uniform> system: System;
+/// ```
+pub const SYSTEM_RESOURCE: &str = "system";
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SystemUniforms {
+    /// Seconds since the project was first built or restarted
+    pub time: f32,
+    /// Frames rendered since the project was first built or restarted
+    pub frame: u32,
+    /// Size of rendered image in pixels
+    pub resolution: [f32; 2],
+    /// Mouse position in pixels
+    pub mouse: [f32; 2],
+    /// Position of render target within the rendered image in pixels. Only nonzero
+    /// when a capture larger than the texture limit is rendered in tiles.
+    pub offset: [f32; 2],
+}
+
+#[derive(Debug, Clone)]
+pub struct Resource {
+    pub decl: ResourceDecl,
+    /// Size of `buffer` in bytes, which depends on the frame size if the
+    /// declaration ends in a runtime-sized array
+    pub size: u64,
+    pub buffer: Arc<wgpu::Buffer>,
+}
+
+/// Size in bytes of the buffer of resource `name` for frames of `frame` pixels.
+///
+/// Compute passes run an invocation per pixel, so trailing runtime-sized arrays
+/// get an element per pixel, or [`RUNTIME_ARRAY_LEN`] for smaller frames. They
+/// are kept within `max_size` unless the declaration alone is larger.
+fn buffer_size(name: &str, decl: &ResourceDecl, frame: (u32, u32), max_size: u64) -> u64 {
+    if name == SYSTEM_RESOURCE {
+        return decl.size.max(std::mem::size_of::<SystemUniforms>() as u64);
+    }
+    match decl.runtime_stride {
+        Some(stride) => {
+            let fixed = decl.size - stride * RUNTIME_ARRAY_LEN;
+            let len = (frame.0 as u64 * frame.1 as u64).max(RUNTIME_ARRAY_LEN);
+            (fixed + stride * len).min(max_size.max(decl.size))
+        }
+        None => decl.size,
+    }
+}
+
+/// Gpu buffers shared between passes, keyed by the name they are declared with.
+///
+/// Clones share their buffers, so that a build can update a copy and only
+/// replace these once passes were created against it.
+#[derive(Debug, Default, Clone)]
+pub struct Resources {
+    map: HashMap<String, Resource>,
+}
+
+impl Resources {
+    /// Brings resources in line with the declarations in `project`, for frames of
+    /// `frame` pixels.
+    ///
+    /// Resources whose declaration and size did not change are left untouched and
+    /// keep their contents, so after a resize only those sized by the frame are
+    /// recreated. Returns the names of resources that were (re)created.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        project: &CompiledProject,
+        frame: (u32, u32),
+    ) -> HashSet<String> {
+        let declared = project.resources();
+        self.map
+            .retain(|name, _| declared.contains_key(name.as_str()));
+
+        let max_size = device.limits().max_storage_buffer_binding_size as u64;
+        let mut created = HashSet::new();
+        for (name, decl) in declared {
+            let size = buffer_size(name, decl, frame, max_size);
+            if matches!(self.map.get(name), Some(resource) if &resource.decl == decl && resource.size == size)
+            {
+                continue;
+            }
+            log::info!("Creating resource `{name}` ({size} bytes)");
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(name),
+                size,
+                usage: wgpu::BufferUsages::UNIFORM
+                    | wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_DST
+                    | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            });
+            self.map.insert(
+                name.to_owned(),
+                Resource {
+                    decl: decl.clone(),
+                    size,
+                    buffer: Arc::new(buffer),
+                },
+            );
+            created.insert(name.to_owned());
+        }
+        created
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Resource> {
+        self.map.get(name)
+    }
+
+    /// Whether shaders declare system uniforms up to and including `offset`, so
+    /// that they can tell which tile of a capture they render
+    pub fn system_has_offset(&self) -> bool {
+        let end = std::mem::offset_of!(SystemUniforms, offset) + std::mem::size_of::<[f32; 2]>();
+        self.get(SYSTEM_RESOURCE)
+            .is_some_and(|resource| resource.decl.size >= end as u64)
+    }
+
+    /// Writes system uniforms, if any shader declared them
+    pub fn write_system(&self, queue: &wgpu::Queue, system: &SystemUniforms) {
+        if let Some(resource) = self.get(SYSTEM_RESOURCE) {
+            queue.write_buffer(&resource.buffer, 0, bytemuck::bytes_of(system));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use gpu_common::Files;
+    use gpu_compiler::{CompiledProject, ResourceDecl, RUNTIME_ARRAY_LEN};
+
+    use super::{buffer_size, Resources, SYSTEM_RESOURCE};
+
+    #[test]
+    fn test_buffer_size() {
+        let decl = |size, runtime_stride| ResourceDecl {
+            size,
+            runtime_stride,
+            ty: String::new(),
+        };
+        let max = 1 << 30;
+        assert_eq!(buffer_size("state", &decl(20, None), (1920, 1080), max), 20);
+        assert_eq!(
+            buffer_size(SYSTEM_RESOURCE, &decl(8, None), (1, 1), max),
+            32
+        );
+
+        // An element per pixel, but never fewer than the compiler counts
+        let cells = decl(16 + 16 * RUNTIME_ARRAY_LEN, Some(16));
+        assert_eq!(buffer_size("cells", &cells, (100, 100), max), cells.size);
+        assert_eq!(
+            buffer_size("cells", &cells, (1920, 1080), max),
+            16 + 16 * 1920 * 1080
+        );
+        assert_eq!(buffer_size("cells", &cells, (8192, 8192), max), max);
+    }
+
+    #[test]
+    fn test_update_on_resize() {
+        let instance = wgpu::Instance::new(wgpu::Backends::PRIMARY);
+        let Some(adapter) = pollster::block_on(instance.request_adapter(&Default::default()))
+        else {
+            return;
+        };
+        let (device, _queue) =
+            pollster::block_on(adapter.request_device(&Default::default(), None)).unwrap();
+        let files = Files::from_entries([(
+            "/cells.wgsl",
+            "struct Cells { values: array<f32> }\n\
+             @group(0) @binding(0) var<storage, read_write> cells: Cells;\n\
+             @group(0) @binding(1) var<storage, read_write> count: u32;\n\
+             @compute @workgroup_size(1) fn main() { cells.values[0] = 1.0; count = 1u; }",
+        )])
+        .unwrap();
+        let project = CompiledProject::compile(&files).unwrap();
+
+        let mut resources = Resources::default();
+        let created = resources.update(&device, &project, (100, 100));
+        assert_eq!(created.len(), 2);
+        assert!(resources.update(&device, &project, (200, 200)).is_empty());
+
+        // Only the resource sized by the frame is recreated, at its new size
+        let created = resources.update(&device, &project, (1000, 1000));
+        assert_eq!(created, HashSet::from(["cells".to_owned()]));
+        assert_eq!(resources.get("cells").unwrap().size, 4 * 1000 * 1000);
+
+        // Copies share buffers until updated, leaving the original as it was
+        let mut copy = resources.clone();
+        copy.update(&device, &project, (2000, 2000));
+        assert_eq!(resources.get("cells").unwrap().size, 4 * 1000 * 1000);
+        assert!(std::sync::Arc::ptr_eq(
+            &copy.get("count").unwrap().buffer,
+            &resources.get("count").unwrap().buffer
+        ));
+    }
+}
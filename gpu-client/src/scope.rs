@@ -0,0 +1,48 @@
+//! Helpers for capturing gpu errors with error scopes, rather than letting them
+//! reach the uncaptured error handler.
+
+use gpu_common::{GpuError, GpuErrorKind, GpuErrorPhase};
+
+/// Starts capturing validation and out-of-memory errors
+pub fn push(device: &wgpu::Device) {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+}
+
+/// Stops capturing errors started by the matching [`push`], returning the first
+/// captured error, if any
+pub async fn pop(device: &wgpu::Device) -> Option<wgpu::Error> {
+    let out_of_memory = device.pop_error_scope().await;
+    let validation = device.pop_error_scope().await;
+    out_of_memory.or(validation)
+}
+
+/// Whether `device` is lost. There is no device lost callback in wgpu yet, but a
+/// lost device fails to create even the smallest buffer.
+pub async fn is_lost(device: &wgpu::Device) -> bool {
+    push(device);
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("device lost probe"),
+        size: wgpu::COPY_BUFFER_ALIGNMENT,
+        usage: wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    pop(device).await.is_some()
+}
+
+/// Converts an error raised by wgpu while doing `phase`. Errors are never of
+/// kind [`GpuErrorKind::DeviceLost`], which only [`is_lost`] can tell.
+pub fn gpu_error(err: wgpu::Error, phase: GpuErrorPhase) -> GpuError {
+    let kind = match err {
+        wgpu::Error::OutOfMemory { .. } => GpuErrorKind::OutOfMemory,
+        wgpu::Error::Validation { .. } => GpuErrorKind::Validation,
+    };
+    GpuError {
+        kind,
+        phase,
+        pass: None,
+        file: None,
+        entry_point: None,
+        message: err.to_string(),
+    }
+}
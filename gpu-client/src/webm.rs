@@ -0,0 +1,189 @@
+//! Minimal WebM muxer for a single VP8 video track.
+//!
+//! There is no VP8 encoder available to the client, so frames are encoded by the
+//! caller (i.e. with WebCodecs in the browser) and only put into a container here.
+//! The whole file is built in memory, which lets every element be written with a
+//! known size.
+
+const EBML: u32 = 0x1A45DFA3;
+const EBML_VERSION: u32 = 0x4286;
+const EBML_READ_VERSION: u32 = 0x42F7;
+const EBML_MAX_ID_LENGTH: u32 = 0x42F2;
+const EBML_MAX_SIZE_LENGTH: u32 = 0x42F3;
+const DOC_TYPE: u32 = 0x4282;
+const DOC_TYPE_VERSION: u32 = 0x4287;
+const DOC_TYPE_READ_VERSION: u32 = 0x4285;
+const SEGMENT: u32 = 0x18538067;
+const INFO: u32 = 0x1549A966;
+const TIMECODE_SCALE: u32 = 0x2AD7B1;
+const DURATION: u32 = 0x4489;
+const MUXING_APP: u32 = 0x4D80;
+const WRITING_APP: u32 = 0x5741;
+const TRACKS: u32 = 0x1654AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_NUMBER: u32 = 0xD7;
+const TRACK_UID: u32 = 0x73C5;
+const TRACK_TYPE: u32 = 0x83;
+const CODEC_ID: u32 = 0x86;
+const VIDEO: u32 = 0xE0;
+const PIXEL_WIDTH: u32 = 0xB0;
+const PIXEL_HEIGHT: u32 = 0xBA;
+const CLUSTER: u32 = 0x1F43B675;
+const TIMECODE: u32 = 0xE7;
+const SIMPLE_BLOCK: u32 = 0xA3;
+
+/// Timestamps are written in milliseconds
+const TIMECODE_SCALE_NS: u64 = 1_000_000;
+/// Block timestamps are relative to their cluster as an i16, so clusters are
+/// started at least this often
+const MAX_CLUSTER_MS: u64 = 30_000;
+
+#[derive(Debug)]
+pub struct WebmMuxer {
+    width: u32,
+    height: u32,
+    /// Finished clusters
+    clusters: Vec<u8>,
+    /// Timestamp and content of the cluster being written
+    cluster: Option<(u64, Vec<u8>)>,
+    /// Timestamp of the last frame, in milliseconds
+    last_timestamp: u64,
+}
+
+impl WebmMuxer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            clusters: Vec::new(),
+            cluster: None,
+            last_timestamp: 0,
+        }
+    }
+
+    /// Adds an encoded VP8 frame shown at `timestamp` milliseconds. Frames must be
+    /// added in order, starting with a keyframe.
+    pub fn push(&mut self, data: &[u8], timestamp: u64, keyframe: bool) {
+        let start_cluster = match self.cluster {
+            Some((start, _)) => keyframe || timestamp - start > MAX_CLUSTER_MS,
+            None => true,
+        };
+        if start_cluster {
+            self.flush_cluster();
+            let mut cluster = Vec::new();
+            write_uint(&mut cluster, TIMECODE, timestamp);
+            self.cluster = Some((timestamp, cluster));
+        }
+        let (start, cluster) = self.cluster.as_mut().expect("cluster was just started");
+
+        let mut block = Vec::with_capacity(data.len() + 4);
+        // Track number as a vint, then timestamp relative to cluster and flags
+        block.push(0x81);
+        block.extend_from_slice(&((timestamp - *start) as i16).to_be_bytes());
+        block.push(if keyframe { 0x80 } else { 0x00 });
+        block.extend_from_slice(data);
+        write_element(cluster, SIMPLE_BLOCK, &block);
+        self.last_timestamp = timestamp;
+    }
+
+    /// Finishes the file, given the duration of the last frame in milliseconds
+    pub fn finish(mut self, frame_duration: u64) -> Vec<u8> {
+        self.flush_cluster();
+
+        let mut header = Vec::new();
+        write_uint(&mut header, EBML_VERSION, 1);
+        write_uint(&mut header, EBML_READ_VERSION, 1);
+        write_uint(&mut header, EBML_MAX_ID_LENGTH, 4);
+        write_uint(&mut header, EBML_MAX_SIZE_LENGTH, 8);
+        write_element(&mut header, DOC_TYPE, b"webm");
+        write_uint(&mut header, DOC_TYPE_VERSION, 2);
+        write_uint(&mut header, DOC_TYPE_READ_VERSION, 2);
+
+        let mut info = Vec::new();
+        write_uint(&mut info, TIMECODE_SCALE, TIMECODE_SCALE_NS);
+        let duration = (self.last_timestamp + frame_duration) as f64;
+        write_element(&mut info, DURATION, &duration.to_be_bytes());
+        write_element(&mut info, MUXING_APP, b"gputoy");
+        write_element(&mut info, WRITING_APP, b"gputoy");
+
+        let mut video = Vec::new();
+        write_uint(&mut video, PIXEL_WIDTH, self.width as u64);
+        write_uint(&mut video, PIXEL_HEIGHT, self.height as u64);
+        let mut track = Vec::new();
+        write_uint(&mut track, TRACK_NUMBER, 1);
+        write_uint(&mut track, TRACK_UID, 1);
+        write_uint(&mut track, TRACK_TYPE, 1);
+        write_element(&mut track, CODEC_ID, b"V_VP8");
+        write_element(&mut track, VIDEO, &video);
+        let mut tracks = Vec::new();
+        write_element(&mut tracks, TRACK_ENTRY, &track);
+
+        let mut segment = Vec::new();
+        write_element(&mut segment, INFO, &info);
+        write_element(&mut segment, TRACKS, &tracks);
+        segment.append(&mut self.clusters);
+
+        let mut out = Vec::new();
+        write_element(&mut out, EBML, &header);
+        write_element(&mut out, SEGMENT, &segment);
+        out
+    }
+
+    fn flush_cluster(&mut self) {
+        if let Some((_, cluster)) = self.cluster.take() {
+            write_element(&mut self.clusters, CLUSTER, &cluster);
+        }
+    }
+}
+
+fn write_element(out: &mut Vec<u8>, id: u32, data: &[u8]) {
+    let id = id.to_be_bytes();
+    let skip = id.iter().take_while(|byte| **byte == 0).count();
+    out.extend_from_slice(&id[skip..]);
+    write_size(out, data.len() as u64);
+    out.extend_from_slice(data);
+}
+
+fn write_uint(out: &mut Vec<u8>, id: u32, value: u64) {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take_while(|byte| **byte == 0).count().min(7);
+    write_element(out, id, &bytes[skip..]);
+}
+
+/// Writes size as a variable length integer, using as few bytes as possible
+fn write_size(out: &mut Vec<u8>, size: u64) {
+    // All ones is reserved to mean unknown size
+    let len = (1..=8u32)
+        .find(|len| size < (1 << (7 * len)) - 1)
+        .expect("element too large");
+    let marked = size | 1 << (7 * len);
+    out.extend_from_slice(&marked.to_be_bytes()[8 - len as usize..]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_size, WebmMuxer};
+
+    #[test]
+    fn test_write_size() {
+        let mut out = Vec::new();
+        write_size(&mut out, 5);
+        write_size(&mut out, 127);
+        write_size(&mut out, 300);
+        assert_eq!(out, [0x85, 0x40, 0x7F, 0x41, 0x2C]);
+    }
+
+    #[test]
+    fn test_mux() {
+        let mut muxer = WebmMuxer::new(2, 2);
+        muxer.push(&[1, 2, 3], 0, true);
+        muxer.push(&[4, 5], 40, false);
+        let out = muxer.finish(40);
+        assert_eq!(&out[..4], &[0x1A, 0x45, 0xDF, 0xA3]);
+        let doc_type = out.windows(4).position(|w| w == b"webm");
+        assert!(doc_type.is_some());
+        // Second block is 40ms after the start of the cluster, and not a keyframe
+        let block = [0xA3, 0x86, 0x81, 0x00, 0x28, 0x00, 4, 5];
+        assert!(out.windows(block.len()).any(|w| w == block));
+    }
+}
@@ -0,0 +1,211 @@
+//! Macros, sequences of actions run by a single [`Action::RunMacro`].
+
+use std::collections::{HashMap, HashSet};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::Action;
+
+/// Most macros a config can define
+pub const MAX_MACROS: usize = 256;
+
+/// Actions run one after the other, i.e. reset, switch param preset and play
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct Macro {
+    pub steps: Vec<MacroStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct MacroStep {
+    pub action: Action,
+    /// Milliseconds to wait before running the action
+    pub delay: Option<u32>,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MacroError {
+    /// What runs the macro, i.e. ``Macro `restart` ``, and the macro
+    #[error("{0} runs macro `{1}`, which does not exist")]
+    Unknown(String, String),
+    #[error("Macro `{}` runs itself: {}", .0[0], .0.join(" -> "))]
+    Recursive(Vec<String>),
+    #[error("Config has {0} macros, at most {MAX_MACROS} are allowed")]
+    TooMany(usize),
+}
+
+impl Macro {
+    /// Names of the macros the macro runs directly
+    pub fn runs(&self) -> impl Iterator<Item = &str> {
+        self.steps.iter().filter_map(|step| match step.action {
+            Action::RunMacro(ref name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+}
+
+/// Checks that every macro run exists and that no macro ends up running
+/// itself, directly or through other macros
+pub fn check_macros(macros: &HashMap<String, Macro>) -> Result<(), MacroError> {
+    if macros.len() > MAX_MACROS {
+        return Err(MacroError::TooMany(macros.len()));
+    }
+    let mut names = macros.keys().collect::<Vec<_>>();
+    names.sort();
+    for name in names.iter() {
+        for run in macros[*name].runs() {
+            if !macros.contains_key(run) {
+                return Err(MacroError::Unknown(
+                    format!("Macro `{name}`"),
+                    run.to_owned(),
+                ));
+            }
+        }
+    }
+    // Macros known not to recurse
+    let mut checked = HashSet::new();
+    for name in names {
+        find_cycle(macros, name, &mut checked)?;
+    }
+    Ok(())
+}
+
+/// Depth first search from `start`, skipping macros already `checked`
+fn find_cycle<'a>(
+    macros: &'a HashMap<String, Macro>,
+    start: &'a str,
+    checked: &mut HashSet<&'a str>,
+) -> Result<(), MacroError> {
+    if checked.contains(start) {
+        return Ok(());
+    }
+    let mut path = vec![start];
+    let mut on_path = HashSet::from([start]);
+    // Macros still to visit from each macro in `path`
+    let mut stack = vec![macros[start].runs()];
+    while let Some(runs) = stack.last_mut() {
+        match runs.next() {
+            Some(run) if on_path.contains(run) => {
+                let start = path.iter().position(|name| *name == run).unwrap_or(0);
+                let mut cycle = path[start..]
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect::<Vec<_>>();
+                cycle.push(run.to_owned());
+                return Err(MacroError::Recursive(cycle));
+            }
+            Some(run) if checked.contains(run) => {}
+            Some(run) => {
+                path.push(run);
+                on_path.insert(run);
+                stack.push(macros[run].runs());
+            }
+            None => {
+                stack.pop();
+                if let Some(name) = path.pop() {
+                    on_path.remove(name);
+                    checked.insert(name);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::Action;
+
+    use super::{check_macros, Macro, MacroError, MacroStep, MAX_MACROS};
+
+    fn steps(actions: Vec<Action>) -> Macro {
+        Macro {
+            steps: actions
+                .into_iter()
+                .map(|action| MacroStep {
+                    action,
+                    delay: None,
+                })
+                .collect(),
+        }
+    }
+
+    fn run(name: &str) -> Action {
+        Action::RunMacro(name.to_owned())
+    }
+
+    #[test]
+    fn test_check_macros() {
+        let mut macros = HashMap::from([
+            (
+                "restart".to_owned(),
+                steps(vec![Action::Reset, Action::PlayPause]),
+            ),
+            (
+                "rebuild".to_owned(),
+                steps(vec![Action::Rebuild, run("restart")]),
+            ),
+            (
+                "twice".to_owned(),
+                steps(vec![run("restart"), run("restart")]),
+            ),
+        ]);
+        assert_eq!(check_macros(&macros), Ok(()));
+
+        macros.insert("missing".to_owned(), steps(vec![run("nope")]));
+        assert_eq!(
+            check_macros(&macros).unwrap_err().to_string(),
+            "Macro `missing` runs macro `nope`, which does not exist"
+        );
+        macros.remove("missing");
+
+        macros.insert("self".to_owned(), steps(vec![Action::Reset, run("self")]));
+        assert_eq!(
+            check_macros(&macros),
+            Err(MacroError::Recursive(vec![
+                "self".to_owned(),
+                "self".to_owned()
+            ]))
+        );
+        macros.remove("self");
+
+        macros.get_mut("restart").unwrap().steps.push(MacroStep {
+            action: run("rebuild"),
+            delay: Some(100),
+        });
+        assert_eq!(
+            check_macros(&macros).unwrap_err().to_string(),
+            "Macro `rebuild` runs itself: rebuild -> restart -> rebuild"
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        // A long chain is walked without recursing
+        let mut macros = (0..MAX_MACROS - 1)
+            .map(|i| (format!("m{i}"), steps(vec![run(&format!("m{}", i + 1))])))
+            .collect::<HashMap<_, _>>();
+        let last = format!("m{}", MAX_MACROS - 1);
+        macros.insert(last.clone(), steps(vec![Action::Reset]));
+        assert_eq!(check_macros(&macros), Ok(()));
+
+        macros.insert(last, steps(vec![run("m0")]));
+        let Err(MacroError::Recursive(cycle)) = check_macros(&macros) else {
+            panic!("cycle not found");
+        };
+        assert_eq!(cycle.len(), MAX_MACROS + 1);
+
+        macros.insert("extra".to_owned(), Macro::default());
+        assert_eq!(
+            check_macros(&macros),
+            Err(MacroError::TooMany(MAX_MACROS + 1))
+        );
+    }
+}
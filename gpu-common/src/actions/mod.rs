@@ -1,9 +1,16 @@
+mod macros;
+mod registry;
+
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::Panel;
 
-#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub use macros::*;
+pub use registry::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
 #[serde(rename_all = "camelCase", tag = "ty", content = "c")]
 pub enum Action {
     /// Toggles pane open and closed
@@ -51,10 +58,15 @@ pub enum Action {
     CloseFile,
     /// Close project
     CloseProject,
+
+    /// Runs the user's macro of the given name
+    RunMacro(String),
 }
 
-#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
 pub struct ShiftPaneArgs {
-    pane: Panel,
-    shift: i32,
+    pub pane: Panel,
+    /// Percent of its split the pane grows by, shrinks by if negative
+    pub shift: i32,
 }
@@ -0,0 +1,321 @@
+//! Metadata of every [`Action`], for the command palette and keybinding editor.
+//!
+//! Descriptions and argument schemas come from the [`Action`] enum itself, its
+//! doc comments and the types of its variants. Titles, categories and default
+//! keybinds are listed here.
+
+use std::collections::{BTreeMap, HashMap};
+
+use schemars::gen::SchemaSettings;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{Action, FilteredAction, Keybind, Panel, Platform};
+
+/// Group an action is listed under
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ActionCategory {
+    View,
+    Editor,
+    Run,
+    File,
+    Project,
+}
+
+/// Title and category of every action, by the tag it is serialized with
+const ACTIONS: [(&str, &str, ActionCategory); 23] = [
+    ("togglePanel", "Toggle Panel", ActionCategory::View),
+    (
+        "toggleDebugPanel",
+        "Toggle Debug Panel",
+        ActionCategory::View,
+    ),
+    (
+        "toggleUserPreferences",
+        "Toggle Preferences",
+        ActionCategory::View,
+    ),
+    ("shiftPanel", "Resize Panel", ActionCategory::View),
+    ("toggleConsole", "Toggle Console", ActionCategory::View),
+    ("focus", "Focus Panel", ActionCategory::View),
+    ("playPause", "Play/Pause", ActionCategory::Run),
+    ("reset", "Reset", ActionCategory::Run),
+    ("rebuild", "Rebuild", ActionCategory::Run),
+    ("closeDocument", "Close Document", ActionCategory::Editor),
+    ("nextDocument", "Next Document", ActionCategory::Editor),
+    (
+        "previousDocument",
+        "Previous Document",
+        ActionCategory::Editor,
+    ),
+    ("openDocument", "Open Document", ActionCategory::Editor),
+    ("createNewFile", "New File", ActionCategory::File),
+    ("saveCurrentFile", "Save File", ActionCategory::File),
+    ("saveAllFiles", "Save All Files", ActionCategory::File),
+    ("closeFile", "Close File", ActionCategory::File),
+    ("createNewProject", "New Project", ActionCategory::Project),
+    (
+        "saveProjectToRemote",
+        "Save Project",
+        ActionCategory::Project,
+    ),
+    ("fork", "Fork Project", ActionCategory::Project),
+    ("publish", "Publish Project", ActionCategory::Project),
+    ("closeProject", "Close Project", ActionCategory::Project),
+    ("runMacro", "Run Macro", ActionCategory::Run),
+];
+
+/// Everything known about an action
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionInfo {
+    /// Tag of the action, its `ty`
+    pub ty: String,
+    pub title: String,
+    pub category: ActionCategory,
+    pub description: String,
+    /// Json schema of the action's argument, its `c`, if it takes one
+    pub args: Option<Value>,
+    /// Keybinds bound to the action in the default keymap
+    pub default_keybinds: Vec<String>,
+}
+
+/// Every action along with the default keymap, generated for the frontend
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionRegistry {
+    pub actions: Vec<ActionInfo>,
+    /// Keybinds every user starts out with
+    pub default_keymap: BTreeMap<String, FilteredAction>,
+}
+
+impl ActionRegistry {
+    pub fn new() -> Self {
+        let default_keymap = default_keymap();
+        let variants = action_variants();
+        let actions = ACTIONS
+            .iter()
+            .map(|(ty, title, category)| {
+                let variant = variants.get(*ty);
+                let mut default_keybinds = default_keymap
+                    .iter()
+                    .filter(|(_, action)| action.action.ty() == *ty)
+                    .map(|(keybind, _)| keybind.clone())
+                    .collect::<Vec<_>>();
+                default_keybinds.sort();
+                ActionInfo {
+                    ty: ty.to_string(),
+                    title: title.to_string(),
+                    category: *category,
+                    description: variant
+                        .and_then(|variant| variant["description"].as_str())
+                        .unwrap_or_default()
+                        .to_owned(),
+                    args: variant.and_then(|variant| variant["properties"].get("c").cloned()),
+                    default_keybinds,
+                }
+            })
+            .collect();
+        Self {
+            actions,
+            // Sorted, so that the generated registry only changes with the keymap
+            default_keymap: default_keymap.into_iter().collect(),
+        }
+    }
+
+    pub fn get(&self, ty: &str) -> Option<&ActionInfo> {
+        self.actions.iter().find(|info| info.ty == ty)
+    }
+}
+
+impl Default for ActionRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Action {
+    /// Tag the action is serialized with, i.e. `togglePanel`
+    pub fn ty(&self) -> &'static str {
+        match self {
+            Self::TogglePanel(_) => "togglePanel",
+            Self::ToggleDebugPanel => "toggleDebugPanel",
+            Self::ToggleUserPreferences => "toggleUserPreferences",
+            Self::ShiftPanel(_) => "shiftPanel",
+            Self::PlayPause => "playPause",
+            Self::Reset => "reset",
+            Self::Rebuild => "rebuild",
+            Self::ToggleConsole => "toggleConsole",
+            Self::Focus(_) => "focus",
+            Self::CloseDocument => "closeDocument",
+            Self::NextDocument => "nextDocument",
+            Self::PreviousDocument => "previousDocument",
+            Self::OpenDocument(_) => "openDocument",
+            Self::CreateNewProject => "createNewProject",
+            Self::CreateNewFile => "createNewFile",
+            Self::SaveProjectToRemote => "saveProjectToRemote",
+            Self::SaveCurrentFile => "saveCurrentFile",
+            Self::SaveAllFiles => "saveAllFiles",
+            Self::Fork => "fork",
+            Self::Publish => "publish",
+            Self::CloseFile => "closeFile",
+            Self::CloseProject => "closeProject",
+            Self::RunMacro(_) => "runMacro",
+        }
+    }
+}
+
+/// Schemas of the variants of [`Action`] by tag, with argument types inlined
+fn action_variants() -> HashMap<String, Value> {
+    let generator = SchemaSettings::draft07()
+        .with(|settings| settings.inline_subschemas = true)
+        .into_generator();
+    let schema = serde_json::to_value(generator.into_root_schema_for::<Action>())
+        .expect("schemas serialize to json");
+    let Value::Array(variants) = schema["oneOf"].clone() else {
+        return HashMap::new();
+    };
+    variants
+        .into_iter()
+        .filter_map(|variant| {
+            let ty = variant["properties"]["ty"]["enum"][0].as_str()?.to_owned();
+            Some((ty, variant))
+        })
+        .collect()
+}
+
+/// Keybinds every user starts out with, see [`merge_keymap`]
+pub fn default_keymap() -> HashMap<String, FilteredAction> {
+    let bind = |action| FilteredAction {
+        action,
+        condition: None,
+    };
+    let bind_if = |action, condition: &str| FilteredAction {
+        action,
+        condition: Some(condition.to_owned()),
+    };
+    [
+        ("ctrl+g", bind(Action::ToggleConsole)),
+        ("ctrl+q", bind(Action::TogglePanel(Panel::ProjectPanel))),
+        ("ctrl+e", bind(Action::TogglePanel(Panel::EditorPanel))),
+        ("ctrl+r", bind(Action::TogglePanel(Panel::ResourcePanel))),
+        ("ctrl+shift+d", bind(Action::ToggleDebugPanel)),
+        ("ctrl+,", bind(Action::ToggleUserPreferences)),
+        ("ctrl+enter", bind(Action::Rebuild)),
+        ("ctrl+shift+enter", bind(Action::PlayPause)),
+        ("ctrl+j", bind(Action::PreviousDocument)),
+        ("ctrl+k", bind(Action::NextDocument)),
+        ("ctrl+u", bind(Action::CloseFile)),
+        ("mod+s", bind(Action::SaveAllFiles)),
+        (
+            "ctrl+shift+s",
+            bind_if(Action::SaveProjectToRemote, "userLoggedIn"),
+        ),
+        ("ctrl+shift+f", bind(Action::Fork)),
+        ("ctrl+shift+g", bind_if(Action::Publish, "userLoggedIn")),
+    ]
+    .into_iter()
+    .map(|(keybind, action)| (keybind.to_owned(), action))
+    .collect()
+}
+
+/// Keymap of `defaults` with `overrides` applied. An override replaces the
+/// default bound to the same keys, however either is written.
+pub fn merge_keymap(
+    defaults: &HashMap<String, FilteredAction>,
+    overrides: &HashMap<String, FilteredAction>,
+) -> HashMap<String, FilteredAction> {
+    let canonical = |keybind: &String| Keybind::normalize(keybind).unwrap_or(keybind.clone());
+    let overridden = overrides.keys().map(canonical).collect::<Vec<_>>();
+    defaults
+        .iter()
+        .filter(|(keybind, _)| !overridden.contains(&canonical(keybind)))
+        .chain(overrides)
+        .map(|(keybind, action)| (keybind.clone(), action.clone()))
+        .collect()
+}
+
+/// Keymap as pressed on `platform`, keyed by keybinds in canonical form with
+/// `mod` replaced. Keybinds that do not parse are left out.
+pub fn resolve_keymap(
+    keymap: &HashMap<String, FilteredAction>,
+    platform: Platform,
+) -> HashMap<String, FilteredAction> {
+    keymap
+        .iter()
+        .filter_map(|(keybind, action)| {
+            let keybind = Keybind::parse(keybind).ok()?.resolve(platform);
+            Some((keybind.to_string(), action.clone()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{find_conflicts, Action, FilteredAction, Keybind, Panel, Platform};
+
+    use super::{
+        action_variants, default_keymap, merge_keymap, resolve_keymap, ActionRegistry, ACTIONS,
+    };
+
+    #[test]
+    fn test_registry() {
+        let variants = action_variants();
+        assert_eq!(variants.len(), ACTIONS.len());
+        let registry = ActionRegistry::new();
+        for info in registry.actions.iter() {
+            assert!(variants.contains_key(&info.ty), "{}", info.ty);
+            assert!(!info.description.is_empty(), "{}", info.ty);
+        }
+
+        let toggle = registry.get("togglePanel").unwrap();
+        assert_eq!(toggle.description, "Toggles pane open and closed");
+        assert_eq!(toggle.default_keybinds, ["ctrl+e", "ctrl+q", "ctrl+r"]);
+        // Argument types are inlined, so the schema stands on its own
+        let args = toggle.args.as_ref().unwrap();
+        assert_eq!(args["enum"][0], "editorPanel");
+        assert!(registry.get("playPause").unwrap().args.is_none());
+        assert_eq!(
+            registry.get("openDocument").unwrap().args.as_ref().unwrap()["type"],
+            "string"
+        );
+    }
+
+    #[test]
+    fn test_default_keymap() {
+        let keymap = default_keymap();
+        for keybind in keymap.keys() {
+            // Written in canonical form, as the frontend looks them up as is
+            assert_eq!(&Keybind::normalize(keybind).unwrap(), keybind);
+        }
+        assert_eq!(find_conflicts(&keymap), []);
+    }
+
+    #[test]
+    fn test_merge_keymap() {
+        let bind = |action| FilteredAction {
+            action,
+            condition: None,
+        };
+        let overrides = HashMap::from([
+            ("C-q".to_owned(), bind(Action::Focus(Panel::ProjectPanel))),
+            ("ctrl+p".to_owned(), bind(Action::PlayPause)),
+        ]);
+        let keymap = merge_keymap(&default_keymap(), &overrides);
+        assert_eq!(keymap.len(), default_keymap().len() + 1);
+        assert!(!keymap.contains_key("ctrl+q"));
+        assert_eq!(keymap["C-q"].action, Action::Focus(Panel::ProjectPanel));
+        assert_eq!(keymap["ctrl+p"].action, Action::PlayPause);
+        assert_eq!(keymap["ctrl+g"].action, Action::ToggleConsole);
+
+        let mac = resolve_keymap(&keymap, Platform::Mac);
+        assert_eq!(mac["meta+s"].action, Action::SaveAllFiles);
+        assert_eq!(mac["ctrl+q"].action, Action::Focus(Panel::ProjectPanel));
+        let other = resolve_keymap(&keymap, Platform::Other);
+        assert_eq!(other["ctrl+s"].action, Action::SaveAllFiles);
+    }
+}
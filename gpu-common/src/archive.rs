@@ -0,0 +1,263 @@
+//! Zip archives of projects, for backups and moving projects between instances.
+//!
+//! An archive holds a project directory as laid out on disk, see
+//! [`crate::ProjectDir`]: the manifest at its root and every file at its path,
+//! binary files as raw bytes. Archives of a directory itself, with the project
+//! in a single folder at their root, are accepted as well.
+//!
+//! Archives come from untrusted sources, so entries that would leave the
+//! project directory are rejected rather than skipped, and sizes are checked
+//! against the bytes actually read rather than the sizes entries claim.
+
+use std::io::{Cursor, Read, Write};
+
+use thiserror::Error;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{split_file, ProjectDir, ProjectDirError, MANIFEST_FILE};
+
+#[derive(Error, Debug)]
+pub enum ArchiveError {
+    #[error("Invalid zip archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Could not read archive: {0}")]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Dir(#[from] ProjectDirError),
+    #[error("`{0}` is not a safe path within the project")]
+    UnsafePath(String),
+    #[error("`{0}` does not have a supported extension")]
+    UnsupportedExtension(String),
+    #[error("Archive has more than {0} files")]
+    TooManyFiles(usize),
+    #[error("Archive is larger than {0} bytes once extracted")]
+    TooLarge(u64),
+}
+
+/// Bounds on what an imported archive may extract to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchiveLimits {
+    /// Number of files, including the manifest
+    pub max_files: usize,
+    /// Total size of all files in bytes
+    pub max_size: u64,
+}
+
+impl Default for ArchiveLimits {
+    fn default() -> Self {
+        Self {
+            max_files: 1024,
+            max_size: 32 * 1024 * 1024,
+        }
+    }
+}
+
+impl ProjectDir {
+    /// Packs the directory into a zip archive. Assets have to be downloaded
+    /// first, as their bytes are not part of the project.
+    pub fn to_archive(&self) -> Result<Vec<u8>, ArchiveError> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        for (path, data) in self.to_entries()? {
+            zip.start_file(path, options)?;
+            zip.write_all(&data)?;
+        }
+        Ok(zip.finish()?.into_inner())
+    }
+
+    /// Unpacks a zip archive created by [`ProjectDir::to_archive`], or of a
+    /// project directory
+    pub fn from_archive(archive: &[u8], limits: &ArchiveLimits) -> Result<Self, ArchiveError> {
+        let mut zip = ZipArchive::new(Cursor::new(archive))?;
+        if zip.len() > limits.max_files {
+            return Err(ArchiveError::TooManyFiles(limits.max_files));
+        }
+
+        let mut entries = Vec::with_capacity(zip.len());
+        let mut size = 0;
+        for index in 0..zip.len() {
+            let file = zip.by_index(index)?;
+            let path = safe_path(file.name())?;
+            if file.is_dir() {
+                continue;
+            }
+            // The size in the header may be a lie, so read at most one byte
+            // more than is left to tell
+            let left = limits.max_size - size;
+            let mut data = Vec::with_capacity(file.size().min(left) as usize);
+            file.take(left + 1).read_to_end(&mut data)?;
+            size += data.len() as u64;
+            if size > limits.max_size {
+                return Err(ArchiveError::TooLarge(limits.max_size));
+            }
+            entries.push((path, data));
+        }
+
+        let root = project_root(&entries);
+        let entries = entries
+            .into_iter()
+            .filter_map(|(path, data)| {
+                let path = path.strip_prefix(root.as_str())?.to_owned();
+                Some((path, data))
+            })
+            .filter(|(path, _)| !path.split('/').any(|segment| segment.starts_with('.')))
+            .map(|(path, data)| {
+                if path != MANIFEST_FILE && split_file(&path).is_err() {
+                    return Err(ArchiveError::UnsupportedExtension(path));
+                }
+                Ok((path, data))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::from_entries(entries)?)
+    }
+}
+
+/// Path of an archive entry, unless it is absolute or leaves the directory the
+/// archive is extracted to
+fn safe_path(name: &str) -> Result<String, ArchiveError> {
+    let unsafe_path = || ArchiveError::UnsafePath(name.to_owned());
+    if name.starts_with('/') || name.contains(['\\', ':', '\0']) {
+        return Err(unsafe_path());
+    }
+    let path = name.trim_end_matches('/');
+    if path.is_empty()
+        || path
+            .split('/')
+            .any(|segment| segment.is_empty() || segment == "..")
+    {
+        return Err(unsafe_path());
+    }
+    Ok(path.to_owned())
+}
+
+/// Prefix of the project within the archive, which is either empty or a
+/// single folder holding the manifest
+fn project_root(entries: &[(String, Vec<u8>)]) -> String {
+    if entries.iter().any(|(path, _)| path == MANIFEST_FILE) {
+        return String::new();
+    }
+    entries
+        .iter()
+        .find_map(|(path, _)| {
+            let (folder, file) = path.split_once('/')?;
+            (file == MANIFEST_FILE).then(|| format!("{folder}/"))
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    use crate::{FileData, Files, ProjectDir};
+
+    use super::{ArchiveError, ArchiveLimits};
+
+    fn project() -> ProjectDir {
+        let files = Files::from_entries([
+            ("/shaders/main.wgsl", FileData::from("// main")),
+            ("/textures/noise.png", FileData::from(vec![0, 159, 146])),
+        ])
+        .unwrap();
+        ProjectDir::from_project("Archived", files.into())
+    }
+
+    fn zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in entries {
+            if name.ends_with('/') {
+                zip.add_directory(*name, FileOptions::default()).unwrap();
+            } else {
+                zip.start_file(*name, FileOptions::default()).unwrap();
+                zip.write_all(data).unwrap();
+            }
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let archive = project().to_archive().unwrap();
+        let project = ProjectDir::from_archive(&archive, &ArchiveLimits::default()).unwrap();
+        assert_eq!(project.manifest.title, "Archived");
+        let noise = project.files.get("/textures/noise.png").unwrap();
+        assert_eq!(noise.data, FileData::from(vec![0, 159, 146]));
+        assert_eq!(
+            project
+                .files
+                .get("/shaders/main.wgsl")
+                .unwrap()
+                .data
+                .as_text(),
+            Some("// main")
+        );
+    }
+
+    #[test]
+    fn test_folder() {
+        let archive = zip(&[
+            ("toy/", b""),
+            ("toy/gputoy.toml", b"title = \"Zipped\""),
+            ("toy/main.wgsl", b"// main"),
+            ("toy/.git/config", b""),
+            ("__MACOSX/toy/._main.wgsl", b""),
+        ]);
+        let project = ProjectDir::from_archive(&archive, &ArchiveLimits::default()).unwrap();
+        assert_eq!(project.manifest.title, "Zipped");
+        assert_eq!(project.files.keys().collect::<Vec<_>>(), ["/main.wgsl"]);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let limits = ArchiveLimits::default();
+        let manifest: (&str, &[u8]) = ("gputoy.toml", b"title = \"Bad\"");
+        for name in [
+            "../evil.wgsl",
+            "/etc/evil.wgsl",
+            "a/../../evil.wgsl",
+            "C:\\evil.wgsl",
+        ] {
+            let archive = zip(&[manifest, (name, b"")]);
+            assert!(
+                matches!(
+                    ProjectDir::from_archive(&archive, &limits),
+                    Err(ArchiveError::UnsafePath(_))
+                ),
+                "{name}"
+            );
+        }
+        let archive = zip(&[manifest, ("main.exe", b"")]);
+        assert!(matches!(
+            ProjectDir::from_archive(&archive, &limits),
+            Err(ArchiveError::UnsupportedExtension(_))
+        ));
+        assert!(matches!(
+            ProjectDir::from_archive(b"not a zip", &limits),
+            Err(ArchiveError::Zip(_))
+        ));
+
+        let archive = zip(&[manifest, ("a.wgsl", b""), ("b.wgsl", b"")]);
+        let few = ArchiveLimits {
+            max_files: 2,
+            ..limits
+        };
+        assert!(matches!(
+            ProjectDir::from_archive(&archive, &few),
+            Err(ArchiveError::TooManyFiles(2))
+        ));
+        let large = [0; 64];
+        let archive = zip(&[manifest, ("a.wgsl", &large)]);
+        let small = ArchiveLimits {
+            max_size: 64,
+            ..limits
+        };
+        assert!(matches!(
+            ProjectDir::from_archive(&archive, &small),
+            Err(ArchiveError::TooLarge(64))
+        ));
+    }
+}
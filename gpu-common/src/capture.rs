@@ -0,0 +1,57 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureOptions {
+    /// Width of the captured image in pixels
+    pub width: u32,
+    /// Height of the captured image in pixels
+    pub height: u32,
+    /// Time in seconds to render at, or the current time if not given
+    #[serde(default)]
+    pub time: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub enum RecordFormat {
+    /// Every frame as a separate png
+    PngSequence,
+    /// Animated gif, with a palette quantized per frame
+    Gif,
+    /// WebM with a VP8 video track. Frames are returned raw when recorded, to be
+    /// encoded to VP8 by the caller and handed back to the recorder, which only
+    /// writes the container. Needs an encoder such as the browser's WebCodecs.
+    Webm,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct RecordOptions {
+    pub format: RecordFormat,
+    /// Width of recorded frames in pixels
+    pub width: u32,
+    /// Height of recorded frames in pixels
+    pub height: u32,
+    /// Frames per second of the recording
+    pub fps: u32,
+    /// Time in seconds of the first frame
+    #[serde(default)]
+    pub start: f32,
+    /// Length of the recording in seconds
+    pub duration: f32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct RecordProgress {
+    /// Frames recorded so far
+    pub frame: u32,
+    /// Frames in the whole recording
+    pub total: u32,
+}
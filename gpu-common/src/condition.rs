@@ -0,0 +1,626 @@
+//! Conditions under which a keybind applies, like `when` clauses in VS Code.
+//!
+//! ```text
+//! editorFocus && !isPlaying
+//! panel == 'resourcePanel' || (userLoggedIn && filesDirty)
+//! ```
+//!
+//! Context keys are looked up in a [`ConditionContext`]. Keys that are missing
+//! are false, strings are true unless empty. `==` and `!=` compare keys and
+//! string or boolean literals by their text. `==` and `!=` bind tightest, then
+//! `!`, then `&&`, then `||`.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Display;
+use std::str::FromStr;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Context keys set by the editor, along with what they mean
+pub const CONTEXT_KEYS: [(&str, &str); 6] = [
+    ("editorFocus", "The editor has focus"),
+    (
+        "panel",
+        "Panel that has focus: 'editorPanel', 'projectPanel', 'resourcePanel' or 'viewportPanel'",
+    ),
+    ("isPlaying", "The project is running"),
+    ("userLoggedIn", "A user is logged in"),
+    ("filesDirty", "Some file has unsaved changes"),
+    ("currentFileDirty", "The open file has unsaved changes"),
+];
+
+/// Most contexts [`Condition::overlaps`] tries before giving up and assuming
+/// the conditions overlap
+const MAX_OVERLAP_CONTEXTS: usize = 1 << 14;
+
+/// Longest condition in characters that is parsed. Conditions come from user
+/// configs, which the backend parses too.
+pub const MAX_CONDITION_LEN: usize = 1024;
+/// Deepest nesting of `!` and parentheses that is parsed, as the parser recurses
+/// on each
+const MAX_CONDITION_DEPTH: usize = 32;
+
+/// Value of a context key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(untagged)]
+pub enum ContextValue {
+    Bool(bool),
+    String(String),
+}
+
+impl From<bool> for ContextValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<&str> for ContextValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_owned())
+    }
+}
+
+/// Values of context keys conditions are evaluated against
+pub type ConditionContext = HashMap<String, ContextValue>;
+
+/// Parsed condition, see the module documentation for the syntax
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    Key(String),
+    Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Eq(Operand, Operand),
+    Ne(Operand, Operand),
+}
+
+/// Side of a comparison
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    Key(String),
+    String(String),
+    Bool(bool),
+}
+
+/// Error in a condition, at a character offset into its source
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{message} at column {}", .offset + 1)]
+pub struct ConditionError {
+    pub message: String,
+    pub offset: usize,
+}
+
+impl Condition {
+    /// Parses `source`, accepting any context key
+    pub fn parse(source: &str) -> Result<Self, ConditionError> {
+        Ok(Parser::new(source)?.parse()?.0)
+    }
+
+    /// Parses `source`, rejecting keys that are not in [`CONTEXT_KEYS`]
+    pub fn parse_known(source: &str) -> Result<Self, ConditionError> {
+        let (condition, keys) = Parser::new(source)?.parse()?;
+        for (key, offset) in keys {
+            if !CONTEXT_KEYS.iter().any(|(known, _)| *known == key) {
+                return Err(ConditionError {
+                    message: format!("Unknown context key `{key}`"),
+                    offset,
+                });
+            }
+        }
+        Ok(condition)
+    }
+
+    pub fn evaluate(&self, context: &ConditionContext) -> bool {
+        match self {
+            Self::Key(key) => match context.get(key) {
+                Some(ContextValue::Bool(value)) => *value,
+                Some(ContextValue::String(value)) => !value.is_empty(),
+                None => false,
+            },
+            Self::Not(condition) => !condition.evaluate(context),
+            Self::And(a, b) => a.evaluate(context) && b.evaluate(context),
+            Self::Or(a, b) => a.evaluate(context) || b.evaluate(context),
+            Self::Eq(a, b) => a.text(context).is_some_and(|a| Some(a) == b.text(context)),
+            Self::Ne(a, b) => !Self::Eq(a.clone(), b.clone()).evaluate(context),
+        }
+    }
+
+    /// Whether some context satisfies both conditions.
+    ///
+    /// Tries every context the two can tell apart: each key either missing,
+    /// a boolean, empty, one of the strings compared against or some other
+    /// string. Conditions with too many keys to try are assumed to overlap.
+    pub fn overlaps(&self, other: &Condition) -> bool {
+        let mut keys = BTreeSet::new();
+        let mut strings = BTreeSet::new();
+        self.collect(&mut keys, &mut strings);
+        other.collect(&mut keys, &mut strings);
+
+        let other_string = (0..)
+            .map(|n| format!("other{n}"))
+            .find(|string| !strings.contains(string.as_str()))
+            .unwrap_or_default();
+        let mut values = vec![
+            None,
+            Some(ContextValue::Bool(true)),
+            Some(ContextValue::Bool(false)),
+            Some(ContextValue::from("")),
+            Some(ContextValue::String(other_string)),
+        ];
+        values.extend(
+            strings
+                .iter()
+                .map(|string| Some(ContextValue::from(*string))),
+        );
+
+        let contexts = u32::try_from(keys.len())
+            .ok()
+            .and_then(|len| values.len().checked_pow(len));
+        if contexts.is_none_or(|contexts| contexts > MAX_OVERLAP_CONTEXTS) {
+            return true;
+        }
+        // Counts through every combination of values, one digit per key
+        let mut digits = vec![0; keys.len()];
+        loop {
+            let context = keys
+                .iter()
+                .zip(&digits)
+                .filter_map(|(key, digit)| Some((key.to_string(), values[*digit].clone()?)))
+                .collect();
+            if self.evaluate(&context) && other.evaluate(&context) {
+                return true;
+            }
+            let Some(position) = digits.iter().position(|digit| digit + 1 < values.len()) else {
+                return false;
+            };
+            digits[position] += 1;
+            digits[..position].fill(0);
+        }
+    }
+
+    /// Keys and string literals appearing in the condition
+    fn collect<'a>(&'a self, keys: &mut BTreeSet<&'a str>, strings: &mut BTreeSet<&'a str>) {
+        match self {
+            Self::Key(key) => {
+                keys.insert(key);
+            }
+            Self::Not(condition) => condition.collect(keys, strings),
+            Self::And(a, b) | Self::Or(a, b) => {
+                a.collect(keys, strings);
+                b.collect(keys, strings);
+            }
+            Self::Eq(a, b) | Self::Ne(a, b) => {
+                for operand in [a, b] {
+                    match operand {
+                        Operand::Key(key) => {
+                            keys.insert(key);
+                        }
+                        Operand::String(string) => {
+                            strings.insert(string);
+                        }
+                        Operand::Bool(_) => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = ConditionError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Self::parse(source)
+    }
+}
+
+impl Operand {
+    /// Text the operand is compared by, or `None` for missing keys
+    fn text(&self, context: &ConditionContext) -> Option<String> {
+        match self {
+            Self::Key(key) => match context.get(key)? {
+                ContextValue::Bool(value) => Some(value.to_string()),
+                ContextValue::String(value) => Some(value.clone()),
+            },
+            Self::String(value) => Some(value.clone()),
+            Self::Bool(value) => Some(value.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    String(String),
+    And,
+    Or,
+    Not,
+    Eq,
+    Ne,
+    Open,
+    Close,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Ident(ident) => write!(f, "`{ident}`"),
+            Token::String(string) => write!(f, "'{string}'"),
+            Token::And => f.write_str("`&&`"),
+            Token::Or => f.write_str("`||`"),
+            Token::Not => f.write_str("`!`"),
+            Token::Eq => f.write_str("`==`"),
+            Token::Ne => f.write_str("`!=`"),
+            Token::Open => f.write_str("`(`"),
+            Token::Close => f.write_str("`)`"),
+        }
+    }
+}
+
+/// Splits `source` into tokens along with their character offsets
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ConditionError> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        let next = chars.get(i + 1).copied();
+        let error = |message: &str| ConditionError {
+            message: message.to_owned(),
+            offset: start,
+        };
+        let token = match chars[i] {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '&' if next == Some('&') => Token::And,
+            '|' if next == Some('|') => Token::Or,
+            '=' if next == Some('=') => Token::Eq,
+            '!' if next == Some('=') => Token::Ne,
+            '&' => return Err(error("Expected `&&`")),
+            '|' => return Err(error("Expected `||`")),
+            '=' => return Err(error("Expected `==`")),
+            '!' => Token::Not,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            quote @ ('\'' | '"') => {
+                let len = chars[i + 1..]
+                    .iter()
+                    .position(|c| *c == quote)
+                    .ok_or_else(|| error("Unterminated string"))?;
+                let string = chars[i + 1..i + 1 + len].iter().collect();
+                i += len + 2;
+                tokens.push((Token::String(string), start));
+                continue;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let len = chars[i..]
+                    .iter()
+                    .position(|c| !(c.is_alphanumeric() || *c == '_' || *c == '.'))
+                    .unwrap_or(chars.len() - i);
+                let ident = chars[i..i + len].iter().collect();
+                i += len;
+                tokens.push((Token::Ident(ident), start));
+                continue;
+            }
+            c => {
+                return Err(ConditionError {
+                    message: format!("Unexpected character `{c}`"),
+                    offset: start,
+                })
+            }
+        };
+        i += match token {
+            Token::Not | Token::Open | Token::Close => 1,
+            _ => 2,
+        };
+        tokens.push((token, start));
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser, collecting the context keys it comes across
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    /// Offset of the end of the source, where errors about missing tokens point
+    end: usize,
+    keys: Vec<(String, usize)>,
+    /// Nesting of `!` and parentheses at the current position
+    depth: usize,
+}
+
+type Parsed<T> = Result<T, ConditionError>;
+
+impl Parser {
+    fn new(source: &str) -> Parsed<Self> {
+        let end = source.chars().count();
+        if end > MAX_CONDITION_LEN {
+            return Err(ConditionError {
+                message: format!("Condition is longer than {MAX_CONDITION_LEN} characters"),
+                offset: MAX_CONDITION_LEN,
+            });
+        }
+        Ok(Self {
+            tokens: tokenize(source)?,
+            position: 0,
+            end,
+            keys: Vec::new(),
+            depth: 0,
+        })
+    }
+
+    fn parse(mut self) -> Parsed<(Condition, Vec<(String, usize)>)> {
+        if self.tokens.is_empty() {
+            return Err(ConditionError {
+                message: "Condition is empty".to_owned(),
+                offset: 0,
+            });
+        }
+        let condition = self.or()?;
+        if let Some((token, offset)) = self.tokens.get(self.position) {
+            return Err(ConditionError {
+                message: format!("Unexpected {token}, expected `&&` or `||`"),
+                offset: *offset,
+            });
+        }
+        Ok((condition, self.keys))
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Option<(Token, usize)> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// Error about the token at the current position, or the end
+    fn expected(&self, expected: &str) -> ConditionError {
+        match self.tokens.get(self.position) {
+            Some((token, offset)) => ConditionError {
+                message: format!("Expected {expected}, found {token}"),
+                offset: *offset,
+            },
+            None => ConditionError {
+                message: format!("Expected {expected}, found end of condition"),
+                offset: self.end,
+            },
+        }
+    }
+
+    fn or(&mut self) -> Parsed<Condition> {
+        let mut condition = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+        Ok(condition)
+    }
+
+    fn and(&mut self) -> Parsed<Condition> {
+        let mut condition = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.position += 1;
+            condition = Condition::And(Box::new(condition), Box::new(self.unary()?));
+        }
+        Ok(condition)
+    }
+
+    fn unary(&mut self) -> Parsed<Condition> {
+        if matches!(self.peek(), Some(Token::Not | Token::Open)) {
+            if self.depth == MAX_CONDITION_DEPTH {
+                return Err(ConditionError {
+                    message: format!("Condition is nested deeper than {MAX_CONDITION_DEPTH}"),
+                    offset: self.tokens[self.position].1,
+                });
+            }
+            self.depth += 1;
+            let condition = self.nested();
+            self.depth -= 1;
+            return condition;
+        }
+        self.comparison()
+    }
+
+    /// `!` or a parenthesized condition
+    fn nested(&mut self) -> Parsed<Condition> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.position += 1;
+                Ok(Condition::Not(Box::new(self.unary()?)))
+            }
+            Some(Token::Open) => {
+                self.position += 1;
+                let condition = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(self.expected("`)`"));
+                }
+                self.position += 1;
+                Ok(condition)
+            }
+            _ => self.comparison(),
+        }
+    }
+
+    fn comparison(&mut self) -> Parsed<Condition> {
+        let left = self.operand()?;
+        let ne = match self.peek() {
+            Some(Token::Eq) => false,
+            Some(Token::Ne) => true,
+            _ => {
+                return match left {
+                    Operand::Key(key) => Ok(Condition::Key(key)),
+                    Operand::Bool(_) | Operand::String(_) => {
+                        self.position -= 1;
+                        Err(ConditionError {
+                            message: "Literals can only be compared with `==` or `!=`".to_owned(),
+                            offset: self.tokens[self.position].1,
+                        })
+                    }
+                };
+            }
+        };
+        self.position += 1;
+        let right = self.operand()?;
+        Ok(if ne {
+            Condition::Ne(left, right)
+        } else {
+            Condition::Eq(left, right)
+        })
+    }
+
+    fn operand(&mut self) -> Parsed<Operand> {
+        let expected = self.expected("a context key or string");
+        match self.next() {
+            Some((Token::Ident(ident), offset)) => Ok(match ident.as_str() {
+                "true" => Operand::Bool(true),
+                "false" => Operand::Bool(false),
+                _ => {
+                    self.keys.push((ident.clone(), offset));
+                    Operand::Key(ident)
+                }
+            }),
+            Some((Token::String(string), _)) => Ok(Operand::String(string)),
+            _ => Err(expected),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Condition, ConditionContext, ConditionError, ContextValue};
+
+    fn context() -> ConditionContext {
+        [
+            ("editorFocus", ContextValue::from(true)),
+            ("isPlaying", false.into()),
+            ("panel", "resourcePanel".into()),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_owned(), value))
+        .collect()
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let context = context();
+        let cases = [
+            ("editorFocus", true),
+            ("!editorFocus", false),
+            ("editorFocus && isPlaying", false),
+            ("editorFocus && !isPlaying", true),
+            ("isPlaying || panel == 'resourcePanel'", true),
+            ("panel == \"editorPanel\"", false),
+            ("panel != 'editorPanel'", true),
+            ("isPlaying == false", true),
+            ("userLoggedIn", false),
+            ("userLoggedIn == ''", false),
+            ("!(isPlaying || userLoggedIn) && editorFocus", true),
+            // `&&` binds tighter than `||`
+            ("editorFocus || isPlaying && userLoggedIn", true),
+            ("!isPlaying == true", true),
+        ];
+        for (source, expected) in cases {
+            let condition = Condition::parse(source).unwrap();
+            assert_eq!(condition.evaluate(&context), expected, "{source}");
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases = [
+            ("", "Condition is empty", 0),
+            ("editorFocus & isPlaying", "Expected `&&`", 12),
+            ("panel = 'a'", "Expected `==`", 6),
+            ("panel == 'a", "Unterminated string", 9),
+            (
+                "editorFocus &&",
+                "Expected a context key or string, found end of condition",
+                14,
+            ),
+            ("(editorFocus", "Expected `)`, found end of condition", 12),
+            (
+                "editorFocus isPlaying",
+                "Unexpected `isPlaying`, expected `&&` or `||`",
+                12,
+            ),
+            (
+                "'a' && b",
+                "Literals can only be compared with `==` or `!=`",
+                0,
+            ),
+            (
+                "a == == b",
+                "Expected a context key or string, found `==`",
+                5,
+            ),
+            ("a # b", "Unexpected character `#`", 2),
+        ];
+        for (source, message, offset) in cases {
+            let err = Condition::parse(source).unwrap_err();
+            assert_eq!(
+                err,
+                ConditionError {
+                    message: message.to_owned(),
+                    offset
+                },
+                "{source}"
+            );
+        }
+        assert_eq!(
+            Condition::parse("a &").unwrap_err().to_string(),
+            "Expected `&&` at column 3"
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let deep = format!("{}editorFocus", "!".repeat(20_000));
+        let err = Condition::parse(&deep).unwrap_err();
+        assert_eq!(err.message, "Condition is longer than 1024 characters");
+
+        let deep = format!("{}editorFocus{}", "(".repeat(100), ")".repeat(100));
+        let err = Condition::parse(&deep).unwrap_err();
+        assert_eq!(err.message, "Condition is nested deeper than 32");
+        assert_eq!(err.offset, 32);
+
+        let nested = format!("{}editorFocus{}", "!(".repeat(16), ")".repeat(16));
+        assert!(Condition::parse(&nested).is_ok());
+    }
+
+    #[test]
+    fn test_known_keys() {
+        assert!(Condition::parse_known("editorFocus && panel == 'editorPanel'").is_ok());
+        let err = Condition::parse_known("editorFocus && editorFocused").unwrap_err();
+        assert_eq!(err.message, "Unknown context key `editorFocused`");
+        assert_eq!(err.offset, 15);
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let cases = [
+            ("editorFocus", "isPlaying", true),
+            ("editorFocus", "!editorFocus", false),
+            ("editorFocus && isPlaying", "!isPlaying", false),
+            ("panel == 'editorPanel'", "panel == 'projectPanel'", false),
+            ("panel == 'editorPanel'", "panel != 'projectPanel'", true),
+            ("panel != 'editorPanel'", "panel", true),
+            ("!panel", "panel == ''", true),
+            ("a == b", "a && !b", false),
+            ("isPlaying == true", "!isPlaying", false),
+        ];
+        for (a, b, overlaps) in cases {
+            let a = Condition::parse(a).unwrap();
+            let b = Condition::parse(b).unwrap();
+            assert_eq!(a.overlaps(&b), overlaps, "{a:?} {b:?}");
+            assert_eq!(b.overlaps(&a), overlaps, "{b:?} {a:?}");
+        }
+    }
+}
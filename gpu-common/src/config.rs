@@ -1,16 +1,27 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
 #[serde(rename_all = "camelCase")]
 pub struct Config {
     #[serde(default)]
-    perf_level: Option<PerformanceLevel>,
+    pub perf_level: Option<PerformanceLevel>,
+    /// Frame rate limit, or 0 for no limit
     #[serde(default)]
-    limit_fps: u32,
+    pub limit_fps: u32,
+    /// Multiplier applied to the canvas size in device pixels, i.e. 0.5 renders at
+    /// half resolution
+    #[serde(default = "default_render_scale")]
+    pub render_scale: f32,
 }
 
-#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+fn default_render_scale() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
 pub enum PerformanceLevel {
     #[default]
     Default,
@@ -0,0 +1,144 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Contents of a file in the virtual directory.
+///
+/// example:
+/// ```ts
+/// "// plain text"
+/// { "base64": "iVBORw0KGgo..." }
+/// { "id": "...", "hash": "9f86d08..." }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(untagged)]
+pub enum FileData {
+    /// Plain text, for every file that is not binary
+    Text(String),
+    /// Raw bytes, sent over the wire as base64
+    Bytes {
+        #[serde(rename = "base64", with = "base64_bytes")]
+        #[schemars(with = "String")]
+        #[cfg_attr(feature = "tsify", tsify(type = "string"))]
+        bytes: Vec<u8>,
+    },
+    /// Bytes uploaded to gpu-back on their own, to be fetched when needed
+    Asset(AssetRef),
+}
+
+impl FileData {
+    /// Text of the file, or `None` if it is binary
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Hash of the contents, the same for bytes and the asset they are
+    /// uploaded as
+    pub fn hash(&self) -> String {
+        match self {
+            Self::Text(text) => content_hash(text.as_bytes()),
+            Self::Bytes { bytes } => content_hash(bytes),
+            Self::Asset(asset) => asset.hash.clone(),
+        }
+    }
+}
+
+impl Default for FileData {
+    fn default() -> Self {
+        Self::Text(String::new())
+    }
+}
+
+impl From<String> for FileData {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<&str> for FileData {
+    fn from(text: &str) -> Self {
+        Self::Text(text.to_owned())
+    }
+}
+
+impl From<Vec<u8>> for FileData {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::Bytes { bytes }
+    }
+}
+
+/// Reference to bytes uploaded to gpu-back
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+pub struct AssetRef {
+    /// Id of the asset, as used in `/asset/{id}`
+    pub id: String,
+    /// Hex encoded sha256 of the bytes
+    pub hash: String,
+}
+
+/// Hex encoded sha256 of `bytes`, which identifies an asset
+pub fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+mod base64_bytes {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        base64::decode(encoded).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{content_hash, AssetRef, FileData};
+
+    #[test]
+    fn test_serialize() {
+        let cases = [
+            (FileData::from("text"), serde_json::json!("text")),
+            (
+                FileData::from(vec![0, 159, 146]),
+                serde_json::json!({ "base64": "AJ+S" }),
+            ),
+            (
+                FileData::Asset(AssetRef {
+                    id: "a".to_owned(),
+                    hash: "b".to_owned(),
+                }),
+                serde_json::json!({ "id": "a", "hash": "b" }),
+            ),
+        ];
+        for (data, json) in cases {
+            assert_eq!(serde_json::to_value(&data).unwrap(), json);
+            assert_eq!(serde_json::from_value::<FileData>(json).unwrap(), data);
+        }
+        assert!(serde_json::from_value::<FileData>(serde_json::json!({ "base64": "!" })).is_err());
+    }
+
+    #[test]
+    fn test_hash() {
+        assert_eq!(
+            content_hash(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let asset = FileData::Asset(AssetRef {
+            id: "a".to_owned(),
+            hash: content_hash(&[1, 2]),
+        });
+        assert_eq!(FileData::from(vec![1, 2]).hash(), asset.hash());
+    }
+}
@@ -0,0 +1,476 @@
+//! On-disk layout of a project, so projects can be kept in git and edited with
+//! regular tools.
+//!
+//! ```text
+//! my-toy/
+//! ├── gputoy.toml          manifest with title, config, layout and runner
+//! ├── shaders/main.wgsl    every file at its path in `Files`
+//! └── textures/noise.png   binary files as raw bytes
+//! ```
+//!
+//! Paths in the directory are relative to its root and separated by `/`, while
+//! keys of `Files` start with a `/`. The manifest carries a `schemaVersion` and
+//! is migrated like a project when read, see [`migrate_project`].
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use thiserror::Error;
+
+use crate::project::current_schema_version;
+use crate::{
+    migrate_project, split_file, Config, FileData, Files, Layout, MigrationError, PathError,
+    Project, SCHEMA_VERSION,
+};
+
+/// Name of the manifest at the root of a project directory
+pub const MANIFEST_FILE: &str = "gputoy.toml";
+
+#[derive(Error, Debug)]
+pub enum ProjectDirError {
+    #[error("Could not access {0}: {1}")]
+    Io(PathBuf, #[source] std::io::Error),
+    #[error("Project directory has no gputoy.toml")]
+    MissingManifest,
+    #[error("Invalid gputoy.toml: {0}")]
+    Manifest(#[from] toml::de::Error),
+    #[error("Invalid gputoy.toml: {0}")]
+    ManifestFields(#[source] serde_json::Error),
+    #[error("Could not migrate gputoy.toml: {0}")]
+    ManifestMigration(#[from] MigrationError),
+    #[error("Could not write gputoy.toml: {0}")]
+    ManifestWrite(#[from] toml::ser::Error),
+    #[error("`{0}` is not a valid project path")]
+    InvalidPath(String),
+    #[error(transparent)]
+    Path(#[from] PathError),
+    #[error("{0} is an asset that was not downloaded")]
+    Asset(String),
+    #[error("{0} is not valid utf-8")]
+    NotUtf8(String),
+}
+
+/// Everything about a project that is not a file
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectManifest {
+    /// Version of the project format the manifest was written with
+    #[serde(default = "current_schema_version")]
+    pub schema_version: u32,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Path of the file the project is run from, relative to the project root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runner: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<Config>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<Layout>,
+}
+
+/// Manifest as written to disk, along with metadata of files that does not fit
+/// in the files themselves
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestFile<M> {
+    #[serde(flatten)]
+    manifest: M,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    files: BTreeMap<String, FileMeta>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FileMeta {
+    /// Url contents are fetched from on project load
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fetch: Option<String>,
+}
+
+/// Project as stored in a directory
+#[derive(Debug, Clone)]
+pub struct ProjectDir {
+    pub manifest: ProjectManifest,
+    pub files: Files,
+}
+
+impl ProjectDir {
+    pub fn from_project(title: impl Into<String>, project: Project) -> Self {
+        Self {
+            manifest: ProjectManifest {
+                schema_version: SCHEMA_VERSION,
+                title: title.into(),
+                description: None,
+                runner: None,
+                config: project.config,
+                layout: project.layout,
+            },
+            files: project.files,
+        }
+    }
+
+    /// Project to build, leaving out the title, description and runner
+    pub fn into_project(self) -> Project {
+        Project {
+            schema_version: SCHEMA_VERSION,
+            files: self.files,
+            layout: self.manifest.layout,
+            config: self.manifest.config,
+        }
+    }
+
+    /// Reads the project in directory `root`.
+    ///
+    /// Hidden files and directories are skipped, i.e. `.git`, along with files
+    /// whose extension is not supported.
+    pub fn read(root: &Path) -> Result<Self, ProjectDirError> {
+        let mut entries = Vec::new();
+        read_entries(root, root, &mut entries)?;
+        Self::from_entries(entries)
+    }
+
+    /// Writes the project to directory `root`, creating it if needed. Files
+    /// already in the directory that are not part of the project are left as is.
+    pub fn write(&self, root: &Path) -> Result<(), ProjectDirError> {
+        for (path, data) in self.to_entries()? {
+            let path = root.join(path);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|err| ProjectDirError::Io(parent.to_owned(), err))?;
+            }
+            std::fs::write(&path, data).map_err(|err| ProjectDirError::Io(path, err))?;
+        }
+        Ok(())
+    }
+
+    /// Contents of every file in the directory keyed by relative path, including
+    /// the manifest
+    pub fn to_entries(&self) -> Result<Vec<(String, Vec<u8>)>, ProjectDirError> {
+        let mut entries = Vec::with_capacity(self.files.len() + 1);
+        let mut meta = BTreeMap::new();
+        for (key, file) in self.files.iter() {
+            let path = key_to_path(key)?;
+            let data = match file.data {
+                FileData::Text(ref text) => text.clone().into_bytes(),
+                FileData::Bytes { ref bytes } => bytes.clone(),
+                FileData::Asset(_) => return Err(ProjectDirError::Asset(path)),
+            };
+            if file.fetch.is_some() {
+                let fetch = file.fetch.clone();
+                meta.insert(path.clone(), FileMeta { fetch });
+            }
+            entries.push((path, data));
+        }
+        let manifest = toml::to_string_pretty(&ManifestFile {
+            manifest: &self.manifest,
+            files: meta,
+        })?;
+        entries.push((MANIFEST_FILE.to_owned(), manifest.into_bytes()));
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(entries)
+    }
+
+    /// Project from the contents of files keyed by relative path. Entries that
+    /// would be skipped when reading a directory are skipped here too.
+    pub fn from_entries(
+        entries: impl IntoIterator<Item = (String, Vec<u8>)>,
+    ) -> Result<Self, ProjectDirError> {
+        let mut manifest = None;
+        let mut files = Files::new();
+        for (path, data) in entries {
+            if path == MANIFEST_FILE {
+                let data =
+                    String::from_utf8(data).map_err(|_| ProjectDirError::NotUtf8(path.clone()))?;
+                manifest = Some(read_manifest(&data)?);
+                continue;
+            }
+            if let Some(data) = file_data(&path, data)? {
+                files.write(&path, data)?;
+            }
+        }
+        let ManifestFile {
+            manifest,
+            files: meta,
+        } = manifest.ok_or(ProjectDirError::MissingManifest)?;
+        // Metadata of files that no longer exist is dropped
+        for (path, meta) in meta {
+            if let Some(file) = files.get_mut(&path) {
+                file.fetch = meta.fetch;
+            }
+        }
+        Ok(Self { manifest, files })
+    }
+}
+
+/// Fields of the manifest that migrations apply to
+const MIGRATED_FIELDS: [&str; 3] = ["schemaVersion", "layout", "config"];
+
+/// Manifest of any schema version, migrated to the current one before it is
+/// deserialized. Migrations see it as a project without files, since those are
+/// read from the directory as they are.
+fn read_manifest(data: &str) -> Result<ManifestFile<ProjectManifest>, ProjectDirError> {
+    let manifest = toml::from_str::<toml::Table>(data)?;
+    let mut manifest = match serde_json::to_value(manifest) {
+        Ok(Value::Object(manifest)) => manifest,
+        Ok(_) => return Err(MigrationError::NotAnObject.into()),
+        Err(err) => return Err(ProjectDirError::ManifestFields(err)),
+    };
+    let mut project = Map::new();
+    for field in MIGRATED_FIELDS {
+        if let Some(value) = manifest.remove(field) {
+            project.insert(field.to_owned(), value);
+        }
+    }
+    project.insert("files".to_owned(), json!({ "map": {} }));
+    let mut project = Value::Object(project);
+    migrate_project(&mut project)?;
+    for field in MIGRATED_FIELDS {
+        if let Some(value) = project.get_mut(field) {
+            manifest.insert(field.to_owned(), value.take());
+        }
+    }
+    serde_json::from_value(Value::Object(manifest)).map_err(ProjectDirError::ManifestFields)
+}
+
+/// Relative path of a file in the directory from its key in `Files`, or an
+/// error if the file could not be read back from that path
+pub fn key_to_path(key: &str) -> Result<String, ProjectDirError> {
+    let path = key.strip_prefix('/').unwrap_or(key);
+    let valid = path.split('/').all(|segment| {
+        !segment.is_empty()
+            // Hidden files would not be read back
+            && !segment.starts_with('.')
+            // Would be a separator or drive prefix on windows
+            && !segment.contains(['\\', ':', '\0'])
+    });
+    if valid && path != MANIFEST_FILE {
+        Ok(path.to_owned())
+    } else {
+        Err(ProjectDirError::InvalidPath(key.to_owned()))
+    }
+}
+
+/// Contents of a directory entry as held in `File.data`, or `None` if it is
+/// skipped
+fn file_data(path: &str, data: Vec<u8>) -> Result<Option<FileData>, ProjectDirError> {
+    if path.split('/').any(|segment| segment.starts_with('.')) {
+        return Ok(None);
+    }
+    let extension = match split_file(path) {
+        Ok((_, _, extension)) => extension,
+        Err(_) => return Ok(None),
+    };
+    let data = if extension.is_binary() {
+        FileData::from(data)
+    } else {
+        String::from_utf8(data)
+            .map_err(|_| ProjectDirError::NotUtf8(path.to_owned()))?
+            .into()
+    };
+    Ok(Some(data))
+}
+
+/// Collects every file under `dir` keyed by its path relative to `root`
+fn read_entries(
+    root: &Path,
+    dir: &Path,
+    entries: &mut Vec<(String, Vec<u8>)>,
+) -> Result<(), ProjectDirError> {
+    let read_dir =
+        std::fs::read_dir(dir).map_err(|err| ProjectDirError::Io(dir.to_owned(), err))?;
+    for entry in read_dir {
+        let entry = entry.map_err(|err| ProjectDirError::Io(dir.to_owned(), err))?;
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let file_type = entry
+            .file_type()
+            .map_err(|err| ProjectDirError::Io(path.clone(), err))?;
+        if file_type.is_dir() {
+            read_entries(root, &path, entries)?;
+            continue;
+        }
+        let relative = path
+            .strip_prefix(root)
+            .ok()
+            .and_then(|relative| relative.to_str())
+            .ok_or_else(|| ProjectDirError::InvalidPath(path.to_string_lossy().into_owned()))?
+            .replace(std::path::MAIN_SEPARATOR, "/");
+        let data = std::fs::read(&path).map_err(|err| ProjectDirError::Io(path.clone(), err))?;
+        entries.push((relative, data));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AssetRef, Config, FileData, Files, Layout, LayoutNode, Panel, PanelState, PerformanceLevel,
+        Project, SCHEMA_VERSION,
+    };
+
+    use super::{ProjectDir, ProjectDirError, MANIFEST_FILE};
+
+    fn project() -> ProjectDir {
+        let mut files = Files::from_entries([
+            ("/shaders/main.wgsl", "// main\n"),
+            ("/notes.md", "# notes"),
+        ])
+        .unwrap();
+        files.get_mut("/notes.md").unwrap().fetch = Some("https://example.com/notes.md".to_owned());
+        // Not valid utf-8
+        files
+            .create("/textures/noise.png", vec![0, 159, 146])
+            .unwrap();
+        let mut project = Project::from(files);
+        project.layout = Some(Layout::default());
+        project.config = Some(Config {
+            perf_level: Some(PerformanceLevel::PowerSaver),
+            limit_fps: 30,
+            render_scale: 0.5,
+        });
+        let mut dir = ProjectDir::from_project("Noise", project);
+        dir.manifest.runner = Some("shaders/main.wgsl".to_owned());
+        dir
+    }
+
+    fn assert_same(a: &ProjectDir, b: &ProjectDir) {
+        assert_eq!(
+            serde_json::to_value(&a.manifest).unwrap(),
+            serde_json::to_value(&b.manifest).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_value(&a.files).unwrap(),
+            serde_json::to_value(&b.files).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_entries_round_trip() {
+        let dir = project();
+        let entries = dir.to_entries().unwrap();
+        let paths: Vec<_> = entries.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                MANIFEST_FILE,
+                "notes.md",
+                "shaders/main.wgsl",
+                "textures/noise.png"
+            ]
+        );
+        assert_eq!(entries[3].1, [0, 159, 146]);
+
+        let manifest = std::str::from_utf8(&entries[0].1).unwrap();
+        assert!(manifest.starts_with(&format!("schemaVersion = {SCHEMA_VERSION}\n")));
+        assert!(manifest.contains("title = \"Noise\""));
+        assert!(manifest.contains("[files.\"notes.md\"]"));
+
+        assert_same(&dir, &ProjectDir::from_entries(entries).unwrap());
+    }
+
+    #[test]
+    fn test_skipped_entries() {
+        let entries = vec![
+            (MANIFEST_FILE.to_owned(), b"title = \"Empty\"".to_vec()),
+            (".git/config".to_owned(), Vec::new()),
+            ("LICENSE".to_owned(), Vec::new()),
+            ("build.rs".to_owned(), Vec::new()),
+            ("shaders/.main.wgsl.swp".to_owned(), Vec::new()),
+        ];
+        let dir = ProjectDir::from_entries(entries).unwrap();
+        assert_eq!(dir.manifest.title, "Empty");
+        assert!(dir.files.is_empty());
+
+        let res = ProjectDir::from_entries(vec![("main.wgsl".to_owned(), Vec::new())]);
+        assert!(matches!(res, Err(ProjectDirError::MissingManifest)));
+    }
+
+    #[test]
+    fn test_manifest_migration() {
+        // Written before the layout was a tree of splits
+        let manifest = r#"
+title = "Old"
+
+[layout]
+isStatusOpen = true
+workspace = ["shaders/main.wgsl"]
+fileIndex = 0
+
+[layout.projectPanel]
+show = false
+size = 20.0
+"#;
+        let entries = vec![(MANIFEST_FILE.to_owned(), manifest.as_bytes().to_vec())];
+        let dir = ProjectDir::from_entries(entries).unwrap();
+        assert_eq!(dir.manifest.schema_version, SCHEMA_VERSION);
+        let layout = dir.manifest.layout.unwrap();
+        assert!(!layout.find(Panel::ProjectPanel).unwrap().is_shown());
+        let Some(LayoutNode::Panel { state, .. }) = layout.find(Panel::EditorPanel) else {
+            panic!("no editor panel");
+        };
+        assert_eq!(
+            *state,
+            PanelState::EditorPanel {
+                workspace: vec!["/shaders/main.wgsl".to_owned()],
+                file_index: Some(0),
+            }
+        );
+
+        // Written with a tree but without a schema version, which is kept as is
+        let dir = project();
+        let mut entries = dir.to_entries().unwrap();
+        let manifest = String::from_utf8(entries[0].1.clone()).unwrap();
+        let manifest = manifest.replace(&format!("schemaVersion = {SCHEMA_VERSION}\n"), "");
+        entries[0].1 = manifest.into_bytes();
+        assert_same(&dir, &ProjectDir::from_entries(entries).unwrap());
+
+        let newer = format!("schemaVersion = {}\ntitle = \"New\"", SCHEMA_VERSION + 1);
+        let entries = vec![(MANIFEST_FILE.to_owned(), newer.into_bytes())];
+        assert!(matches!(
+            ProjectDir::from_entries(entries),
+            Err(ProjectDirError::ManifestMigration(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_paths() {
+        for key in ["/.main.wgsl", "/c:/main.wgsl", "/shaders/.main.wgsl"] {
+            let mut dir = project();
+            dir.files.create(key, String::new()).unwrap();
+            assert!(
+                matches!(dir.to_entries(), Err(ProjectDirError::InvalidPath(_))),
+                "{key}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_asset_entry() {
+        let mut dir = project();
+        let asset = AssetRef {
+            id: "noise".to_owned(),
+            hash: String::new(),
+        };
+        dir.files
+            .write("/textures/noise.png", FileData::Asset(asset))
+            .unwrap();
+        assert!(matches!(dir.to_entries(), Err(ProjectDirError::Asset(_))));
+    }
+
+    #[test]
+    fn test_dir_round_trip() {
+        let root = std::env::temp_dir().join(format!("gputoy-project-{}", std::process::id()));
+        let dir = project();
+        dir.write(&root).unwrap();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::write(root.join(".git/HEAD"), "ref: refs/heads/main").unwrap();
+
+        let read = ProjectDir::read(&root);
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_same(&dir, &read.unwrap());
+    }
+}
@@ -0,0 +1,138 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::ProfileSnapshot;
+
+/// Event emitted by the gpu context, for the frontend to subscribe to
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase", tag = "ty", content = "c")]
+pub enum Event {
+    /// A frame was rendered and presented
+    Frame(FrameEvent),
+    /// A project build finished, successfully or not
+    Build(BuildEvent),
+    /// The gpu raised an error
+    Error(GpuError),
+    /// Periodic profiler stats, only emitted while profiling is enabled
+    Stats(ProfileSnapshot),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct FrameEvent {
+    /// Frames rendered since the project was first built or restarted, including this one
+    pub frame: u32,
+    /// Seconds since the project was first built or restarted
+    pub time: f32,
+    /// Milliseconds spent on the cpu recording and submitting the frame
+    pub cpu_time: f64,
+    /// Size of the rendered frame in pixels
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct BuildEvent {
+    pub success: bool,
+    /// Problems found while compiling, empty if the build failed on the gpu instead
+    pub diagnostics: Vec<BuildDiagnostic>,
+}
+
+/// Problem found while compiling a project file
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct BuildDiagnostic {
+    /// Path of the offending file, same as its key in `Files`
+    pub file: String,
+    pub message: String,
+    /// 1-based line the problem starts at, if known
+    pub line: Option<u32>,
+    /// 1-based column the problem starts at, if known
+    pub column: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub enum PassKind {
+    Render,
+    Compute,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub enum GpuErrorKind {
+    Validation,
+    OutOfMemory,
+    DeviceLost,
+}
+
+/// What the gpu was doing when an error was raised
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub enum GpuErrorPhase {
+    PipelineCreation,
+    Frame,
+    /// Raised outside of any error scope, so it could not be attributed to anything
+    Uncaptured,
+}
+
+/// Error raised by the gpu
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct GpuError {
+    pub kind: GpuErrorKind,
+    pub phase: GpuErrorPhase,
+    /// Kind of pass the error was raised in, if it could be narrowed down to one
+    pub pass: Option<PassKind>,
+    /// Shader file the error was raised for
+    pub file: Option<String>,
+    /// Entry point the error was raised for
+    pub entry_point: Option<String>,
+    pub message: String,
+}
+
+impl GpuError {
+    pub fn in_file(mut self, file: &str) -> Self {
+        self.file = Some(file.to_owned());
+        self
+    }
+
+    pub fn in_pass(mut self, file: &str, entry_point: &str, kind: PassKind) -> Self {
+        self.pass = Some(kind);
+        self.file = Some(file.to_owned());
+        self.entry_point = Some(entry_point.to_owned());
+        self
+    }
+}
+
+impl std::fmt::Display for GpuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let phase = match self.phase {
+            GpuErrorPhase::PipelineCreation => "creating pipeline",
+            GpuErrorPhase::Frame => "rendering frame",
+            GpuErrorPhase::Uncaptured => "doing something unknown",
+        };
+        write!(f, "{:?} error while {phase}", self.kind)?;
+        if let Some(pass) = self.pass {
+            write!(f, " in {pass:?} pass")?;
+        }
+        if let Some(ref entry_point) = self.entry_point {
+            write!(f, " `{entry_point}`")?;
+        }
+        if let Some(ref file) = self.file {
+            write!(f, " of {file}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl std::error::Error for GpuError {}
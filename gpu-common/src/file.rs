@@ -1,34 +1,331 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::path::{is_within, normalize_dir, normalize_file, split_file, PathError};
+use crate::FileData;
 
 /// Gputoy virtual directory. Each file in the map
-/// has its path from root as key, including file name
-/// and extension
+/// has its normalized path from root as key, including
+/// file name and extension. The other path fields of a
+/// file are derived from its key.
 ///
 /// example:
 /// ```ts
 /// map: {
-///     "shaders/main.wgsl": {
+///     "/shaders/main.wgsl": {
 ///         "data": "...",
-///         "dir": "shaders/",
+///         "dir": "shaders",
 ///         "fileName": "main",
 ///         "extension": "wgsl",
 ///     }
 /// }
 /// ```
-#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(try_from = "FilesRepr")]
 pub struct Files {
-    pub map: HashMap<String, File>,
+    map: BTreeMap<String, File>,
+}
+
+/// Files as sent over the wire, before their keys are checked
+#[derive(Deserialize)]
+struct FilesRepr {
+    map: HashMap<String, FileRepr>,
+}
+
+/// File as sent over the wire. The path fields are derived from its key, so
+/// they are neither required nor checked.
+#[derive(Deserialize)]
+struct FileRepr {
+    data: FileData,
+    #[serde(default)]
+    fetch: Option<String>,
+}
+
+/// Normalizes every key and derives the path fields of each file from its key,
+/// ignoring the ones sent along
+impl TryFrom<FilesRepr> for Files {
+    type Error = PathError;
+
+    fn try_from(repr: FilesRepr) -> Result<Self, Self::Error> {
+        let mut files = Files::new();
+        for (key, file) in repr.map {
+            let key = normalize_file(&key)?;
+            let file = File::at(&key, file.data, file.fetch)?;
+            files.check_free(&key)?;
+            files.map.insert(key, file);
+        }
+        Ok(files)
+    }
+}
+
+impl Files {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Files from contents keyed by path, written in order, so a later entry
+    /// replaces an earlier one at the same path. Fails if a path is invalid or is
+    /// a directory of another file.
+    pub fn from_entries<P: AsRef<str>, D: Into<FileData>>(
+        entries: impl IntoIterator<Item = (P, D)>,
+    ) -> Result<Self, PathError> {
+        let mut files = Files::new();
+        for (path, data) in entries {
+            files.write(path.as_ref(), data)?;
+        }
+        Ok(files)
+    }
+
+    pub fn get(&self, path: &str) -> Option<&File> {
+        self.map.get(&normalize_file(path).ok()?)
+    }
+
+    pub fn get_mut(&mut self, path: &str) -> Option<&mut File> {
+        self.map.get_mut(&normalize_file(path).ok()?)
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.get(path).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Files along with their keys, sorted by key
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &File)> {
+        self.map.iter().map(|(key, file)| (key.as_str(), file))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut File)> {
+        self.map.iter_mut().map(|(key, file)| (key.as_str(), file))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.map.keys().map(String::as_str)
+    }
+
+    /// Creates a file at `path`, failing if one already exists
+    pub fn create(
+        &mut self,
+        path: &str,
+        data: impl Into<FileData>,
+    ) -> Result<&mut File, PathError> {
+        let key = normalize_file(path)?;
+        if self.map.contains_key(&key) {
+            return Err(PathError::Exists(key));
+        }
+        self.write(&key, data)
+    }
+
+    /// Sets the contents of the file at `path`, creating it if needed
+    pub fn write(&mut self, path: &str, data: impl Into<FileData>) -> Result<&mut File, PathError> {
+        let key = normalize_file(path)?;
+        let data = data.into();
+        let file = match self.map.remove(&key) {
+            Some(file) => File { data, ..file },
+            None => {
+                self.check_free(&key)?;
+                File::at(&key, data, None)?
+            }
+        };
+        Ok(self.map.entry(key).or_insert(file))
+    }
+
+    /// Puts `file` at `path`, returning the file it replaced. Path fields of
+    /// `file` are derived from `path`.
+    pub fn insert(&mut self, path: &str, file: File) -> Result<Option<File>, PathError> {
+        let key = normalize_file(path)?;
+        let file = File::at(&key, file.data, file.fetch)?;
+        if !self.map.contains_key(&key) {
+            self.check_free(&key)?;
+        }
+        Ok(self.map.insert(key, file))
+    }
+
+    pub fn remove(&mut self, path: &str) -> Result<File, PathError> {
+        let key = normalize_file(path)?;
+        self.map.remove(&key).ok_or(PathError::NotFound(key))
+    }
+
+    /// Removes every file within the directory at `path`, returning their keys
+    pub fn remove_dir(&mut self, path: &str) -> Result<Vec<String>, PathError> {
+        let dir = normalize_dir(path)?;
+        let keys = self.keys_within(&dir);
+        if keys.is_empty() {
+            return Err(PathError::NotFound(dir));
+        }
+        for key in keys.iter() {
+            self.map.remove(key);
+        }
+        Ok(keys)
+    }
+
+    /// Renames or moves the file at `from` to `to`, failing if `to` exists
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<(), PathError> {
+        let from = normalize_file(from)?;
+        let to = normalize_file(to)?;
+        if !self.map.contains_key(&from) {
+            return Err(PathError::NotFound(from));
+        }
+        if from == to {
+            return Ok(());
+        }
+        self.move_all(vec![(from, to)])
+    }
+
+    /// Renames or moves the directory at `from` along with everything in it
+    /// to `to`. Nothing is moved if any file would replace an existing one.
+    pub fn rename_dir(&mut self, from: &str, to: &str) -> Result<(), PathError> {
+        let from = normalize_dir(from)?;
+        let to = normalize_dir(to)?;
+        if from == "/" || is_within(&to, &from) {
+            return Err(PathError::IntoItself(from));
+        }
+        let keys = self.keys_within(&from);
+        if keys.is_empty() {
+            return Err(PathError::NotFound(from));
+        }
+        if from == to {
+            return Ok(());
+        }
+        let prefix = if to == "/" { "" } else { to.as_str() };
+        let moves = keys
+            .into_iter()
+            .map(|key| {
+                let new_key = format!("{prefix}{}", &key[from.len()..]);
+                (key, new_key)
+            })
+            .collect();
+        self.move_all(moves)
+    }
+
+    /// Files as a tree of directories, with directories sorted by name and
+    /// files sorted by key
+    pub fn tree(&self) -> DirNode<'_> {
+        let mut root = DirNode {
+            name: "",
+            path: "/".to_owned(),
+            dirs: Vec::new(),
+            files: Vec::new(),
+        };
+        for (key, file) in self.iter() {
+            let mut node = &mut root;
+            for name in file.dir.split('/').filter(|name| !name.is_empty()) {
+                let index = match node.dirs.iter().position(|dir| dir.name == name) {
+                    Some(index) => index,
+                    None => {
+                        let path = match node.path.as_str() {
+                            "/" => format!("/{name}"),
+                            parent => format!("{parent}/{name}"),
+                        };
+                        node.dirs.push(DirNode {
+                            name,
+                            path,
+                            dirs: Vec::new(),
+                            files: Vec::new(),
+                        });
+                        node.dirs.len() - 1
+                    }
+                };
+                node = &mut node.dirs[index];
+            }
+            node.files.push((key, file));
+        }
+        root.sort();
+        root
+    }
+
+    fn keys_within(&self, dir: &str) -> Vec<String> {
+        self.map
+            .keys()
+            .filter(|key| is_within(key, dir))
+            .cloned()
+            .collect()
+    }
+
+    /// Fails if a file is at `key`, within a directory at `key`, or at any of
+    /// the directories leading to `key`, since a path can not be both a file
+    /// and a directory
+    fn check_free(&self, key: &str) -> Result<(), PathError> {
+        if self.map.contains_key(key) || self.map.keys().any(|other| is_within(other, key)) {
+            return Err(PathError::Exists(key.to_owned()));
+        }
+        for (index, _) in key.match_indices('/').skip(1) {
+            if self.map.contains_key(&key[..index]) {
+                return Err(PathError::Exists(key[..index].to_owned()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves every file from its old key to its new one, or none of them
+    fn move_all(&mut self, moves: Vec<(String, String)>) -> Result<(), PathError> {
+        let old: Vec<_> = moves
+            .iter()
+            .filter_map(|(from, _)| self.map.remove_entry(from))
+            .collect();
+        let mut moved = Vec::with_capacity(moves.len());
+        let mut result = Ok(());
+        for ((_, to), (_, file)) in moves.iter().zip(old.iter()) {
+            let file = self
+                .check_free(to)
+                .and_then(|_| File::at(to, file.data.clone(), file.fetch.clone()));
+            match file {
+                Ok(file) => {
+                    self.map.insert(to.clone(), file);
+                    moved.push(to);
+                }
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
+        }
+        if result.is_err() {
+            for key in moved {
+                self.map.remove(key);
+            }
+            self.map.extend(old);
+        }
+        result
+    }
+}
+
+/// Directory in `Files::tree`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirNode<'a> {
+    /// Name of directory, empty for the root
+    pub name: &'a str,
+    /// Normalized path of directory, `/` for the root
+    pub path: String,
+    pub dirs: Vec<DirNode<'a>>,
+    /// Files directly within this directory along with their keys
+    pub files: Vec<(&'a str, &'a File)>,
+}
+
+impl<'a> DirNode<'a> {
+    fn sort(&mut self) {
+        self.dirs.sort_by(|a, b| a.name.cmp(b.name));
+        self.dirs.iter_mut().for_each(DirNode::sort);
+    }
 }
 
 /// Encapsulates all data needed to emulate a file in
 /// gputoy virtual directory structure.
-#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
 pub struct File {
-    /// Contents of file in plain text
-    data: String,
-    /// File path starting at / (project root)
+    /// Contents of file, either text or binary
+    pub data: FileData,
+    /// Directory of file from project root, without leading
+    /// or trailing slash. Empty if file is at the root.
     dir: String,
     /// Name of file
     #[serde(rename = "fileName")]
@@ -38,10 +335,37 @@ pub struct File {
     /// Fetch url. If exists, then contents will be fetched
     /// from remote URL on project load
     #[serde(skip_serializing_if = "Option::is_none")]
-    fetch: Option<String>,
+    pub fetch: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+impl File {
+    /// File at normalized `path`
+    fn at(path: &str, data: FileData, fetch: Option<String>) -> Result<Self, PathError> {
+        let (dir, file_name, extension) = split_file(path)?;
+        Ok(Self {
+            data,
+            dir: dir.to_owned(),
+            file_name: file_name.to_owned(),
+            extension,
+            fetch,
+        })
+    }
+
+    pub fn dir(&self) -> &str {
+        &self.dir
+    }
+
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    pub fn extension(&self) -> SupportedExtension {
+        self.extension
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
 #[serde(rename_all = "lowercase")]
 pub enum SupportedExtension {
     Wgsl,
@@ -65,14 +389,220 @@ impl SupportedExtension {
     pub fn is_buffer(&self) -> bool {
         matches!(self, Self::Csv | Self::Png | Self::Jpeg | Self::Mp3)
     }
+
+    /// Returns true if file contents are binary, rather than text
+    pub fn is_binary(&self) -> bool {
+        matches!(self, Self::Png | Self::Jpeg | Self::Mp3)
+    }
+
+    /// Extension from a file name's extension, ignoring case
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        Some(match extension.to_ascii_lowercase().as_str() {
+            "wgsl" => Self::Wgsl,
+            "glsl" => Self::Glsl,
+            "txt" => Self::Txt,
+            "md" => Self::Md,
+            "json" => Self::Json,
+            "csv" => Self::Csv,
+            "png" => Self::Png,
+            "jpeg" | "jpg" => Self::Jpeg,
+            "mp3" => Self::Mp3,
+            _ => return None,
+        })
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::SupportedExtension;
+    use crate::PathError;
+
+    use super::{Files, SupportedExtension};
+
+    fn files(keys: &[&str]) -> Files {
+        let mut files = Files::new();
+        for key in keys {
+            files.create(key, key.to_string()).unwrap();
+        }
+        files
+    }
 
     #[test]
     fn test_extensions() {
-        assert_eq!(false, SupportedExtension::Csv.is_shader());
+        assert!(!SupportedExtension::Csv.is_shader());
+        assert!(SupportedExtension::Png.is_binary());
+        assert_eq!(
+            SupportedExtension::from_extension("JPG"),
+            Some(SupportedExtension::Jpeg)
+        );
+        assert_eq!(SupportedExtension::from_extension("rs"), None);
+    }
+
+    #[test]
+    fn test_create() {
+        let mut files = files(&["shaders//main.wgsl"]);
+        let file = files.get("/shaders/main.wgsl").unwrap();
+        assert_eq!(file.dir(), "shaders");
+        assert_eq!(file.file_name(), "main");
+        assert_eq!(file.extension(), SupportedExtension::Wgsl);
+        assert_eq!(files.keys().collect::<Vec<_>>(), ["/shaders/main.wgsl"]);
+
+        assert_eq!(
+            files
+                .create("/shaders/main.wgsl", String::new())
+                .unwrap_err(),
+            PathError::Exists("/shaders/main.wgsl".to_owned())
+        );
+        // A path can not be a file and a directory at once
+        assert!(files.create("/shaders", String::new()).is_err());
+        assert!(files
+            .create("/shaders/main.wgsl/a.wgsl", String::new())
+            .is_err());
+
+        files.get_mut("/shaders/main.wgsl").unwrap().fetch = Some("url".to_owned());
+        let file = files.write("/shaders/main.wgsl", "new".to_owned()).unwrap();
+        assert_eq!(file.data.as_text(), Some("new"));
+        assert_eq!(file.fetch.as_deref(), Some("url"));
+    }
+
+    #[test]
+    fn test_from_entries() {
+        let files = Files::from_entries([("shaders//main.wgsl", "a"), ("/shaders/main.wgsl", "b")])
+            .unwrap();
+        assert_eq!(files.len(), 1);
+        let file = files.get("/shaders/main.wgsl").unwrap();
+        assert_eq!(file.data.as_text(), Some("b"));
+        assert_eq!(file.dir(), "shaders");
+
+        assert!(matches!(
+            Files::from_entries([("/../main.wgsl", "a")]),
+            Err(PathError::Parent(_))
+        ));
+    }
+
+    #[test]
+    fn test_rename() {
+        let mut files = files(&["/a.wgsl", "/b.wgsl", "/shaders/c.wgsl"]);
+        files.rename("/a.wgsl", "/shaders/lib/d.glsl").unwrap();
+        let file = files.get("/shaders/lib/d.glsl").unwrap();
+        assert_eq!(file.data.as_text(), Some("/a.wgsl"));
+        assert_eq!(file.dir(), "shaders/lib");
+        assert_eq!(file.extension(), SupportedExtension::Glsl);
+        assert!(!files.contains("/a.wgsl"));
+
+        assert!(matches!(
+            files.rename("/b.wgsl", "/shaders/c.wgsl"),
+            Err(PathError::Exists(_))
+        ));
+        assert!(matches!(
+            files.rename("/b.wgsl", "/b.rs"),
+            Err(PathError::UnsupportedExtension(_))
+        ));
+        assert!(files.contains("/b.wgsl"));
+        assert!(matches!(
+            files.rename("/a.wgsl", "/e.wgsl"),
+            Err(PathError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_rename_dir() {
+        let mut files = files(&["/shaders/a.wgsl", "/shaders/lib/b.wgsl", "/lib/b.wgsl"]);
+        assert!(matches!(
+            files.rename_dir("/shaders", "/shaders/lib"),
+            Err(PathError::IntoItself(_))
+        ));
+        // Nothing is moved if any file is in the way
+        assert!(matches!(
+            files.rename_dir("/shaders", "/"),
+            Err(PathError::Exists(_))
+        ));
+        assert_eq!(files.len(), 3);
+        assert!(files.contains("/shaders/a.wgsl"));
+
+        files.rename_dir("/shaders/", "/src/shaders").unwrap();
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            [
+                "/lib/b.wgsl",
+                "/src/shaders/a.wgsl",
+                "/src/shaders/lib/b.wgsl"
+            ]
+        );
+        assert_eq!(
+            files.get("/src/shaders/lib/b.wgsl").unwrap().dir(),
+            "src/shaders/lib"
+        );
+
+        assert_eq!(
+            files.remove_dir("/src").unwrap(),
+            ["/src/shaders/a.wgsl", "/src/shaders/lib/b.wgsl"]
+        );
+        assert_eq!(files.len(), 1);
+    }
+
+    #[test]
+    fn test_tree() {
+        let files = files(&["/z.md", "/shaders/main.wgsl", "/a-b/c.wgsl", "/a/b/c.wgsl"]);
+        let tree = files.tree();
+        let names = |node: &super::DirNode| -> Vec<String> {
+            node.dirs.iter().map(|dir| dir.path.clone()).collect()
+        };
+        assert_eq!(names(&tree), ["/a", "/a-b", "/shaders"]);
+        assert_eq!(tree.files[0].0, "/z.md");
+        assert_eq!(names(&tree.dirs[0]), ["/a/b"]);
+        assert_eq!(tree.dirs[0].dirs[0].name, "b");
+        assert_eq!(tree.dirs[0].dirs[0].files[0].0, "/a/b/c.wgsl");
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let json = serde_json::json!({
+            "map": {
+                "shaders//main.wgsl": {
+                    "data": "",
+                    "dir": "stale",
+                    "fileName": "stale",
+                    "extension": "md",
+                },
+            }
+        });
+        let files: Files = serde_json::from_value(json).unwrap();
+        let file = files.get("/shaders/main.wgsl").unwrap();
+        assert_eq!(file.dir(), "shaders");
+        assert_eq!(file.file_name(), "main");
+        assert_eq!(file.extension(), SupportedExtension::Wgsl);
+
+        // Path fields are optional, and unknown extensions are ignored
+        let json = serde_json::json!({
+            "map": {
+                "/main.wgsl": { "data": "" },
+                "/shaders/common.wgsl": {
+                    "data": "",
+                    "extension": "hlsl",
+                    "fetch": "https://example.com/common.wgsl",
+                },
+            }
+        });
+        let files: Files = serde_json::from_value(json).unwrap();
+        assert_eq!(files.get("/main.wgsl").unwrap().file_name(), "main");
+        assert_eq!(
+            files.get("/shaders/common.wgsl").unwrap().fetch.as_deref(),
+            Some("https://example.com/common.wgsl")
+        );
+
+        let file = serde_json::json!({
+            "data": "",
+            "dir": "",
+            "fileName": "main",
+            "extension": "wgsl",
+        });
+        for map in [
+            serde_json::json!({ "/../main.wgsl": file }),
+            serde_json::json!({ "/main.wgsl": file, "main.wgsl": file }),
+            serde_json::json!({ "/main": file }),
+        ] {
+            let res = serde_json::from_value::<Files>(serde_json::json!({ "map": map }));
+            assert!(res.is_err(), "{map}");
+        }
     }
 }
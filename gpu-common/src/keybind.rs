@@ -0,0 +1,493 @@
+//! Keybinds, the keys of [`crate::UserConfig::keybinds`].
+//!
+//! ```text
+//! ctrl+s
+//! mod+shift+p
+//! ctrl+k ctrl+s
+//! ```
+//!
+//! A keybind is a sequence of chords separated by spaces, each chord being
+//! modifiers and a key joined by `+`. `mod` is ctrl, or cmd on macOS, so one
+//! binding works on every platform. The older `C-S-x` form, with `C`, `S`, `A`
+//! and `M` for ctrl, shift, alt and meta, is accepted as well.
+//!
+//! Keybinds are compared by their canonical form, in which modifiers come in
+//! the order `mod`, `ctrl`, `alt`, `shift`, `meta` and keys are lowercase.
+
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{Condition, FilteredAction};
+
+/// Keys that are named rather than written as the character they type, as in
+/// `KeyboardEvent.key` but lowercase
+const NAMED_KEYS: [&str; 15] = [
+    "escape",
+    "enter",
+    "tab",
+    "space",
+    "backspace",
+    "delete",
+    "insert",
+    "home",
+    "end",
+    "pageup",
+    "pagedown",
+    "arrowup",
+    "arrowdown",
+    "arrowleft",
+    "arrowright",
+];
+
+/// Other names accepted for keys, with the name they stand for
+const KEY_ALIASES: [(&str, &str); 8] = [
+    ("esc", "escape"),
+    ("return", "enter"),
+    ("del", "delete"),
+    ("up", "arrowup"),
+    ("down", "arrowdown"),
+    ("left", "arrowleft"),
+    ("right", "arrowright"),
+    ("plus", "+"),
+];
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum KeybindError {
+    #[error("Keybind is empty")]
+    Empty,
+    #[error("Unknown modifier `{0}`")]
+    UnknownModifier(String),
+    #[error("`{0}` appears twice in a chord")]
+    DuplicateModifier(Modifier),
+    #[error("Chord `{0}` has no key")]
+    MissingKey(String),
+    #[error("Unknown key `{0}`")]
+    UnknownKey(String),
+}
+
+/// Modifier held down in a chord, ordered as in the canonical form
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Modifier {
+    /// Ctrl, or cmd on macOS
+    Mod,
+    Ctrl,
+    Alt,
+    Shift,
+    /// Cmd on macOS, the windows key elsewhere
+    Meta,
+}
+
+impl Modifier {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_lowercase().as_str() {
+            "mod" => Self::Mod,
+            "ctrl" | "control" => Self::Ctrl,
+            "alt" | "option" | "opt" => Self::Alt,
+            "shift" => Self::Shift,
+            "meta" | "cmd" | "command" | "super" | "win" => Self::Meta,
+            _ => return None,
+        })
+    }
+}
+
+impl Display for Modifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Mod => "mod",
+            Self::Ctrl => "ctrl",
+            Self::Alt => "alt",
+            Self::Shift => "shift",
+            Self::Meta => "meta",
+        })
+    }
+}
+
+/// Platform a keybind is pressed on, which decides what `mod` is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub enum Platform {
+    Mac,
+    Other,
+}
+
+impl Platform {
+    pub const ALL: [Platform; 2] = [Platform::Mac, Platform::Other];
+}
+
+/// Modifiers and a key pressed together
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Chord {
+    pub modifiers: BTreeSet<Modifier>,
+    /// Lowercase key, either a single character or one of the named keys
+    pub key: String,
+}
+
+impl Chord {
+    fn parse(source: &str) -> Result<Self, KeybindError> {
+        if let Some(chord) = Self::parse_legacy(source) {
+            return chord;
+        }
+        // The `+` key is written `+` on its own or after a `+`, as in `ctrl++`
+        let (modifiers, key) = if source == "+" {
+            ("", "+")
+        } else if let Some(modifiers) = source.strip_suffix("++") {
+            (modifiers, "+")
+        } else {
+            source.rsplit_once('+').unwrap_or(("", source))
+        };
+
+        let mut chord = Chord {
+            modifiers: BTreeSet::new(),
+            key: parse_key(key).ok_or_else(|| match key {
+                "" => KeybindError::MissingKey(source.to_owned()),
+                _ => KeybindError::UnknownKey(key.to_owned()),
+            })?,
+        };
+        for name in modifiers.split('+').filter(|_| !modifiers.is_empty()) {
+            let modifier = Modifier::parse(name)
+                .ok_or_else(|| KeybindError::UnknownModifier(name.to_owned()))?;
+            if !chord.modifiers.insert(modifier) {
+                return Err(KeybindError::DuplicateModifier(modifier));
+            }
+        }
+        Ok(chord)
+    }
+
+    /// Parses chords like `C-S-x`, if the source is one
+    fn parse_legacy(source: &str) -> Option<Result<Self, KeybindError>> {
+        let mut modifiers = Vec::new();
+        let mut rest = source;
+        while let Some((prefix, key)) = rest.split_at_checked(2).filter(|(_, key)| !key.is_empty())
+        {
+            let modifier = match prefix {
+                "C-" => Modifier::Ctrl,
+                "S-" => Modifier::Shift,
+                "A-" => Modifier::Alt,
+                "M-" => Modifier::Meta,
+                _ => break,
+            };
+            modifiers.push(modifier);
+            rest = key;
+        }
+        if modifiers.is_empty() || (rest.contains('+') && rest != "+") {
+            return None;
+        }
+
+        let mut chord = Chord {
+            modifiers: BTreeSet::new(),
+            key: match parse_key(rest) {
+                Some(key) => key,
+                None => return Some(Err(KeybindError::UnknownKey(rest.to_owned()))),
+            },
+        };
+        for modifier in modifiers {
+            if !chord.modifiers.insert(modifier) {
+                return Some(Err(KeybindError::DuplicateModifier(modifier)));
+            }
+        }
+        Some(Ok(chord))
+    }
+
+    fn resolve(&self, platform: Platform) -> Self {
+        let mut chord = self.clone();
+        if chord.modifiers.remove(&Modifier::Mod) {
+            chord.modifiers.insert(match platform {
+                Platform::Mac => Modifier::Meta,
+                Platform::Other => Modifier::Ctrl,
+            });
+        }
+        chord
+    }
+}
+
+impl Display for Chord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for modifier in self.modifiers.iter() {
+            write!(f, "{modifier}+")?;
+        }
+        f.write_str(&self.key)
+    }
+}
+
+/// Lowercase name of a key, resolving aliases, or `None` if it is unknown
+fn parse_key(key: &str) -> Option<String> {
+    let key = key.to_lowercase();
+    let key = KEY_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == key)
+        .map_or(key.as_str(), |(_, name)| name);
+    let mut chars = key.chars();
+    let single = chars.next().is_some_and(|c| !c.is_whitespace()) && chars.next().is_none();
+    let function = key
+        .strip_prefix('f')
+        .and_then(|n| n.parse::<u8>().ok())
+        .is_some_and(|n| (1..=24).contains(&n) && !key.starts_with("f0"));
+    (single || function || NAMED_KEYS.contains(&key)).then(|| key.to_owned())
+}
+
+/// Sequence of chords pressed one after the other
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Keybind {
+    pub chords: Vec<Chord>,
+}
+
+impl Keybind {
+    pub fn parse(source: &str) -> Result<Self, KeybindError> {
+        let chords = source
+            .split_whitespace()
+            .map(Chord::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        if chords.is_empty() {
+            return Err(KeybindError::Empty);
+        }
+        Ok(Self { chords })
+    }
+
+    /// Parses `source` and writes it back in canonical form, i.e. `C-S-x`
+    /// becomes `ctrl+shift+x`
+    pub fn normalize(source: &str) -> Result<String, KeybindError> {
+        Ok(Self::parse(source)?.to_string())
+    }
+
+    /// The keybind as pressed on `platform`, with `mod` replaced
+    pub fn resolve(&self, platform: Platform) -> Self {
+        Self {
+            chords: self
+                .chords
+                .iter()
+                .map(|chord| chord.resolve(platform))
+                .collect(),
+        }
+    }
+
+    /// Whether pressing this keybind presses all of `other` or the other way
+    /// around, so that one of the two can never finish or is ambiguous
+    pub fn clashes(&self, other: &Keybind) -> bool {
+        self.chords.starts_with(&other.chords) || other.chords.starts_with(&self.chords)
+    }
+}
+
+impl Display for Keybind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, chord) in self.chords.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{chord}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Keybind {
+    type Err = KeybindError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Self::parse(source)
+    }
+}
+
+/// Two keybinds that trigger different actions on the same keys in some
+/// context, see [`find_conflicts`]
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+#[error("Keybind `{keybind}` conflicts with `{other}`{}", match .platform {
+    Some(Platform::Mac) => " on macOS",
+    Some(Platform::Other) => " outside of macOS",
+    None => "",
+})]
+pub struct KeybindConflict {
+    /// Keybind as written in the config
+    pub keybind: String,
+    /// Keybind it conflicts with, as written in the config
+    pub other: String,
+    /// Whether `other` starts with `keybind` or the other way around, rather
+    /// than the two being the same
+    pub prefix: bool,
+    /// Platform the two only conflict on, if they don't on every platform
+    pub platform: Option<Platform>,
+}
+
+/// Finds pairs of keybinds that clash on some platform, have overlapping
+/// conditions and trigger different actions. Keybinds or conditions that do
+/// not parse are skipped, they are reported by validation.
+pub fn find_conflicts<'a>(
+    keybinds: impl IntoIterator<Item = (&'a String, &'a FilteredAction)>,
+) -> Vec<KeybindConflict> {
+    let mut parsed = keybinds
+        .into_iter()
+        .filter_map(|(source, action)| {
+            let keybind = Keybind::parse(source).ok()?;
+            let condition = match action.condition {
+                Some(ref condition) => Some(Condition::parse(condition).ok()?),
+                None => None,
+            };
+            Some((source, keybind, condition, action))
+        })
+        .collect::<Vec<_>>();
+    parsed.sort_by_key(|(source, ..)| *source);
+
+    let mut conflicts = Vec::new();
+    for (index, (source, keybind, condition, action)) in parsed.iter().enumerate() {
+        for (other_source, other, other_condition, other_action) in &parsed[index + 1..] {
+            if action.action == other_action.action {
+                continue;
+            }
+            let platforms = Platform::ALL
+                .into_iter()
+                .filter(|platform| {
+                    keybind
+                        .resolve(*platform)
+                        .clashes(&other.resolve(*platform))
+                })
+                .collect::<Vec<_>>();
+            if platforms.is_empty() {
+                continue;
+            }
+            let overlaps = match (condition, other_condition) {
+                (Some(a), Some(b)) => a.overlaps(b),
+                _ => true,
+            };
+            if !overlaps {
+                continue;
+            }
+            let platform = platforms[0];
+            conflicts.push(KeybindConflict {
+                keybind: source.to_string(),
+                other: other_source.to_string(),
+                prefix: keybind.resolve(platform).chords.len()
+                    != other.resolve(platform).chords.len(),
+                platform: (platforms.len() == 1).then_some(platform),
+            });
+        }
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{Action, FilteredAction, Panel};
+
+    use super::{find_conflicts, Keybind, KeybindConflict, KeybindError, Modifier, Platform};
+
+    #[test]
+    fn test_normalize() {
+        let cases = [
+            ("ctrl+s", "ctrl+s"),
+            ("Shift+Ctrl+P", "ctrl+shift+p"),
+            ("mod+k  mod+s", "mod+k mod+s"),
+            ("cmd+option+Esc", "alt+meta+escape"),
+            ("ctrl++", "ctrl++"),
+            ("+", "+"),
+            ("ctrl+plus", "ctrl++"),
+            ("ctrl+-", "ctrl+-"),
+            ("ctrl+F12", "ctrl+f12"),
+            ("C-S-x", "ctrl+shift+x"),
+            ("C--", "ctrl+-"),
+            ("C-k C-s", "ctrl+k ctrl+s"),
+            ("C-up", "ctrl+arrowup"),
+            ("a", "a"),
+        ];
+        for (source, canonical) in cases {
+            assert_eq!(Keybind::normalize(source).unwrap(), canonical, "{source}");
+            assert_eq!(Keybind::normalize(canonical).unwrap(), canonical);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases = [
+            ("", KeybindError::Empty),
+            ("   ", KeybindError::Empty),
+            ("hyper+k", KeybindError::UnknownModifier("hyper".to_owned())),
+            (
+                "ctrl+shift+Ctrl+k",
+                KeybindError::DuplicateModifier(Modifier::Ctrl),
+            ),
+            ("C-C-k", KeybindError::DuplicateModifier(Modifier::Ctrl)),
+            ("ctrl+", KeybindError::MissingKey("ctrl+".to_owned())),
+            ("ctrl+foo", KeybindError::UnknownKey("foo".to_owned())),
+            ("ctrl+f25", KeybindError::UnknownKey("f25".to_owned())),
+            ("C-foo", KeybindError::UnknownKey("foo".to_owned())),
+        ];
+        for (source, err) in cases {
+            assert_eq!(Keybind::parse(source), Err(err), "{source}");
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let keybind = Keybind::parse("mod+k mod+shift+s").unwrap();
+        assert_eq!(
+            keybind.resolve(Platform::Mac).to_string(),
+            "meta+k shift+meta+s"
+        );
+        assert_eq!(
+            keybind.resolve(Platform::Other).to_string(),
+            "ctrl+k ctrl+shift+s"
+        );
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let bind = |action: Action, condition: Option<&str>| FilteredAction {
+            action,
+            condition: condition.map(str::to_owned),
+        };
+        let keybinds: HashMap<String, FilteredAction> = [
+            ("C-s", bind(Action::SaveAllFiles, None)),
+            ("ctrl+s", bind(Action::SaveCurrentFile, Some("editorFocus"))),
+            // Same action, not a conflict
+            ("ctrl+S", bind(Action::SaveAllFiles, None)),
+            ("mod+k", bind(Action::NextDocument, None)),
+            ("meta+k", bind(Action::PreviousDocument, None)),
+            ("ctrl+k ctrl+p", bind(Action::PlayPause, None)),
+            // Never applies when `mod+k` does
+            ("ctrl+p", bind(Action::Reset, Some("isPlaying"))),
+            ("mod+p", bind(Action::Rebuild, Some("!isPlaying"))),
+            (
+                "ctrl+q",
+                bind(Action::TogglePanel(Panel::EditorPanel), None),
+            ),
+            (
+                "alt+q",
+                bind(Action::TogglePanel(Panel::ProjectPanel), None),
+            ),
+            // Invalid conditions are left to validation
+            ("ctrl+x", bind(Action::Fork, Some("editorFocus &&"))),
+            ("ctrl+x ", bind(Action::Publish, None)),
+        ]
+        .into_iter()
+        .map(|(keybind, action)| (keybind.to_owned(), action))
+        .collect();
+
+        let conflict = |keybind: &str, other: &str, prefix, platform| KeybindConflict {
+            keybind: keybind.to_owned(),
+            other: other.to_owned(),
+            prefix,
+            platform,
+        };
+        assert_eq!(
+            find_conflicts(&keybinds),
+            [
+                conflict("C-s", "ctrl+s", false, None),
+                conflict("ctrl+S", "ctrl+s", false, None),
+                conflict("ctrl+k ctrl+p", "mod+k", true, Some(Platform::Other)),
+                conflict("meta+k", "mod+k", false, Some(Platform::Mac)),
+            ]
+        );
+        assert_eq!(
+            conflict("meta+k", "mod+k", false, Some(Platform::Mac)).to_string(),
+            "Keybind `meta+k` conflicts with `mod+k` on macOS"
+        );
+    }
+}
@@ -1,35 +1,408 @@
+//! Layout of the editor, a tree of splits with panels as its leaves.
+
 use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
+
+use crate::ShiftPaneArgs;
+
+/// Panels smaller than this are opened at their default size when toggled
+pub const MIN_PANEL_SIZE: f32 = 12.0;
 
-#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
 #[serde(rename_all = "camelCase")]
 pub struct Layout {
     /// Is the left side status panel open
-    is_status_open: bool,
-    /// List of file identifiers which is open in workspace.
-    /// Order of identifiers in vec is the order it is listed in the editor.
-    workspace: Vec<String>,
-    /// Currently opened file index within workspace
-    #[serde(rename = "fileIndex")]
-    workspace_file_index: Option<usize>,
-    /// Panel settings for projectPanel
-    project_panel: PanelState,
-    /// Panel settings for editorPanel
-    editor_panel: PanelState,
-    /// Panel settings for resourcePanel
-    resource_panel: PanelState,
-}
-
-#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+    pub is_status_open: bool,
+    /// Node taking up the whole editor
+    pub root: LayoutNode,
+}
+
+/// Direction a split lays out its children in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub enum SplitDirection {
+    /// Side by side, left to right
+    Horizontal,
+    /// Stacked, top to bottom
+    Vertical,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(tag = "ty", rename_all = "camelCase")]
+pub enum LayoutNode {
+    /// Nodes laid out next to each other
+    Split {
+        /// Percent of the parent split taken up
+        #[serde(deserialize_with = "size")]
+        size: f32,
+        direction: SplitDirection,
+        children: Vec<LayoutNode>,
+    },
+    /// Single panel
+    Panel {
+        /// Percent of the parent split taken up while shown
+        #[serde(deserialize_with = "size")]
+        size: f32,
+        show: bool,
+        state: PanelState,
+    },
+}
+
+/// Sizes are shares of their split, which can't be negative
+fn size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    let size = f32::deserialize(deserializer)?;
+    if size.is_finite() && size >= 0.0 {
+        Ok(size)
+    } else {
+        Err(D::Error::custom(format!("invalid size {size}")))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
 #[serde(rename_all = "camelCase")]
 pub enum Panel {
     EditorPanel,
     ProjectPanel,
     ResourcePanel,
+    ViewportPanel,
+}
+
+/// Kind of a panel along with whatever it keeps track of
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(tag = "ty", rename_all = "camelCase")]
+pub enum PanelState {
+    #[serde(rename_all = "camelCase")]
+    EditorPanel {
+        /// List of file identifiers which is open in workspace.
+        /// Order of identifiers in vec is the order it is listed in the editor.
+        workspace: Vec<String>,
+        /// Currently opened file index within workspace
+        #[cfg_attr(feature = "tsify", tsify(optional))]
+        file_index: Option<usize>,
+    },
+    ProjectPanel,
+    ResourcePanel,
+    ViewportPanel,
+}
+
+impl PanelState {
+    pub fn panel(&self) -> Panel {
+        match self {
+            Self::EditorPanel { .. } => Panel::EditorPanel,
+            Self::ProjectPanel => Panel::ProjectPanel,
+            Self::ResourcePanel => Panel::ResourcePanel,
+            Self::ViewportPanel => Panel::ViewportPanel,
+        }
+    }
+}
+
+impl LayoutNode {
+    pub fn split(direction: SplitDirection, size: f32, children: Vec<LayoutNode>) -> Self {
+        Self::Split {
+            size,
+            direction,
+            children,
+        }
+    }
+
+    /// Panel that is shown
+    pub fn panel(state: PanelState, size: f32) -> Self {
+        Self::Panel {
+            size,
+            show: true,
+            state,
+        }
+    }
+
+    pub fn size(&self) -> f32 {
+        match self {
+            Self::Split { size, .. } | Self::Panel { size, .. } => *size,
+        }
+    }
+
+    fn size_mut(&mut self) -> &mut f32 {
+        match self {
+            Self::Split { size, .. } | Self::Panel { size, .. } => size,
+        }
+    }
+
+    /// Whether any panel within the node is shown
+    pub fn is_shown(&self) -> bool {
+        match self {
+            Self::Split { children, .. } => children.iter().any(Self::is_shown),
+            Self::Panel { show, .. } => *show,
+        }
+    }
+
+    /// Indices of the children leading to the first panel of kind `panel`,
+    /// depth first
+    fn path_to(&self, panel: Panel) -> Option<Vec<usize>> {
+        match self {
+            Self::Split { children, .. } => children.iter().enumerate().find_map(|(i, child)| {
+                let mut path = child.path_to(panel)?;
+                path.insert(0, i);
+                Some(path)
+            }),
+            Self::Panel { state, .. } => (state.panel() == panel).then(Vec::new),
+        }
+    }
+
+    fn node_at_mut(&mut self, path: &[usize]) -> Option<&mut LayoutNode> {
+        path.iter().try_fold(self, |node, index| match node {
+            Self::Split { children, .. } => children.get_mut(*index),
+            Self::Panel { .. } => None,
+        })
+    }
+}
+
+impl Layout {
+    /// First panel of kind `panel`, depth first
+    pub fn find(&self, panel: Panel) -> Option<&LayoutNode> {
+        let mut node = &self.root;
+        for index in self.root.path_to(panel)? {
+            let LayoutNode::Split { children, .. } = node else {
+                return None;
+            };
+            node = &children[index];
+        }
+        Some(node)
+    }
+
+    pub fn find_mut(&mut self, panel: Panel) -> Option<&mut LayoutNode> {
+        let path = self.root.path_to(panel)?;
+        self.root.node_at_mut(&path)
+    }
+
+    /// Shows the first panel of kind `panel` if `show` is true, hides it if
+    /// false and flips it if unset. Panels too small to be seen are shown
+    /// rather than hidden, at `default_size`.
+    ///
+    /// Returns whether the panel is now shown, or `None` if there is no such panel.
+    pub fn toggle_panel(
+        &mut self,
+        panel: Panel,
+        show: Option<bool>,
+        default_size: f32,
+    ) -> Option<bool> {
+        let LayoutNode::Panel {
+            size, show: shown, ..
+        } = self.find_mut(panel)?
+        else {
+            return None;
+        };
+        let too_small = *size < MIN_PANEL_SIZE;
+        *shown = show.unwrap_or(!*shown || too_small);
+        if *shown && too_small {
+            *size = default_size;
+        }
+        Some(*shown)
+    }
+
+    /// Grows the pane by taking from the closest shown node in its split,
+    /// preferring the one after it. Panes alone in their split grow their
+    /// split instead. Sizes are kept between 0 and the sizes of both nodes.
+    ///
+    /// Returns whether anything was resized.
+    pub fn shift_panel(&mut self, args: &ShiftPaneArgs) -> bool {
+        let Some(mut path) = self.root.path_to(args.pane) else {
+            return false;
+        };
+        if !self
+            .root
+            .node_at_mut(&path)
+            .is_some_and(|node| node.is_shown())
+        {
+            return false;
+        }
+        while let Some(index) = path.pop() {
+            let Some(LayoutNode::Split { children, .. }) = self.root.node_at_mut(&path) else {
+                return false;
+            };
+            let sibling = (index + 1..children.len())
+                .chain((0..index).rev())
+                .find(|i| children[*i].is_shown());
+            if let Some(sibling) = sibling {
+                // Not clamp, which panics if a size is negative or NaN
+                let shift = (args.shift as f32)
+                    .max(-children[index].size())
+                    .min(children[sibling].size());
+                *children[index].size_mut() += shift;
+                *children[sibling].size_mut() -= shift;
+                return shift != 0.0;
+            }
+        }
+        false
+    }
+}
+
+impl Default for Layout {
+    /// Project panel on the left, the editor on the right and the viewport in
+    /// between, over the resource panel
+    fn default() -> Self {
+        use LayoutNode as Node;
+        use SplitDirection::*;
+        Self {
+            is_status_open: true,
+            root: Node::split(
+                Horizontal,
+                100.0,
+                vec![
+                    Node::panel(PanelState::ProjectPanel, 20.0),
+                    Node::split(
+                        Horizontal,
+                        80.0,
+                        vec![
+                            Node::split(
+                                Vertical,
+                                50.0,
+                                vec![
+                                    Node::panel(PanelState::ViewportPanel, 50.0),
+                                    Node::panel(PanelState::ResourcePanel, 50.0),
+                                ],
+                            ),
+                            Node::panel(
+                                PanelState::EditorPanel {
+                                    workspace: Vec::new(),
+                                    file_index: None,
+                                },
+                                50.0,
+                            ),
+                        ],
+                    ),
+                ],
+            ),
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, JsonSchema)]
-pub struct PanelState {
-    show: bool,
-    size: f32,
+#[cfg(test)]
+mod tests {
+    use crate::ShiftPaneArgs;
+
+    use super::{Layout, LayoutNode, Panel, PanelState};
+
+    fn size(layout: &Layout, panel: Panel) -> f32 {
+        layout.find(panel).unwrap().size()
+    }
+
+    fn children(node: &LayoutNode) -> &[LayoutNode] {
+        match node {
+            LayoutNode::Split { children, .. } => children,
+            LayoutNode::Panel { .. } => &[],
+        }
+    }
+
+    #[test]
+    fn test_find() {
+        let mut layout = Layout::default();
+        for panel in [
+            Panel::EditorPanel,
+            Panel::ProjectPanel,
+            Panel::ResourcePanel,
+            Panel::ViewportPanel,
+        ] {
+            let Some(LayoutNode::Panel { state, .. }) = layout.find(panel) else {
+                panic!("{panel:?} not found");
+            };
+            assert_eq!(state.panel(), panel);
+        }
+
+        if let Some(LayoutNode::Panel { state, .. }) = layout.find_mut(Panel::EditorPanel) {
+            *state = PanelState::EditorPanel {
+                workspace: vec!["/shaders/main.wgsl".to_owned()],
+                file_index: Some(0),
+            };
+        }
+        let json = serde_json::to_value(&layout).unwrap();
+        let editor = &json["root"]["children"][1]["children"][1];
+        assert_eq!(editor["ty"], "panel");
+        assert_eq!(editor["state"]["ty"], "editorPanel");
+        assert_eq!(editor["state"]["fileIndex"], 0);
+        assert_eq!(serde_json::from_value::<Layout>(json).unwrap(), layout);
+    }
+
+    #[test]
+    fn test_toggle_panel() {
+        let mut layout = Layout::default();
+        assert_eq!(
+            layout.toggle_panel(Panel::ProjectPanel, None, 30.0),
+            Some(false)
+        );
+        assert!(!layout.find(Panel::ProjectPanel).unwrap().is_shown());
+        assert_eq!(size(&layout, Panel::ProjectPanel), 20.0);
+        assert_eq!(
+            layout.toggle_panel(Panel::ProjectPanel, Some(true), 30.0),
+            Some(true)
+        );
+        assert_eq!(size(&layout, Panel::ProjectPanel), 20.0);
+
+        // Dragged until it could not be seen, so it is opened at the default size
+        if let Some(LayoutNode::Panel { size, .. }) = layout.find_mut(Panel::ResourcePanel) {
+            *size = 4.0;
+        }
+        assert_eq!(
+            layout.toggle_panel(Panel::ResourcePanel, None, 40.0),
+            Some(true)
+        );
+        assert_eq!(size(&layout, Panel::ResourcePanel), 40.0);
+
+        layout.root = LayoutNode::panel(PanelState::ViewportPanel, 100.0);
+        assert_eq!(layout.toggle_panel(Panel::EditorPanel, None, 50.0), None);
+    }
+
+    #[test]
+    fn test_shift_panel() {
+        let mut layout = Layout::default();
+        let shift = |pane, shift| ShiftPaneArgs { pane, shift };
+
+        assert!(layout.shift_panel(&shift(Panel::ProjectPanel, 10)));
+        assert_eq!(size(&layout, Panel::ProjectPanel), 30.0);
+        assert_eq!(children(&layout.root)[1].size(), 70.0);
+
+        // Last in its split, so it takes from the viewport before it
+        assert!(layout.shift_panel(&shift(Panel::ResourcePanel, -70)));
+        assert_eq!(size(&layout, Panel::ResourcePanel), 0.0);
+        assert_eq!(size(&layout, Panel::ViewportPanel), 100.0);
+        assert!(!layout.shift_panel(&shift(Panel::ResourcePanel, -10)));
+
+        // Hidden siblings are skipped, and alone in its split the viewport
+        // grows the split instead
+        layout.toggle_panel(Panel::ResourcePanel, Some(false), 50.0);
+        assert!(layout.shift_panel(&shift(Panel::ViewportPanel, 20)));
+        assert_eq!(size(&layout, Panel::ViewportPanel), 100.0);
+        assert_eq!(size(&layout, Panel::EditorPanel), 30.0);
+
+        // Hidden panels are left alone
+        assert!(!layout.shift_panel(&shift(Panel::ResourcePanel, 10)));
+    }
+
+    #[test]
+    fn test_invalid_size() {
+        let mut json = serde_json::to_value(Layout::default()).unwrap();
+        json["root"]["children"][0]["size"] = (-20.0).into();
+        let err = serde_json::from_value::<Layout>(json).unwrap_err();
+        assert!(err.to_string().contains("invalid size -20"), "{err}");
+
+        // Built in code rather than deserialized, so shifting must still not panic
+        let mut layout = Layout::default();
+        if let Some(LayoutNode::Panel { size, .. }) = layout.find_mut(Panel::ProjectPanel) {
+            *size = f32::NAN;
+        }
+        layout.shift_panel(&ShiftPaneArgs {
+            pane: Panel::ProjectPanel,
+            shift: 10,
+        });
+        if let Some(LayoutNode::Panel { size, .. }) = layout.find_mut(Panel::ProjectPanel) {
+            *size = -20.0;
+        }
+        layout.shift_panel(&ShiftPaneArgs {
+            pane: Panel::ProjectPanel,
+            shift: 10,
+        });
+    }
 }
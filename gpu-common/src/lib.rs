@@ -1,14 +1,38 @@
 mod actions;
+mod archive;
+mod capture;
+mod condition;
 mod config;
+mod content;
+mod disk;
+mod event;
 mod file;
+mod keybind;
 mod layout;
+mod migrate;
+mod path;
+mod profile;
 mod project;
 pub mod realm;
+mod shadertoy;
 mod user;
+mod worker;
 
 pub use actions::*;
+pub use archive::*;
+pub use capture::*;
+pub use condition::*;
 pub use config::*;
+pub use content::*;
+pub use disk::*;
+pub use event::*;
 pub use file::*;
+pub use keybind::*;
 pub use layout::*;
+pub use migrate::*;
+pub use path::*;
+pub use profile::*;
 pub use project::*;
+pub use shadertoy::*;
 pub use user::*;
+pub use worker::*;
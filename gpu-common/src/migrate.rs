@@ -0,0 +1,264 @@
+//! Upgrades projects saved by older releases to the current format.
+//!
+//! Serialized projects carry a `schemaVersion`, which is missing from projects
+//! saved before it existed. Migrations work on JSON rather than on the current
+//! types, so each step only has to know the format it starts from. Projects are
+//! upgraded one version at a time until they reach [`SCHEMA_VERSION`].
+//!
+//! Any object with `files`, `layout` and `config` fields can be migrated, which
+//! covers `Project`, `ProjectResponse` and `ProjectUpsert`.
+//!
+//! The frontend mirrors these steps in `front/src/lib/core/migrate.ts`.
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+use thiserror::Error;
+
+use crate::SupportedExtension;
+
+/// Version of the project format written by this release
+pub const SCHEMA_VERSION: u32 = 2;
+
+#[derive(Error, Debug)]
+pub enum MigrationError {
+    #[error("Project is not a json object")]
+    NotAnObject,
+    #[error("Invalid schema version: {0}")]
+    InvalidVersion(Value),
+    #[error("Project was saved by a newer release (schema version {0})")]
+    TooNew(u64),
+    #[error("Could not migrate project to schema version {0}: {1}")]
+    Step(u32, String),
+    #[error("Invalid project: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Step that upgrades a project from the version at its index to the next one
+type Migration = fn(&mut Map<String, Value>) -> Result<(), String>;
+
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] =
+    [v0_leading_slash_and_bytes, v1_split_tree_layout];
+
+/// Upgrades `project` in place to [`SCHEMA_VERSION`]
+pub fn migrate_project(project: &mut Value) -> Result<(), MigrationError> {
+    let object = project.as_object_mut().ok_or(MigrationError::NotAnObject)?;
+    let version = match object.get("schemaVersion") {
+        None | Some(Value::Null) => 0,
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| MigrationError::InvalidVersion(version.clone()))?,
+    };
+    if version > SCHEMA_VERSION as u64 {
+        return Err(MigrationError::TooNew(version));
+    }
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(object).map_err(|message| MigrationError::Step(from as u32 + 1, message))?;
+    }
+    object.insert("schemaVersion".to_owned(), SCHEMA_VERSION.into());
+    Ok(())
+}
+
+/// Deserializes a project of any version, migrating it first
+pub fn load_project<T: DeserializeOwned>(mut project: Value) -> Result<T, MigrationError> {
+    migrate_project(&mut project)?;
+    Ok(serde_json::from_value(project)?)
+}
+
+/// Keys of files started with a `/` only by convention, and binary files held
+/// their bytes as a base64 string rather than in a `{ "base64": ... }` object.
+/// Files open in the layout are referred to by key, so they get a `/` too.
+fn v0_leading_slash_and_bytes(project: &mut Map<String, Value>) -> Result<(), String> {
+    let map = match project
+        .get_mut("files")
+        .and_then(|files| files.get_mut("map"))
+    {
+        Some(Value::Object(map)) => map,
+        _ => return Err("files has no map".to_owned()),
+    };
+    for (key, mut file) in std::mem::take(map) {
+        let binary = file
+            .get("extension")
+            .and_then(Value::as_str)
+            .and_then(SupportedExtension::from_extension)
+            .is_some_and(|extension| extension.is_binary());
+        if let Some(data) = file
+            .get_mut("data")
+            .filter(|data| data.is_string() && binary)
+        {
+            *data = serde_json::json!({ "base64": data.take() });
+        }
+        map.insert(leading_slash(key), file);
+    }
+    let workspace = project
+        .get_mut("layout")
+        .and_then(|layout| layout.get_mut("workspace"))
+        .and_then(Value::as_array_mut);
+    for key in workspace.into_iter().flatten() {
+        if let Value::String(ref mut key) = key {
+            *key = leading_slash(std::mem::take(key));
+        }
+    }
+    Ok(())
+}
+
+/// Layouts had a fixed set of panels, each with its own field, rather than a
+/// tree of splits. The tree built mirrors how the editor laid those out.
+///
+/// Layouts that already are a tree are kept, as manifests of project
+/// directories used to be written without a schema version.
+fn v1_split_tree_layout(project: &mut Map<String, Value>) -> Result<(), String> {
+    let Some(Value::Object(old)) = project.get("layout").cloned() else {
+        return Ok(());
+    };
+    if old.contains_key("root") {
+        return Ok(());
+    }
+    let panel = |ty: &str, default_size: f64, mut state: Value| {
+        let panel = old.get(ty).unwrap_or(&Value::Null);
+        let size = panel["size"].as_f64().unwrap_or(default_size);
+        let show = panel["show"].as_bool().unwrap_or(true);
+        state["ty"] = ty.into();
+        let node = json!({ "ty": "panel", "size": size, "show": show, "state": state });
+        (node, size)
+    };
+    let split = |size: f64, direction: &str, children: [Value; 2]| json!({ "ty": "split", "size": size, "direction": direction, "children": children });
+    let (project_panel, project_size) = panel("projectPanel", 20.0, json!({}));
+    let (resource, resource_size) = panel("resourcePanel", 50.0, json!({}));
+    let (viewport, _) = panel("viewportPanel", 100.0 - resource_size, json!({}));
+    let (editor, editor_size) = panel(
+        "editorPanel",
+        50.0,
+        json!({
+            "workspace": old.get("workspace").cloned().unwrap_or_else(|| json!([])),
+            "fileIndex": old.get("fileIndex").cloned().unwrap_or(Value::Null),
+        }),
+    );
+    let main = split(
+        100.0 - project_size,
+        "horizontal",
+        [
+            split(100.0 - editor_size, "vertical", [viewport, resource]),
+            editor,
+        ],
+    );
+    let layout = json!({
+        "isStatusOpen": old.get("isStatusOpen").cloned().unwrap_or(true.into()),
+        "root": split(100.0, "horizontal", [project_panel, main]),
+    });
+    project.insert("layout".to_owned(), layout);
+    Ok(())
+}
+
+fn leading_slash(key: String) -> String {
+    if key.starts_with('/') {
+        key
+    } else {
+        format!("/{key}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::realm::ProjectResponse;
+    use crate::{FileData, LayoutNode, Panel, PanelState, Project};
+
+    use super::{load_project, migrate_project, MigrationError, SCHEMA_VERSION};
+
+    fn fixture(name: &str) -> Value {
+        let path = format!("{}/fixtures/{name}.json", env!("CARGO_MANIFEST_DIR"));
+        let json = std::fs::read_to_string(&path).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_fixtures() {
+        let current: Project =
+            load_project(fixture(&format!("project-v{SCHEMA_VERSION}"))).unwrap();
+        let current = serde_json::to_value(current).unwrap();
+        for version in 0..=SCHEMA_VERSION {
+            let mut project = fixture(&format!("project-v{version}"));
+            migrate_project(&mut project).unwrap();
+            // Migrating twice changes nothing
+            let migrated = project.clone();
+            migrate_project(&mut project).unwrap();
+            assert_eq!(project, migrated, "v{version}");
+            // Compared once deserialized, as older configs lack fields with defaults
+            let project: Project = serde_json::from_value(project).unwrap();
+            assert_eq!(
+                serde_json::to_value(project).unwrap(),
+                current,
+                "v{version}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_v0() {
+        let project: Project = load_project(fixture("project-v0")).unwrap();
+        assert_eq!(project.schema_version, SCHEMA_VERSION);
+        let noise = project.files.get("/textures/noise.png").unwrap();
+        assert_eq!(noise.data, FileData::from(vec![0, 159, 146]));
+        assert!(project.files.contains("/shaders/main.wgsl"));
+        // Saved before there was a render scale
+        assert_eq!(project.config.unwrap().render_scale, 1.0);
+
+        // Saved by the frontend, with the rest of the project response
+        let mut response = fixture("project-v0");
+        response["id"] = "toy".into();
+        response["title"] = "Toy".into();
+        response["published"] = false.into();
+        response["createdAt"] = "2022-10-01T12:00:00".into();
+        response["updatedAt"] = "2022-10-01T12:00:00".into();
+        let response: ProjectResponse = load_project(response).unwrap();
+        assert_eq!(response.schema_version, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_v1() {
+        let mut project = fixture("project-v1");
+        project["layout"] = Value::Null;
+        let project: Project = load_project(project).unwrap();
+        assert!(project.layout.is_none());
+
+        // Hidden panels stay hidden, and the editor keeps its documents
+        let mut project = fixture("project-v1");
+        project["layout"]["projectPanel"]["show"] = false.into();
+        let project: Project = load_project(project).unwrap();
+        let layout = project.layout.unwrap();
+        assert!(!layout.find(Panel::ProjectPanel).unwrap().is_shown());
+        let Some(LayoutNode::Panel { state, .. }) = layout.find(Panel::EditorPanel) else {
+            panic!("no editor panel");
+        };
+        assert_eq!(
+            *state,
+            PanelState::EditorPanel {
+                workspace: vec!["/shaders/main.wgsl".to_owned(), "/Readme.md".to_owned()],
+                file_index: Some(0),
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut newer = fixture(&format!("project-v{SCHEMA_VERSION}"));
+        newer["schemaVersion"] = (SCHEMA_VERSION + 1).into();
+        assert!(matches!(
+            migrate_project(&mut newer),
+            Err(MigrationError::TooNew(_))
+        ));
+        assert!(matches!(
+            migrate_project(&mut serde_json::json!({ "schemaVersion": "1" })),
+            Err(MigrationError::InvalidVersion(_))
+        ));
+        assert!(matches!(
+            migrate_project(&mut serde_json::json!([])),
+            Err(MigrationError::NotAnObject)
+        ));
+        assert!(matches!(
+            migrate_project(&mut serde_json::json!({})),
+            Err(MigrationError::Step(1, _))
+        ));
+    }
+}
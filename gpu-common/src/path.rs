@@ -0,0 +1,150 @@
+//! Paths within a project's virtual directory.
+//!
+//! Paths are normalized before use, so every file has exactly one key:
+//! segments are joined by a single `/`, the path starts with `/` and never ends
+//! with one, `.` segments are dropped and `..` segments are rejected.
+
+use thiserror::Error;
+
+use crate::SupportedExtension;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PathError {
+    #[error("Path is empty")]
+    Empty,
+    #[error("`{0}` leaves its directory with `..`")]
+    Parent(String),
+    #[error("`{0}` contains a backslash or nul character")]
+    InvalidCharacter(String),
+    #[error("`{0}` has no file name or extension")]
+    InvalidName(String),
+    #[error("`{0}` does not have a supported extension")]
+    UnsupportedExtension(String),
+    #[error("`{0}` already exists")]
+    Exists(String),
+    #[error("`{0}` does not exist")]
+    NotFound(String),
+    #[error("Can not move `{0}` into itself")]
+    IntoItself(String),
+}
+
+/// Normalizes the path of a directory, the root being `/`
+pub fn normalize_dir(path: &str) -> Result<String, PathError> {
+    if path.contains(['\\', '\0']) {
+        return Err(PathError::InvalidCharacter(path.to_owned()));
+    }
+    let mut normalized = String::with_capacity(path.len() + 1);
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => return Err(PathError::Parent(path.to_owned())),
+            segment => {
+                normalized.push('/');
+                normalized.push_str(segment);
+            }
+        }
+    }
+    if normalized.is_empty() {
+        normalized.push('/');
+    }
+    Ok(normalized)
+}
+
+/// Normalizes the path of a file, which is its key in `Files`
+pub fn normalize_file(path: &str) -> Result<String, PathError> {
+    let normalized = normalize_dir(path)?;
+    if normalized == "/" {
+        return Err(PathError::Empty);
+    }
+    Ok(normalized)
+}
+
+/// Directory, file name and extension of the file at normalized `path`. The
+/// directory has no leading or trailing `/`, and is empty for the root.
+pub fn split_file(path: &str) -> Result<(&str, &str, SupportedExtension), PathError> {
+    let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+    let (file_name, extension) = match name.rsplit_once('.') {
+        Some((file_name, extension)) if !file_name.is_empty() && !extension.is_empty() => {
+            (file_name, extension)
+        }
+        _ => return Err(PathError::InvalidName(path.to_owned())),
+    };
+    let extension = SupportedExtension::from_extension(extension)
+        .ok_or_else(|| PathError::UnsupportedExtension(path.to_owned()))?;
+    Ok((dir.trim_start_matches('/'), file_name, extension))
+}
+
+/// Whether normalized `path` is within normalized directory `dir`
+pub fn is_within(path: &str, dir: &str) -> bool {
+    dir == "/"
+        || path
+            .strip_prefix(dir)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::SupportedExtension;
+
+    use super::{is_within, normalize_dir, normalize_file, split_file, PathError};
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize_file("shaders/main.wgsl").unwrap(),
+            "/shaders/main.wgsl"
+        );
+        assert_eq!(
+            normalize_file("//shaders/./main.wgsl").unwrap(),
+            "/shaders/main.wgsl"
+        );
+        assert_eq!(normalize_dir("/shaders/").unwrap(), "/shaders");
+        assert_eq!(normalize_dir("").unwrap(), "/");
+        assert_eq!(normalize_dir("./").unwrap(), "/");
+        assert_eq!(normalize_file("/"), Err(PathError::Empty));
+        assert!(matches!(
+            normalize_file("/a/../b.wgsl"),
+            Err(PathError::Parent(_))
+        ));
+        assert!(matches!(
+            normalize_file("a\\b.wgsl"),
+            Err(PathError::InvalidCharacter(_))
+        ));
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(
+            split_file("/shaders/extras/noise.wgsl").unwrap(),
+            ("shaders/extras", "noise", SupportedExtension::Wgsl)
+        );
+        assert_eq!(
+            split_file("/Readme.MD").unwrap(),
+            ("", "Readme", SupportedExtension::Md)
+        );
+        assert_eq!(
+            split_file("/noise.tar.png").unwrap(),
+            ("", "noise.tar", SupportedExtension::Png)
+        );
+        assert!(matches!(
+            split_file("/.wgsl"),
+            Err(PathError::InvalidName(_))
+        ));
+        assert!(matches!(
+            split_file("/main"),
+            Err(PathError::InvalidName(_))
+        ));
+        assert!(matches!(
+            split_file("/main.rs"),
+            Err(PathError::UnsupportedExtension(_))
+        ));
+    }
+
+    #[test]
+    fn test_within() {
+        assert!(is_within("/shaders/main.wgsl", "/shaders"));
+        assert!(is_within("/shaders/main.wgsl", "/"));
+        assert!(!is_within("/shaders2/main.wgsl", "/shaders"));
+        assert!(!is_within("/shaders", "/shaders"));
+    }
+}
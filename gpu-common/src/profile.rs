@@ -0,0 +1,52 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Timing statistics over the profiler's rolling history, in milliseconds
+#[derive(Debug, Default, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+pub struct TimingStats {
+    pub min: f64,
+    pub avg: f64,
+    pub p95: f64,
+}
+
+/// How pass timings were measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub enum TimingSource {
+    /// Gpu timestamp queries, measures actual execution time on the gpu
+    Gpu,
+    /// Cpu timer around command recording, used when timestamp queries are unavailable
+    Cpu,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct PassTiming {
+    /// Shader file of pass
+    pub file: String,
+    /// Entry point of pass
+    pub entry_point: String,
+    pub stats: TimingStats,
+}
+
+/// Snapshot of frame and pass timings since profiling was enabled or the
+/// project was last built
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileSnapshot {
+    pub source: TimingSource,
+    /// Time between consecutive frames
+    pub frame: TimingStats,
+    /// Per pass timings, in the order passes are run
+    pub passes: Vec<PassTiming>,
+    /// Frames rendered
+    pub frames: u64,
+    /// Frames skipped because no surface texture could be acquired
+    pub dropped_frames: u64,
+    /// Frames that took more than 1.5x the frame budget
+    pub late_frames: u64,
+}
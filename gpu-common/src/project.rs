@@ -1,10 +1,35 @@
-use crate::{Config, Files, Layout};
+use crate::{Config, Files, Layout, SCHEMA_VERSION};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
 pub struct Project {
+    /// Version of the project format. Projects are migrated to the current
+    /// version when loaded, see `load_project`.
+    #[serde(rename = "schemaVersion", default = "current_schema_version")]
+    #[cfg_attr(feature = "tsify", tsify(optional))]
+    pub schema_version: u32,
     pub files: Files,
+    #[cfg_attr(feature = "tsify", tsify(optional))]
     pub layout: Option<Layout>,
+    #[cfg_attr(feature = "tsify", tsify(optional))]
     pub config: Option<Config>,
 }
+
+/// Anything deserialized without being migrated is expected to be current
+pub(crate) fn current_schema_version() -> u32 {
+    SCHEMA_VERSION
+}
+
+/// Project of only `files`, without a layout or config
+impl From<Files> for Project {
+    fn from(files: Files) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            files,
+            layout: None,
+            config: None,
+        }
+    }
+}
@@ -5,7 +5,8 @@ use validator_derive::Validate;
 
 use crate::{Config, Files, Layout};
 
-#[derive(Debug, Deserialize, Validate, JsonSchema)]
+#[derive(Debug, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct ProjectUpsert {
     pub id: Option<String>,
     #[validate(length(min = 3, max = 50))]
@@ -15,11 +16,18 @@ pub struct ProjectUpsert {
     pub layout: Option<Layout>,
     pub config: Option<Config>,
     pub published: bool,
+    /// Revision of the project the update was made on. When given, the update is
+    /// rejected if the project was updated since.
+    #[serde(default)]
+    pub updated_at: Option<NaiveDateTime>,
 }
 
-#[derive(Debug, Serialize, JsonSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ProjectResponse {
+    /// Version of the project format, see `Project::schema_version`
+    #[serde(default = "crate::current_schema_version")]
+    pub schema_version: u32,
     pub id: String,
     pub title: String,
     pub description: Option<String>,
@@ -1,4 +1,5 @@
 use crate::user::UserConfig;
+use crate::KeybindConflict;
 use chrono::NaiveDateTime;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -44,6 +45,10 @@ pub struct UserInfoResponse {
     pub active: bool,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    /// Keybinds of the saved config that conflict. They are saved anyway, as
+    /// the user may be halfway through rebinding them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<KeybindConflict>,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
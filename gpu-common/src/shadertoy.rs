@@ -0,0 +1,493 @@
+//! Imports shaders exported from Shadertoy as json.
+//!
+//! The Image tab becomes `/shaders/image.glsl`. Glsl has no includes, so the
+//! Common tab is kept in `/shaders/common.txt` and copied into it. Uniforms like `iTime` are defined in terms of the
+//! `system` uniform, see `gpu_client::resource::SystemUniforms`.
+//!
+//! Passes in gputoy can only bind buffers, so channel inputs have no
+//! counterpart yet. Channels read as black and are listed in the report of the
+//! import, as are Buffer tabs, whose output no other pass could read.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::{FileData, Files, PathError, Project, SCHEMA_VERSION};
+
+/// File holding the code of the Common tab
+const COMMON_FILE: &str = "/shaders/common.txt";
+
+/// Shadertoy uniforms that have no counterpart in gputoy
+const UNSUPPORTED_UNIFORMS: [&str; 6] = [
+    "iTimeDelta",
+    "iFrameRate",
+    "iChannelTime",
+    "iChannelResolution",
+    "iDate",
+    "iSampleRate",
+];
+
+#[derive(Error, Debug)]
+pub enum ShadertoyError {
+    #[error("Invalid Shadertoy export: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Shader `{0}` has no Image pass")]
+    NoImage(String),
+    #[error(transparent)]
+    Path(#[from] PathError),
+}
+
+/// Project created from a Shadertoy shader
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct ShadertoyImport {
+    pub title: String,
+    pub description: Option<String>,
+    /// Username of the author on Shadertoy
+    pub author: Option<String>,
+    pub project: Project,
+    /// Everything that could not be carried over
+    pub report: Vec<Unmapped>,
+}
+
+/// Part of a Shadertoy shader that was left out of the import
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct Unmapped {
+    /// Name of the tab, i.e. `Buffer A`
+    pub pass: String,
+    /// Set if a channel input could not be mapped
+    pub channel: Option<u8>,
+    pub message: String,
+}
+
+/// Imports every shader in `json`, which is either a single shader as served
+/// by the Shadertoy api, or the list of shaders Shadertoy exports
+pub fn import_shadertoy(json: &str) -> Result<Vec<ShadertoyImport>, ShadertoyError> {
+    let shaders = match serde_json::from_str(json)? {
+        Value::Array(shaders) => shaders,
+        shader => vec![shader],
+    };
+    shaders
+        .into_iter()
+        .map(|mut shader| {
+            if let Some(inner) = shader.get_mut("Shader") {
+                shader = inner.take();
+            }
+            import_shader(serde_json::from_value(shader)?)
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct Shader {
+    info: Info,
+    renderpass: Vec<RenderPass>,
+}
+
+#[derive(Deserialize)]
+struct Info {
+    #[serde(default)]
+    id: String,
+    name: String,
+    #[serde(default)]
+    username: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RenderPass {
+    #[serde(default)]
+    inputs: Vec<Input>,
+    #[serde(default)]
+    outputs: Vec<Output>,
+    code: String,
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Deserialize)]
+struct Input {
+    #[serde(default)]
+    id: Value,
+    /// Older exports call it `src`
+    #[serde(default, alias = "src")]
+    filepath: String,
+    ctype: String,
+    channel: u8,
+}
+
+#[derive(Deserialize)]
+struct Output {
+    id: Value,
+}
+
+fn import_shader(shader: Shader) -> Result<ShadertoyImport, ShadertoyError> {
+    let Shader { info, renderpass } = shader;
+    let mut report = Vec::new();
+
+    let common = renderpass
+        .iter()
+        .find(|pass| pass.kind == "common")
+        .map(|pass| pass.code.as_str());
+    let image = match renderpass.iter().find(|pass| pass.kind == "image") {
+        Some(image) => image,
+        None => return Err(ShadertoyError::NoImage(info.name)),
+    };
+    // Names of the buffer tabs by the id of their output, for inputs to refer to
+    let mut buffers = BTreeMap::new();
+    for pass in renderpass.iter() {
+        let message = match pass.kind.as_str() {
+            "image" | "common" => continue,
+            "buffer" => {
+                for output in pass.outputs.iter() {
+                    buffers.insert(output.id.to_string(), pass.name.as_str());
+                }
+                "Buffer passes are not supported, as no pass could read their output".to_owned()
+            }
+            "sound" => "Sound passes are not supported".to_owned(),
+            "cubemap" => "Cubemap passes are not supported".to_owned(),
+            kind => format!("Unknown pass type `{kind}`"),
+        };
+        report.push(unmapped(&pass.name, None, message));
+    }
+
+    let mut inputs = image.inputs.iter().collect::<Vec<_>>();
+    inputs.sort_by_key(|input| input.channel);
+    for input in inputs {
+        let message = match input.ctype.as_str() {
+            "buffer" => match buffer_name(input, &buffers) {
+                Some(name) => format!("Reads the output of `{name}`, which is not imported"),
+                None => "Reads a buffer that is not part of the shader".to_owned(),
+            },
+            "keyboard" => "Keyboard input is not supported".to_owned(),
+            "texture" => format!("Texture `{}` is not supported", input.filepath),
+            ctype => format!("Inputs of type `{ctype}` are not supported"),
+        };
+        report.push(unmapped(&image.name, Some(input.channel), message));
+    }
+
+    for code in [Some(image.code.as_str()), common].into_iter().flatten() {
+        for uniform in UNSUPPORTED_UNIFORMS {
+            if uses(code, uniform) && !uses_reported(&report, uniform) {
+                let message = format!("`{uniform}` has no counterpart in gputoy");
+                report.push(unmapped(&image.name, None, message));
+            }
+        }
+    }
+
+    let mut files = Files::new();
+    let file = format!("/shaders/{}.glsl", identifier(&image.name));
+    files.create(&file, glsl(image, common))?;
+    if let Some(common) = common {
+        files.create(COMMON_FILE, common)?;
+    }
+    if !info.id.is_empty() {
+        let author = info.username.as_deref().unwrap_or("unknown");
+        let readme = format!(
+            "# {}\n\nImported from https://www.shadertoy.com/view/{} by {author}.\n",
+            info.name, info.id
+        );
+        files.create("/Readme.md", readme)?;
+    }
+
+    Ok(ShadertoyImport {
+        title: info.name,
+        description: info
+            .description
+            .filter(|description| !description.is_empty()),
+        author: info.username,
+        project: Project {
+            schema_version: SCHEMA_VERSION,
+            files,
+            layout: None,
+            config: None,
+        },
+        report,
+    })
+}
+
+fn unmapped(pass: &str, channel: Option<u8>, message: String) -> Unmapped {
+    Unmapped {
+        pass: pass.to_owned(),
+        channel,
+        message,
+    }
+}
+
+/// Name of the buffer tab whose output `input` reads. Older exports only name
+/// the buffer in the path, i.e. `/media/previz/buffer00.png` for Buffer A.
+fn buffer_name<'a>(input: &Input, buffers: &BTreeMap<String, &'a str>) -> Option<&'a str> {
+    if let Some(name) = buffers.get(&input.id.to_string()) {
+        return Some(name);
+    }
+    let index = input
+        .filepath
+        .strip_prefix("/media/previz/buffer0")?
+        .strip_suffix(".png")?
+        .parse::<u8>()
+        .ok()
+        .filter(|index| *index < 4)?;
+    let name = format!("Buffer {}", (b'A' + index) as char);
+    buffers.values().copied().find(|buffer| *buffer == name)
+}
+
+/// Lowercase identifier from a name, i.e. `buffer_a` from `Buffer A`
+fn identifier(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Whether `code` mentions the identifier `name`
+fn uses(code: &str, name: &str) -> bool {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    code.match_indices(name).any(|(start, _)| {
+        let before = code[..start].chars().next_back();
+        let after = code[start + name.len()..].chars().next();
+        !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+    })
+}
+
+/// Unsupported uniforms are reported once, for the first pass using them
+fn uses_reported(report: &[Unmapped], uniform: &str) -> bool {
+    report
+        .iter()
+        .any(|unmapped| unmapped.message.starts_with(&format!("`{uniform}`")))
+}
+
+/// Glsl of a pass, calling its `mainImage` with coordinates from the bottom
+/// left like Shadertoy does
+fn glsl(pass: &RenderPass, common: Option<&str>) -> FileData {
+    let mut source = String::from(
+        r#"#version 450
+// Imported from Shadertoy, see the import report for what was left out
+
+layout(location = 0) out vec4 gputoy_color;
+
+layout(set = 0, binding = 0) uniform System {
+    float time;
+    uint frame;
+    vec2 resolution;
+    vec2 mouse;
+    vec2 offset;
+} system;
+
+#define iTime system.time
+#define iFrame int(system.frame)
+#define iResolution vec3(system.resolution, 1.0)
+#define iMouse vec4(system.mouse.x, system.resolution.y - system.mouse.y, 0.0, 0.0)
+"#,
+    );
+    let code = [Some(pass.code.as_str()), common].into_iter().flatten();
+    if code.clone().any(|code| code.contains("iChannel")) {
+        source.push_str(
+            r#"
+// Channels can not be bound, so every read returns black
+#define iChannel0 0
+#define iChannel1 1
+#define iChannel2 2
+#define iChannel3 3
+vec4 texture(int channel, vec2 uv) { return vec4(0.0); }
+vec4 texture(int channel, vec2 uv, float bias) { return vec4(0.0); }
+vec4 textureLod(int channel, vec2 uv, float lod) { return vec4(0.0); }
+vec4 texelFetch(int channel, ivec2 texel, int lod) { return vec4(0.0); }
+ivec2 textureSize(int channel, int lod) { return ivec2(1); }
+"#,
+        );
+    }
+    if let Some(common) = common {
+        let _ = write!(
+            source,
+            "\n// Common, copied from {COMMON_FILE}\n{}\n",
+            common.trim_end()
+        );
+    }
+    let _ = write!(
+        source,
+        r#"
+// {}
+{}
+
+void main() {{
+    vec2 coord = gl_FragCoord.xy + system.offset;
+    mainImage(gputoy_color, vec2(coord.x, system.resolution.y - coord.y));
+}}
+"#,
+        pass.name,
+        pass.code.trim_end()
+    );
+    source.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{import_shadertoy, uses, ShadertoyError};
+
+    fn export() -> serde_json::Value {
+        serde_json::json!({
+            "Shader": {
+                "info": {
+                    "id": "abcd12",
+                    "name": "Feedback",
+                    "username": "someone",
+                    "description": "Buffer feedback"
+                },
+                "renderpass": [
+                    {
+                        "inputs": [
+                            {
+                                "id": "4dXGR8",
+                                "filepath": "/media/previz/buffer00.png",
+                                "ctype": "buffer",
+                                "channel": 0,
+                                "sampler": { "filter": "linear", "wrap": "clamp", "vflip": "true", "srgb": "false" }
+                            },
+                            {
+                                "id": "4dXGRr",
+                                "filepath": "/presets/tex00.jpg",
+                                "ctype": "keyboard",
+                                "channel": 1,
+                                "sampler": { "filter": "nearest", "wrap": "clamp", "vflip": "false", "srgb": "false" }
+                            },
+                            {
+                                "id": "XsXGRn",
+                                "filepath": "/media/a/cd4c518b.png",
+                                "ctype": "texture",
+                                "channel": 2,
+                                "sampler": { "filter": "mipmap", "wrap": "repeat", "vflip": "true", "srgb": "false" }
+                            },
+                            {
+                                "id": "XsXGRr",
+                                "filepath": "/media/a/webcam.png",
+                                "ctype": "webcam",
+                                "channel": 3
+                            }
+                        ],
+                        "outputs": [{ "id": "4dfGRr", "channel": 0 }],
+                        "code": "void mainImage(out vec4 c, in vec2 p) { c = texture(iChannel0, p / iResolution.xy); }",
+                        "name": "Image",
+                        "type": "image"
+                    },
+                    {
+                        "inputs": [],
+                        "outputs": [{ "id": "4dXGR8", "channel": 0 }],
+                        "code": "void mainImage(out vec4 c, in vec2 p) { c = vec4(wave(iTime)); }",
+                        "name": "Buffer A",
+                        "type": "buffer"
+                    },
+                    {
+                        "inputs": [],
+                        "outputs": [],
+                        "code": "float wave(float t) { return sin(t + iTimeDelta); }",
+                        "name": "Common",
+                        "type": "common"
+                    },
+                    {
+                        "inputs": [],
+                        "outputs": [],
+                        "code": "vec2 mainSound(int s, float t) { return vec2(0.0); }",
+                        "name": "Sound",
+                        "type": "sound"
+                    }
+                ]
+            }
+        })
+    }
+
+    #[test]
+    fn test_import() {
+        let mut imports = import_shadertoy(&export().to_string()).unwrap();
+        assert_eq!(imports.len(), 1);
+        let import = imports.remove(0);
+        assert_eq!(import.title, "Feedback");
+        assert_eq!(import.author.as_deref(), Some("someone"));
+
+        let files = &import.project.files;
+        let keys = files.keys().collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            ["/Readme.md", "/shaders/common.txt", "/shaders/image.glsl"]
+        );
+        let image = files.get("/shaders/image.glsl").unwrap();
+        let image = image.data.as_text().unwrap();
+        assert!(image.contains("#define iChannel0 0"));
+        assert!(image.contains("float wave(float t)"));
+        assert!(!image.contains("Buffer A"));
+
+        let report = import
+            .report
+            .iter()
+            .map(|unmapped| (unmapped.pass.as_str(), unmapped.channel))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            report,
+            [
+                ("Buffer A", None),
+                ("Sound", None),
+                ("Image", Some(0)),
+                ("Image", Some(1)),
+                ("Image", Some(2)),
+                ("Image", Some(3)),
+                ("Image", None)
+            ]
+        );
+        assert_eq!(
+            import.report[2].message,
+            "Reads the output of `Buffer A`, which is not imported"
+        );
+        assert!(import.report[6].message.contains("iTimeDelta"));
+    }
+
+    #[test]
+    fn test_formats() {
+        // Lists as exported by Shadertoy, with bare shaders and numeric ids
+        let mut shader = export()["Shader"].take();
+        shader["renderpass"][0]["inputs"][0]["id"] = 257.into();
+        shader["renderpass"][1]["outputs"][0]["id"] = 257.into();
+        let json = serde_json::json!([shader.clone(), shader]).to_string();
+        let imports = import_shadertoy(&json).unwrap();
+        assert_eq!(imports.len(), 2);
+        assert_eq!(
+            imports[1].report[2].message,
+            "Reads the output of `Buffer A`, which is not imported"
+        );
+
+        // Older exports only name the buffer in the path
+        let mut shader = export();
+        shader["Shader"]["renderpass"][0]["inputs"][0]["id"] = "unknown".into();
+        let imports = import_shadertoy(&shader.to_string()).unwrap();
+        assert!(imports[0].report[2].message.contains("`Buffer A`"));
+
+        let mut shader = export();
+        shader["Shader"]["renderpass"][0]["type"] = "buffer".into();
+        assert!(matches!(
+            import_shadertoy(&shader.to_string()),
+            Err(ShadertoyError::NoImage(_))
+        ));
+        assert!(matches!(
+            import_shadertoy("{}"),
+            Err(ShadertoyError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn test_uses() {
+        assert!(uses("float t = iTimeDelta;", "iTimeDelta"));
+        assert!(!uses("float t = iTimeDeltas;", "iTimeDelta"));
+        assert!(!uses("float t = my_iDate;", "iDate"));
+    }
+}
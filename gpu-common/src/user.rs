@@ -1,7 +1,16 @@
 use crate::actions::Action;
+use crate::{
+    check_macros, default_keymap, find_conflicts, merge_keymap, Condition, ConditionError, Keybind,
+    KeybindConflict, KeybindError, Macro, MacroError,
+};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use thiserror::Error;
+
+/// Most keybinds a config may set, conflicts are found between every pair of
+/// keybinds of the keymap so it has to stay small
+pub const MAX_KEYBINDS: usize = 128;
 
 #[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
@@ -10,6 +19,93 @@ pub struct UserConfig {
     pub editor: UserEditorConfig,
     pub theme: HashMap<String, String>,
     pub general: UserGeneralConfig,
+    /// Macros by name, run with `Action::RunMacro`
+    #[serde(default)]
+    pub macros: HashMap<String, Macro>,
+}
+
+impl UserConfig {
+    /// Parses the condition of every keybind, so that configs with conditions
+    /// that can never be evaluated are rejected when saved
+    pub fn check_conditions(&self) -> Result<(), InvalidCondition> {
+        let mut keybinds = self.keybinds.iter().collect::<Vec<_>>();
+        keybinds.sort_by_key(|(keybind, _)| keybind.as_str());
+        for (keybind, action) in keybinds {
+            if let Some(ref condition) = action.condition {
+                Condition::parse_known(condition).map_err(|error| InvalidCondition {
+                    keybind: keybind.clone(),
+                    error,
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses every keybind, so that configs with keybinds that can never be
+    /// pressed are rejected when saved, and rejects configs with more than
+    /// [`MAX_KEYBINDS`] keybinds
+    pub fn check_keybinds(&self) -> Result<(), InvalidKeybind> {
+        if self.keybinds.len() > MAX_KEYBINDS {
+            return Err(InvalidKeybind::TooMany(self.keybinds.len()));
+        }
+        let mut keybinds = self.keybinds.keys().collect::<Vec<_>>();
+        keybinds.sort();
+        for keybind in keybinds {
+            Keybind::parse(keybind).map_err(|error| InvalidKeybind::Parse {
+                keybind: keybind.clone(),
+                error,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Checks that macros do not recurse and that every macro run, by a
+    /// macro or a keybind, exists
+    pub fn check_macros(&self) -> Result<(), MacroError> {
+        check_macros(&self.macros)?;
+        let mut keybinds = self.keybinds.iter().collect::<Vec<_>>();
+        keybinds.sort_by_key(|(keybind, _)| keybind.as_str());
+        for (keybind, action) in keybinds {
+            if let Action::RunMacro(ref name) = action.action {
+                if !self.macros.contains_key(name) {
+                    return Err(MacroError::Unknown(
+                        format!("Keybind `{keybind}`"),
+                        name.clone(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The default keymap with the user's keybinds applied over it
+    pub fn keymap(&self) -> HashMap<String, FilteredAction> {
+        merge_keymap(&default_keymap(), &self.keybinds)
+    }
+
+    /// Keybinds of the keymap that trigger different actions on the same
+    /// keys, see [`find_conflicts`]
+    pub fn keybind_conflicts(&self) -> Vec<KeybindConflict> {
+        find_conflicts(&self.keymap())
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Invalid condition for keybind `{keybind}`: {error}")]
+pub struct InvalidCondition {
+    pub keybind: String,
+    pub error: ConditionError,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum InvalidKeybind {
+    #[error("Invalid keybind `{keybind}`: {error}")]
+    Parse {
+        keybind: String,
+        error: KeybindError,
+    },
+    #[error("Config has {0} keybinds, at most {MAX_KEYBINDS} are allowed")]
+    TooMany(usize),
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
@@ -19,10 +115,12 @@ pub struct UserKeymap {
     pub map: HashMap<String, FilteredAction>,
 }
 
-#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
 #[serde(rename_all = "camelCase")]
 pub struct FilteredAction {
     pub action: Action,
+    /// Context the keybind applies in, i.e. `editorFocus && !isPlaying`
     pub condition: Option<String>,
 }
 
@@ -51,3 +149,103 @@ pub struct UserGeneralConfig {
     editor_panel_size: f32,
     resource_panel_size: f32,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::Action;
+
+    use super::{FilteredAction, InvalidKeybind, UserConfig, MAX_KEYBINDS};
+
+    #[test]
+    fn test_check_conditions() {
+        let mut config = UserConfig::default();
+        let bind = |condition: &str| FilteredAction {
+            action: Action::PlayPause,
+            condition: Some(condition.to_owned()),
+        };
+        config
+            .keybinds
+            .insert("C-s".to_owned(), bind("userLoggedIn && filesDirty"));
+        assert!(config.check_conditions().is_ok());
+
+        config
+            .keybinds
+            .insert("C-p".to_owned(), bind("editorFocus &&"));
+        let err = config.check_conditions().unwrap_err();
+        assert_eq!(err.keybind, "C-p");
+        assert_eq!(
+            err.to_string(),
+            "Invalid condition for keybind `C-p`: Expected a context key or string, found end of condition at column 15"
+        );
+    }
+
+    #[test]
+    fn test_check_keybinds() {
+        let mut config = UserConfig::default();
+        let bind = |action| FilteredAction {
+            action,
+            condition: None,
+        };
+        config
+            .keybinds
+            .insert("mod+k mod+s".to_owned(), bind(Action::SaveAllFiles));
+        config
+            .keybinds
+            .insert("C-k".to_owned(), bind(Action::NextDocument));
+        assert!(config.check_keybinds().is_ok());
+        assert_eq!(
+            config.keybind_conflicts()[0].to_string(),
+            "Keybind `C-k` conflicts with `mod+k mod+s` outside of macOS"
+        );
+
+        config
+            .keybinds
+            .insert("ctrl+shft+k".to_owned(), bind(Action::PlayPause));
+        assert_eq!(
+            config.check_keybinds().unwrap_err().to_string(),
+            "Invalid keybind `ctrl+shft+k`: Unknown modifier `shft`"
+        );
+
+        config.keybinds = (0..=MAX_KEYBINDS)
+            .map(|i| (format!("C-{i}"), bind(Action::PlayPause)))
+            .collect();
+        assert_eq!(
+            config.check_keybinds(),
+            Err(InvalidKeybind::TooMany(MAX_KEYBINDS + 1))
+        );
+    }
+
+    #[test]
+    fn test_check_macros() {
+        let mut config = UserConfig::default();
+        config.keybinds.insert(
+            "ctrl+shift+r".to_owned(),
+            FilteredAction {
+                action: Action::RunMacro("restart".to_owned()),
+                condition: None,
+            },
+        );
+        assert_eq!(
+            config.check_macros().unwrap_err().to_string(),
+            "Keybind `ctrl+shift+r` runs macro `restart`, which does not exist"
+        );
+
+        let config: UserConfig = serde_json::from_value(serde_json::json!({
+            "keybinds": config.keybinds,
+            "editor": { "lineNumbers": "on" },
+            "theme": {},
+            "general": { "projectPanelSize": 12, "editorPanelSize": 50, "resourcePanelSize": 40 },
+            "macros": {
+                "restart": {
+                    "steps": [
+                        { "action": { "ty": "reset" } },
+                        { "action": { "ty": "playPause" }, "delay": 500 }
+                    ]
+                }
+            }
+        }))
+        .unwrap();
+        assert_eq!(config.check_macros(), Ok(()));
+        assert_eq!(config.macros["restart"].steps[1].delay, Some(500));
+    }
+}
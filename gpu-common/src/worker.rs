@@ -0,0 +1,173 @@
+//! Messages exchanged with a gpu context running in a dedicated worker.
+//!
+//! The canvas is transferred to the worker once when it is spawned, everything
+//! after that goes through these messages.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{CaptureOptions, Event, Project};
+
+/// Message posted from the main thread to the worker
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase", tag = "ty", content = "c")]
+pub enum WorkerRequest {
+    Build(Project),
+    Resize(ResizeRequest),
+    Input(InputEvent),
+    SetParam(ParamUpdate),
+    /// Renders a png, answered with `WorkerResponse::Captured` carrying the same id
+    Capture {
+        id: u32,
+        options: CaptureOptions,
+    },
+    /// Starts the render loop
+    Start,
+    /// Stops the render loop
+    Stop,
+    /// Visibility of the page, since workers can not observe it themselves
+    Visibility {
+        hidden: bool,
+    },
+    SetProfiling(bool),
+    /// Restarts time and frame count from zero
+    Restart,
+}
+
+/// Message posted from the worker to the main thread
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase", tag = "ty", content = "c")]
+pub enum WorkerResponse {
+    /// Context was created, requests can be posted from now on
+    Ready,
+    Event(Event),
+    Captured {
+        id: u32,
+        /// Encoded png, which arrives as a `Uint8Array`
+        #[serde(with = "bytes")]
+        #[schemars(with = "Vec<u8>")]
+        #[cfg_attr(feature = "tsify", tsify(type = "Uint8Array"))]
+        png: Vec<u8>,
+    },
+    /// A request failed. `id` is set for requests that are answered.
+    Failed {
+        id: Option<u32>,
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct ResizeRequest {
+    /// Size of the canvas in css pixels
+    pub width: f64,
+    pub height: f64,
+    /// Device pixel ratio of the page, which workers have no access to
+    pub pixel_ratio: f64,
+}
+
+/// Input forwarded from the canvas
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase", tag = "ty", content = "c")]
+pub enum InputEvent {
+    /// Pointer moved over the canvas, in css pixels from its top left corner
+    PointerMove { x: f64, y: f64 },
+}
+
+/// Values written directly into a resource, i.e. to tweak uniforms from the ui
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct ParamUpdate {
+    /// Name the resource is declared with
+    pub resource: String,
+    /// Offset into the resource in bytes
+    #[serde(default)]
+    pub offset: u64,
+    pub values: Vec<f32>,
+}
+
+/// Serializes bytes as a byte array rather than a sequence, which
+/// `serde_wasm_bindgen` turns into a `Uint8Array` instead of an array of numbers
+mod bytes {
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("bytes")
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, bytes: &[u8]) -> Result<Vec<u8>, E> {
+                Ok(bytes.to_vec())
+            }
+
+            fn visit_byte_buf<E: serde::de::Error>(self, bytes: Vec<u8>) -> Result<Vec<u8>, E> {
+                Ok(bytes)
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<Vec<u8>, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                Ok(bytes)
+            }
+        }
+
+        deserializer.deserialize_byte_buf(Visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InputEvent, WorkerRequest, WorkerResponse};
+
+    #[test]
+    fn test_request_format() {
+        let request = WorkerRequest::Input(InputEvent::PointerMove { x: 1.0, y: 2.0 });
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(
+            json,
+            r#"{"ty":"input","c":{"ty":"pointerMove","c":{"x":1.0,"y":2.0}}}"#
+        );
+        let json = r#"{"ty":"capture","c":{"id":3,"options":{"width":64,"height":32}}}"#;
+        match serde_json::from_str(json).unwrap() {
+            WorkerRequest::Capture { id, options } => {
+                assert_eq!((id, options.width, options.height), (3, 64, 32));
+                assert!(options.time.is_none());
+            }
+            request => panic!("unexpected request {request:?}"),
+        }
+    }
+
+    #[test]
+    fn test_captured_round_trip() {
+        let response = WorkerResponse::Captured {
+            id: 1,
+            png: vec![137, 80, 78, 71],
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        match serde_json::from_str(&json).unwrap() {
+            WorkerResponse::Captured { id, png } => {
+                assert_eq!((id, png), (1, vec![137, 80, 78, 71]))
+            }
+            response => panic!("unexpected response {response:?}"),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::fmt::Display;
+
+use thiserror::Error;
+
+/// Location within a source file, both 1-based
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: u32,
+    pub column: u32,
+}
+
+/// Single problem found while compiling a project file
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    /// Path of the offending file, same as its key in `Files`
+    pub file: String,
+    /// Short description of the problem
+    pub message: String,
+    /// Where in the file the problem starts, if known
+    pub location: Option<Location>,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.location {
+            Some(Location { line, column }) => {
+                write!(f, "{}:{}:{}: {}", self.file, line, column, self.message)
+            }
+            None => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone)]
+pub struct Error(pub Vec<Diagnostic>);
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut diagnostics = self.0.iter();
+        if let Some(first) = diagnostics.next() {
+            write!(f, "{first}")?;
+        }
+        for diagnostic in diagnostics {
+            write!(f, "\n{diagnostic}")?;
+        }
+        Ok(())
+    }
+}
+
+impl From<Diagnostic> for Error {
+    fn from(diagnostic: Diagnostic) -> Self {
+        Self(vec![diagnostic])
+    }
+}
+
+impl From<&Diagnostic> for gpu_common::BuildDiagnostic {
+    fn from(diagnostic: &Diagnostic) -> Self {
+        Self {
+            file: diagnostic.file.clone(),
+            message: diagnostic.message.clone(),
+            line: diagnostic.location.map(|location| location.line),
+            column: diagnostic.location.map(|location| location.column),
+        }
+    }
+}
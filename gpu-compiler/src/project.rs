@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+
+use gpu_common::Files;
+
+use crate::{CompiledShader, Diagnostic, Error, ResourceDecl};
+
+/// Every shader within a project, compiled and checked for consistency.
+#[derive(Debug, Clone, Default)]
+pub struct CompiledProject {
+    /// Compiled shaders keyed by file path
+    pub shaders: BTreeMap<String, CompiledShader>,
+}
+
+impl CompiledProject {
+    /// Compiles every shader file in `files`.
+    ///
+    /// Errors from all files are collected rather than stopping at the first one.
+    pub fn compile(files: &Files) -> Result<Self, Error> {
+        let mut shaders = BTreeMap::new();
+        let mut diagnostics = Vec::new();
+
+        for (path, file) in files.iter() {
+            if !file.extension().is_shader() {
+                continue;
+            }
+            match CompiledShader::compile(path, file) {
+                Ok(shader) => {
+                    shaders.insert(path.to_owned(), shader);
+                }
+                Err(Error(errs)) => diagnostics.extend(errs),
+            }
+        }
+
+        let project = Self { shaders };
+        if let Err(Error(errs)) = project.check_resources() {
+            diagnostics.extend(errs);
+        }
+
+        if diagnostics.is_empty() {
+            Ok(project)
+        } else {
+            Err(Error(diagnostics))
+        }
+    }
+
+    /// Every resource declared across all shaders, keyed by name
+    pub fn resources(&self) -> BTreeMap<&str, &ResourceDecl> {
+        self.shaders
+            .values()
+            .flat_map(|shader| shader.bindings.iter())
+            .map(|binding| (binding.name.as_str(), &binding.decl))
+            .collect()
+    }
+
+    /// Resources are shared between shaders by name, so every shader declaring
+    /// the same name must agree on its layout
+    fn check_resources(&self) -> Result<(), Error> {
+        let mut seen: BTreeMap<&str, (&str, &ResourceDecl)> = BTreeMap::new();
+        let mut diagnostics = Vec::new();
+        for shader in self.shaders.values() {
+            for binding in shader.bindings.iter() {
+                match seen.get(binding.name.as_str()) {
+                    Some((other, decl)) if *decl != &binding.decl => diagnostics.push(Diagnostic {
+                        file: shader.path.clone(),
+                        message: format!(
+                            "Resource `{}` is declared differently in {other}",
+                            binding.name
+                        ),
+                        location: None,
+                    }),
+                    Some(_) => (),
+                    None => {
+                        seen.insert(&binding.name, (&shader.path, &binding.decl));
+                    }
+                }
+            }
+        }
+        if diagnostics.is_empty() {
+            Ok(())
+        } else {
+            Err(Error(diagnostics))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gpu_common::{import_shadertoy, Files};
+
+    use crate::{Stage, RUNTIME_ARRAY_LEN};
+
+    use super::CompiledProject;
+
+    #[test]
+    fn test_shared_resource() {
+        let decl = "struct State { count: u32, values: array<f32, 4> }\n\
+                    @group(0) @binding(0) var<storage, read_write> state: State;\n";
+        let files = Files::from_entries([
+            (
+                "/a.wgsl",
+                format!("{decl}@compute @workgroup_size(1) fn main() {{ state.count += 1u; }}"),
+            ),
+            (
+                "/b.wgsl",
+                format!("{decl}@compute @workgroup_size(1) fn main() {{ state.count = 0u; }}"),
+            ),
+        ])
+        .unwrap();
+        let project = CompiledProject::compile(&files).unwrap();
+        let resources = project.resources();
+        assert_eq!(resources.len(), 1);
+        assert_eq!(resources["state"].size, 20);
+        assert_eq!(resources["state"].runtime_stride, None);
+    }
+
+    #[test]
+    fn test_runtime_array_resource() {
+        let files = Files::from_entries([(
+            "/cells.wgsl",
+            "struct Cells { count: u32, values: array<vec4<f32>> }\n\
+             @group(0) @binding(0) var<storage, read_write> cells: Cells;\n\
+             @compute @workgroup_size(1) fn main() { cells.values[0].x = 1.0; }",
+        )])
+        .unwrap();
+        let project = CompiledProject::compile(&files).unwrap();
+        let cells = project.resources()["cells"];
+        assert_eq!(cells.runtime_stride, Some(16));
+        assert_eq!(cells.size, 16 + 16 * RUNTIME_ARRAY_LEN);
+    }
+
+    #[test]
+    fn test_conflicting_resource() {
+        let files = Files::from_entries([
+            (
+                "/a.wgsl",
+                "@group(0) @binding(0) var<storage, read_write> state: u32;\n\
+                 @compute @workgroup_size(1) fn main() { state = 1u; }",
+            ),
+            (
+                "/b.wgsl",
+                "@group(0) @binding(0) var<storage, read_write> state: f32;\n\
+                 @compute @workgroup_size(1) fn main() { state = 1.0; }",
+            ),
+        ])
+        .unwrap();
+        let err = CompiledProject::compile(&files).unwrap_err();
+        assert_eq!(err.0.len(), 1);
+        assert_eq!(err.0[0].file, "/b.wgsl");
+    }
+
+    #[test]
+    fn test_shadertoy_import() {
+        let json = r#"{
+            "info": { "name": "Channels" },
+            "renderpass": [
+                {
+                    "inputs": [
+                        { "id": 1, "ctype": "buffer", "channel": 0 },
+                        { "id": 2, "filepath": "/media/a/noise.png", "ctype": "texture", "channel": 1 }
+                    ],
+                    "outputs": [],
+                    "code": "void mainImage(out vec4 c, in vec2 p) {\n    vec2 uv = p / iResolution.xy;\n    c = texture(iChannel0, uv) + texture(iChannel1, uv, 0.5) + textureLod(iChannel1, uv, 0.0);\n    c += texelFetch(iChannel0, ivec2(p) % textureSize(iChannel0, 0), 0) + vec4(shade(iTime), iMouse.xy, float(iFrame));\n}",
+                    "name": "Image",
+                    "type": "image"
+                },
+                {
+                    "inputs": [],
+                    "outputs": [{ "id": 1 }],
+                    "code": "void mainImage(out vec4 c, in vec2 p) { c = vec4(1.0); }",
+                    "name": "Buffer A",
+                    "type": "buffer"
+                },
+                {
+                    "code": "float shade(float t) { return sin(t); }",
+                    "name": "Common",
+                    "type": "common"
+                }
+            ]
+        }"#;
+        let import = import_shadertoy(json).unwrap().remove(0);
+        let project = CompiledProject::compile(&import.project.files).unwrap();
+        let image = &project.shaders["/shaders/image.glsl"];
+        assert!(image.entry_point(Stage::Fragment).is_some());
+        assert_eq!(
+            project.resources().into_keys().collect::<Vec<_>>(),
+            ["system"]
+        );
+    }
+}
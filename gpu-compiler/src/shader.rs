@@ -0,0 +1,336 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use gpu_common::{File, SupportedExtension};
+use naga::proc::Layouter;
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use naga::{AddressSpace, ArraySize, ConstantInner, Handle, Module, ScalarValue, TypeInner};
+
+use crate::{Diagnostic, Error, Location};
+
+/// Number of elements allocated for a runtime-sized array within a storage buffer
+pub const RUNTIME_ARRAY_LEN: u64 = 1 << 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    Vertex,
+    Fragment,
+    Compute,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryPoint {
+    pub name: String,
+    pub stage: Stage,
+    /// Only meaningful for compute entry points
+    pub workgroup_size: [u32; 3],
+}
+
+/// Buffer declaration as seen from shader code.
+///
+/// Declarations that compare equal describe the same memory layout, so a buffer
+/// created for one can be bound to the other without losing its contents.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResourceDecl {
+    /// Size of buffer in bytes, counting [`RUNTIME_ARRAY_LEN`] elements of a
+    /// trailing runtime-sized array
+    pub size: u64,
+    /// Stride of the elements of a trailing runtime-sized array, if there is one.
+    /// Its length is up to whoever creates the buffer.
+    pub runtime_stride: Option<u64>,
+    /// Canonical signature of the declared type
+    pub ty: String,
+}
+
+/// Resource binding used by a shader
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    /// Name of the global variable. Bindings with the same name in different
+    /// shaders refer to the same resource.
+    pub name: String,
+    pub group: u32,
+    pub binding: u32,
+    pub decl: ResourceDecl,
+}
+
+/// Shader file that passed validation and is ready to be handed to the gpu
+#[derive(Debug, Clone)]
+pub struct CompiledShader {
+    /// Path of shader file, same as its key in `Files`
+    pub path: String,
+    /// Wgsl source. Glsl files are translated, while wgsl files are kept verbatim
+    /// so that errors reported by the gpu line up with the user's file.
+    pub source: String,
+    /// Hash of `source`, used to tell whether a shader changed between builds
+    pub hash: u64,
+    pub entry_points: Vec<EntryPoint>,
+    pub bindings: Vec<Binding>,
+}
+
+impl CompiledShader {
+    pub fn compile(path: &str, file: &File) -> Result<Self, Error> {
+        let source = text(path, file)?;
+        let module = parse(path, file)?;
+
+        let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+            .validate(&module)
+            .map_err(|err| Diagnostic {
+                file: path.to_owned(),
+                message: error_chain(err.as_inner()),
+                location: err.location(source).map(Location::from),
+            })?;
+
+        let mut layouter = Layouter::default();
+        layouter
+            .update(&module.types, &module.constants)
+            .map_err(|err| Diagnostic {
+                file: path.to_owned(),
+                message: error_chain(&err),
+                location: None,
+            })?;
+
+        let mut bindings = Vec::new();
+        for (handle, var) in module.global_variables.iter() {
+            let binding = match var.binding {
+                Some(ref binding) => binding,
+                None => continue,
+            };
+            let name = var.name.clone().unwrap_or_default();
+            let location = Some(
+                module
+                    .global_variables
+                    .get_span(handle)
+                    .location(source)
+                    .into(),
+            );
+            match var.space {
+                AddressSpace::Uniform | AddressSpace::Storage { .. } => (),
+                _ => {
+                    return Err(Diagnostic {
+                        file: path.to_owned(),
+                        message: format!(
+                            "Resource `{name}` is not a buffer. Only uniform and storage buffers can be bound"
+                        ),
+                        location,
+                    }
+                    .into())
+                }
+            }
+            bindings.push(Binding {
+                name,
+                group: binding.group,
+                binding: binding.binding,
+                decl: ResourceDecl {
+                    size: buffer_size(&module, &layouter, var.ty),
+                    runtime_stride: trailing_runtime_array_stride(&module, var.ty).map(u64::from),
+                    ty: type_signature(&module, var.ty),
+                },
+            });
+        }
+
+        let entry_points = module
+            .entry_points
+            .iter()
+            .map(|entry_point| EntryPoint {
+                name: entry_point.name.clone(),
+                stage: entry_point.stage.into(),
+                workgroup_size: entry_point.workgroup_size,
+            })
+            .collect();
+
+        let source = match file.extension() {
+            SupportedExtension::Glsl => naga::back::wgsl::write_string(
+                &module,
+                &info,
+                naga::back::wgsl::WriterFlags::empty(),
+            )
+            .map_err(|err| Diagnostic {
+                file: path.to_owned(),
+                message: format!("Could not translate to wgsl: {}", error_chain(&err)),
+                location: None,
+            })?,
+            _ => source.to_owned(),
+        };
+
+        let mut hasher = DefaultHasher::new();
+        source.hash(&mut hasher);
+
+        Ok(Self {
+            path: path.to_owned(),
+            hash: hasher.finish(),
+            source,
+            entry_points,
+            bindings,
+        })
+    }
+
+    /// Returns the first entry point for the given stage, if any
+    pub fn entry_point(&self, stage: Stage) -> Option<&EntryPoint> {
+        self.entry_points.iter().find(|ep| ep.stage == stage)
+    }
+}
+
+/// Source of a shader file, which has to be text
+fn text<'a>(path: &str, file: &'a File) -> Result<&'a str, Error> {
+    file.data.as_text().ok_or_else(|| {
+        Diagnostic {
+            file: path.to_owned(),
+            message: "Shader is not a text file".to_owned(),
+            location: None,
+        }
+        .into()
+    })
+}
+
+fn parse(path: &str, file: &File) -> Result<Module, Error> {
+    let source = text(path, file)?;
+    match file.extension() {
+        SupportedExtension::Wgsl => naga::front::wgsl::parse_str(source).map_err(|err| {
+            Diagnostic {
+                file: path.to_owned(),
+                message: err.message().to_owned(),
+                location: err.location(source).map(Location::from),
+            }
+            .into()
+        }),
+        SupportedExtension::Glsl => {
+            // Glsl has no way to declare the stage of a shader within the source,
+            // so assume fragment unless a workgroup size is declared.
+            let stage = if source.contains("local_size_x") {
+                naga::ShaderStage::Compute
+            } else {
+                naga::ShaderStage::Fragment
+            };
+            naga::front::glsl::Parser::default()
+                .parse(&stage.into(), source)
+                .map_err(|errors| {
+                    Error(
+                        errors
+                            .into_iter()
+                            .map(|err| Diagnostic {
+                                file: path.to_owned(),
+                                message: err.kind.to_string(),
+                                location: err
+                                    .meta
+                                    .to_range()
+                                    .map(|_| err.meta.location(source).into()),
+                            })
+                            .collect(),
+                    )
+                })
+        }
+        ext => Err(Diagnostic {
+            file: path.to_owned(),
+            message: format!("Files with extension {ext:?} are not shaders"),
+            location: None,
+        }
+        .into()),
+    }
+}
+
+/// Size in bytes of the buffer backing a variable of type `ty`
+fn buffer_size(module: &Module, layouter: &Layouter, ty: Handle<naga::Type>) -> u64 {
+    let size = layouter[ty].size as u64;
+    // The layouter counts a single element for runtime-sized arrays
+    match trailing_runtime_array_stride(module, ty) {
+        Some(stride) => size + stride as u64 * (RUNTIME_ARRAY_LEN - 1),
+        None => size,
+    }
+}
+
+fn trailing_runtime_array_stride(module: &Module, ty: Handle<naga::Type>) -> Option<u32> {
+    match module.types[ty].inner {
+        TypeInner::Array {
+            size: ArraySize::Dynamic,
+            stride,
+            ..
+        } => Some(stride),
+        TypeInner::Struct { ref members, .. } => members
+            .last()
+            .and_then(|member| trailing_runtime_array_stride(module, member.ty)),
+        _ => None,
+    }
+}
+
+/// Renders type as a string that only depends on its structure and memory layout
+fn type_signature(module: &Module, ty: Handle<naga::Type>) -> String {
+    let ty = &module.types[ty];
+    match ty.inner {
+        TypeInner::Scalar { kind, width } => format!("{kind:?}{width}"),
+        TypeInner::Vector { size, kind, width } => format!("vec{}<{kind:?}{width}>", size as u8),
+        TypeInner::Matrix {
+            columns,
+            rows,
+            width,
+        } => format!("mat{}x{}<Float{width}>", columns as u8, rows as u8),
+        TypeInner::Atomic { kind, width } => format!("atomic<{kind:?}{width}>"),
+        TypeInner::Array { base, size, stride } => {
+            let base = type_signature(module, base);
+            match size {
+                ArraySize::Constant(constant) => match module.constants[constant].inner {
+                    ConstantInner::Scalar {
+                        value: ScalarValue::Uint(len),
+                        ..
+                    } => format!("array<{base},{len},{stride}>"),
+                    ConstantInner::Scalar {
+                        value: ScalarValue::Sint(len),
+                        ..
+                    } => format!("array<{base},{len},{stride}>"),
+                    _ => format!("array<{base},?,{stride}>"),
+                },
+                ArraySize::Dynamic => format!("array<{base},{stride}>"),
+            }
+        }
+        TypeInner::Struct { ref members, span } => {
+            let members = members
+                .iter()
+                .map(|member| {
+                    format!(
+                        "{}@{}:{}",
+                        member.name.as_deref().unwrap_or_default(),
+                        member.offset,
+                        type_signature(module, member.ty)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+            format!(
+                "{}{{{members}}}{span}",
+                ty.name.as_deref().unwrap_or_default()
+            )
+        }
+        ref other => format!("{other:?}"),
+    }
+}
+
+/// Joins error with all of its sources, since naga nests the useful part of
+/// validation errors a few levels deep
+fn error_chain(err: &dyn std::error::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+    message
+}
+
+impl From<naga::ShaderStage> for Stage {
+    fn from(stage: naga::ShaderStage) -> Self {
+        match stage {
+            naga::ShaderStage::Vertex => Self::Vertex,
+            naga::ShaderStage::Fragment => Self::Fragment,
+            naga::ShaderStage::Compute => Self::Compute,
+        }
+    }
+}
+
+impl From<naga::SourceLocation> for Location {
+    fn from(location: naga::SourceLocation) -> Self {
+        Self {
+            line: location.line_number,
+            column: location.line_position,
+        }
+    }
+}
@@ -45,9 +45,32 @@ fn main() -> std::io::Result<()> {
     let schema = &schema_for!(gpu_common::realm::UpdateUserInfoArgs);
     write_schema(&dir, "update_user_info", schema)?;
 
+    let schema = &schema_for!(gpu_common::Event);
+    write_schema(&dir, "event", schema)?;
+
+    let schema = &schema_for!(gpu_common::ProfileSnapshot);
+    write_schema(&dir, "profile_snapshot", schema)?;
+
+    let schema = &schema_for!(gpu_common::WorkerRequest);
+    write_schema(&dir, "worker_request", schema)?;
+
+    let schema = &schema_for!(gpu_common::WorkerResponse);
+    write_schema(&dir, "worker_response", schema)?;
+
     let schema = &schema_for!(gpu_common::Action);
     write_schema(&dir, "action", schema)?;
 
+    let schema = &schema_for!(gpu_common::ActionRegistry);
+    write_schema(&dir, "action_registry", schema)?;
+
+    // The registry itself is generated too, for the command palette and
+    // keybinding editor
+    let registry = serde_json::to_string_pretty(&gpu_common::ActionRegistry::new()).unwrap();
+    let generated = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("..")
+        .join("front/src/generated");
+    std::fs::write(generated.join("actions.json"), registry)?;
+
     println!("Wrote schemas to {}", dir.to_string_lossy());
 
     Ok(())
@@ -0,0 +1,637 @@
+//! Syncs project directories with projects stored on gpu-back, so projects can
+//! be worked on locally and shared through the website.
+//!
+//! The revision and file hashes of the last sync are kept in the directory under
+//! `.gputoy`, which project reads skip. Comparing both sides with them tells
+//! which side changed what, so that changes to different files on either side
+//! are merged, while changes to the same file are reported as conflicts instead
+//! of overwriting one side.
+
+mod remote;
+mod snapshot;
+
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDateTime;
+use gpu_common::realm::{ProjectResponse, ProjectUpsert};
+use gpu_common::{
+    key_to_path, FileData, Files, PathError, Project, ProjectDir, ProjectDirError, ProjectManifest,
+    MANIFEST_FILE, SCHEMA_VERSION,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub use remote::*;
+pub use snapshot::*;
+
+/// Path of the sync state relative to the project root
+pub const STATE_FILE: &str = ".gputoy/sync.json";
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Project(#[from] ProjectDirError),
+    #[error(transparent)]
+    Path(#[from] PathError),
+    #[error("Could not access sync state at {0}: {1}")]
+    State(PathBuf, #[source] std::io::Error),
+    #[error("Invalid sync state: {0}")]
+    StateFormat(#[from] serde_json::Error),
+    #[error("Project is not linked to a remote project, pull one by id first")]
+    NotLinked,
+    #[error("No api url to sync with")]
+    NoApi,
+    #[error("Both sides changed since the last sync, force to overwrite them\n{0}")]
+    Conflict(Box<Status>),
+    #[error("Remote project was updated in the meantime, pull first")]
+    RemoteChanged,
+    #[error("Api responded with {0}: {1}")]
+    Api(u16, String),
+    #[error("Request failed: {0}")]
+    Request(String),
+}
+
+/// Remote revision a project directory was last synced with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncState {
+    /// Url of the api the project is stored on
+    pub api: String,
+    pub project_id: String,
+    /// Revision of the remote project
+    pub updated_at: NaiveDateTime,
+    /// Contents of both sides right after the sync
+    pub base: Snapshot,
+}
+
+/// Changes on both sides since the last sync
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    /// Remote project, or `None` if it has not been pushed yet
+    pub project_id: Option<String>,
+    pub local: Changes,
+    pub remote: Changes,
+    /// Files changed differently on both sides, including the manifest
+    pub conflicts: Vec<String>,
+}
+
+impl Status {
+    fn new(
+        project_id: Option<String>,
+        base: &Snapshot,
+        local: &Snapshot,
+        remote: &Snapshot,
+    ) -> Self {
+        let local_changes = Changes::between(base, local);
+        let remote_changes = Changes::between(base, remote);
+        let mut conflicts: Vec<String> = local_changes
+            .files
+            .keys()
+            .filter(|key| remote_changes.files.contains_key(*key))
+            .filter(|key| local.files.get(*key) != remote.files.get(*key))
+            .cloned()
+            .collect();
+        if local_changes.manifest && remote_changes.manifest && local.manifest != remote.manifest {
+            conflicts.insert(0, MANIFEST_FILE.to_owned());
+        }
+        Self {
+            project_id,
+            local: local_changes,
+            remote: remote_changes,
+            conflicts,
+        }
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.local.is_empty() && self.remote.is_empty()
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.project_id {
+            Some(ref id) => writeln!(f, "Remote project {id}")?,
+            None => writeln!(f, "Not pushed yet")?,
+        }
+        if self.is_synced() {
+            return writeln!(f, "Up to date");
+        }
+        if !self.local.is_empty() {
+            write!(f, "Local changes:\n{}", self.local)?;
+        }
+        if !self.remote.is_empty() {
+            write!(f, "Remote changes:\n{}", self.remote)?;
+        }
+        if !self.conflicts.is_empty() {
+            writeln!(f, "Conflicts:")?;
+            for key in self.conflicts.iter() {
+                writeln!(f, "  ! {key}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Project directory that is synced with a remote project
+pub struct LocalProject {
+    root: PathBuf,
+    state: Option<SyncState>,
+}
+
+impl LocalProject {
+    /// Opens the project in `root`, which may not exist yet if it is pulled
+    pub fn open(root: &Path) -> Result<Self, Error> {
+        let path = root.join(STATE_FILE);
+        let state = match std::fs::read_to_string(&path) {
+            Ok(state) => Some(serde_json::from_str(&state)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => return Err(Error::State(path, err)),
+        };
+        Ok(Self {
+            root: root.to_owned(),
+            state,
+        })
+    }
+
+    /// State of the last sync, or `None` if the project was never synced
+    pub fn state(&self) -> Option<&SyncState> {
+        self.state.as_ref()
+    }
+
+    pub fn status(&self, remote: &impl Remote) -> Result<Status, Error> {
+        let state = self.state.as_ref().ok_or(Error::NotLinked)?;
+        let response = remote.get(&state.project_id)?;
+        let local = self.read()?;
+        Ok(Status::new(
+            Some(state.project_id.clone()),
+            &state.base,
+            &local.map_or_else(|| state.base.clone(), |local| Snapshot::of(&local)),
+            &Snapshot::of(&remote_dir(&response)),
+        ))
+    }
+
+    /// Applies remote changes to the directory, linking it to project `id` if
+    /// given. Local changes to other files are kept.
+    ///
+    /// Fails with the status if any file changed on both sides, unless `force`
+    /// is set, in which case the directory is replaced by the remote project.
+    pub fn pull(
+        &mut self,
+        remote: &impl Remote,
+        id: Option<&str>,
+        force: bool,
+    ) -> Result<Status, Error> {
+        let linked = self.state.as_ref().map(|state| state.project_id.as_str());
+        let id = id.or(linked).ok_or(Error::NotLinked)?.to_owned();
+        let response = remote.get(&id)?;
+        let theirs = remote_dir(&response);
+        let ours = self.read()?;
+        let base = match self.state {
+            Some(ref state) if state.project_id == id => state.base.clone(),
+            _ => Snapshot::default(),
+        };
+        // Without a project in the directory there is nothing to keep
+        let ours_snapshot = ours.as_ref().map_or_else(|| base.clone(), Snapshot::of);
+        let status = Status::new(
+            Some(id.clone()),
+            &base,
+            &ours_snapshot,
+            &Snapshot::of(&theirs),
+        );
+        if !status.conflicts.is_empty() && !force {
+            return Err(Error::Conflict(Box::new(status)));
+        }
+
+        let mut merged = match ours {
+            Some(ref ours) => {
+                let merged = if force {
+                    ProjectDir {
+                        manifest: ProjectManifest {
+                            runner: ours.manifest.runner.clone(),
+                            ..theirs.manifest.clone()
+                        },
+                        files: theirs.files.clone(),
+                    }
+                } else {
+                    merge(ours, &theirs, &status.remote)?
+                };
+                self.remove_missing(ours, &merged)?;
+                merged
+            }
+            None => theirs.clone(),
+        };
+        download_assets(&mut merged.files, remote)?;
+        merged.write(&self.root)?;
+        self.save(SyncState {
+            api: remote.url().to_owned(),
+            project_id: id,
+            updated_at: response.updated_at,
+            base: Snapshot::of(&theirs),
+        })?;
+        Ok(status)
+    }
+
+    /// Uploads the directory, creating the remote project if it was never synced.
+    ///
+    /// Fails with the status if the remote project changed since the last sync,
+    /// unless `force` is set, in which case remote changes are overwritten.
+    pub fn push(&mut self, remote: &impl Remote, force: bool) -> Result<Status, Error> {
+        let ours = ProjectDir::read(&self.root)?;
+        let ours_snapshot = Snapshot::of(&ours);
+        let (theirs, status) = match self.state {
+            Some(ref state) => {
+                let theirs = remote.get(&state.project_id)?;
+                let status = Status::new(
+                    Some(state.project_id.clone()),
+                    &state.base,
+                    &ours_snapshot,
+                    &Snapshot::of(&remote_dir(&theirs)),
+                );
+                (Some(theirs), status)
+            }
+            None => {
+                let status = Status::new(
+                    None,
+                    &Snapshot::default(),
+                    &ours_snapshot,
+                    &Snapshot::default(),
+                );
+                (None, status)
+            }
+        };
+        if !status.remote.is_empty() && !force {
+            return Err(Error::Conflict(Box::new(status)));
+        }
+
+        let ProjectDir {
+            manifest,
+            mut files,
+        } = ours;
+        if let Some(ref theirs) = theirs {
+            reuse_assets(&mut files, &theirs.files);
+        }
+        let upsert = ProjectUpsert {
+            id: theirs.as_ref().map(|theirs| theirs.id.clone()),
+            title: manifest.title,
+            description: manifest.description,
+            files,
+            layout: manifest.layout,
+            config: manifest.config,
+            published: theirs.as_ref().is_some_and(|theirs| theirs.published),
+            // Rejected if the project changed after it was compared above
+            updated_at: theirs
+                .as_ref()
+                .filter(|_| !force)
+                .map(|theirs| theirs.updated_at),
+        };
+        let response = remote.upsert(&upsert)?;
+        self.save(SyncState {
+            api: remote.url().to_owned(),
+            project_id: response.id.clone(),
+            updated_at: response.updated_at,
+            base: Snapshot::of(&remote_dir(&response)),
+        })?;
+        Ok(status)
+    }
+
+    /// Reads the directory, or `None` if it holds no project yet
+    fn read(&self) -> Result<Option<ProjectDir>, Error> {
+        if !self.root.join(MANIFEST_FILE).exists() {
+            return Ok(None);
+        }
+        Ok(Some(ProjectDir::read(&self.root)?))
+    }
+
+    /// Deletes files of `old` that are not part of `new`
+    fn remove_missing(&self, old: &ProjectDir, new: &ProjectDir) -> Result<(), Error> {
+        for key in old.files.keys() {
+            if new.files.contains(key) {
+                continue;
+            }
+            let path = self.root.join(key_to_path(key)?);
+            std::fs::remove_file(&path).map_err(|err| ProjectDirError::Io(path, err))?;
+        }
+        Ok(())
+    }
+
+    fn save(&mut self, state: SyncState) -> Result<(), Error> {
+        let path = self.root.join(STATE_FILE);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|err| Error::State(path.clone(), err))?;
+        }
+        let json = serde_json::to_string_pretty(&state)?;
+        std::fs::write(&path, json).map_err(|err| Error::State(path, err))?;
+        self.state = Some(state);
+        Ok(())
+    }
+}
+
+/// Project as it would be stored in a directory
+fn remote_dir(response: &ProjectResponse) -> ProjectDir {
+    let project = Project {
+        schema_version: SCHEMA_VERSION,
+        files: response.files.clone(),
+        layout: response.layout.clone(),
+        config: response.config.clone(),
+    };
+    let mut dir = ProjectDir::from_project(response.title.clone(), project);
+    dir.manifest.description = response.description.clone();
+    dir
+}
+
+/// Replaces asset references with their bytes, so the files can be written
+fn download_assets(files: &mut Files, remote: &impl Remote) -> Result<(), Error> {
+    for (_, file) in files.iter_mut() {
+        if let FileData::Asset(ref asset) = file.data {
+            file.data = FileData::from(remote.asset(&asset.id)?);
+        }
+    }
+    Ok(())
+}
+
+/// Sends references to assets the remote already has instead of their bytes
+fn reuse_assets(files: &mut Files, theirs: &Files) {
+    for (key, file) in files.iter_mut() {
+        if let Some(FileData::Asset(asset)) = theirs.get(key).map(|theirs| &theirs.data) {
+            if matches!(file.data, FileData::Bytes { .. }) && file.data.hash() == asset.hash {
+                file.data = FileData::Asset(asset.clone());
+            }
+        }
+    }
+}
+
+/// `ours` with the changes `theirs` made since the last sync
+fn merge(ours: &ProjectDir, theirs: &ProjectDir, changes: &Changes) -> Result<ProjectDir, Error> {
+    let mut merged = ours.clone();
+    for (key, change) in changes.files.iter() {
+        match change {
+            FileChange::Removed => {
+                merged.files.remove(key)?;
+            }
+            FileChange::Added | FileChange::Modified => {
+                if let Some(file) = theirs.files.get(key) {
+                    merged.files.insert(key, file.clone())?;
+                }
+            }
+        }
+    }
+    if changes.manifest {
+        merged.manifest = ProjectManifest {
+            runner: ours.manifest.runner.clone(),
+            ..theirs.manifest.clone()
+        };
+    }
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+
+    use chrono::{Duration, NaiveDateTime};
+    use gpu_common::realm::{ProjectResponse, ProjectUpsert};
+    use gpu_common::{AssetRef, FileData, Files, ProjectDir, SCHEMA_VERSION};
+
+    use super::{Error, LocalProject, Remote};
+
+    /// Single remote project, whose revision advances with every upsert. Bytes
+    /// are stored as assets keyed by their hash, like gpu-back does.
+    struct FakeRemote {
+        project: RefCell<ProjectResponse>,
+        assets: RefCell<HashMap<String, Vec<u8>>>,
+        uploads: RefCell<usize>,
+    }
+
+    impl FakeRemote {
+        fn new(files: &[(&str, &str)]) -> Self {
+            let project = ProjectResponse {
+                schema_version: SCHEMA_VERSION,
+                id: "toy".to_owned(),
+                title: "Remote".to_owned(),
+                description: None,
+                files: Files::from_entries(files.iter().copied()).unwrap(),
+                layout: None,
+                config: None,
+                published: true,
+                created_at: NaiveDateTime::default(),
+                updated_at: NaiveDateTime::default(),
+                author_id: None,
+                forked_from_id: None,
+            };
+            Self {
+                project: RefCell::new(project),
+                assets: RefCell::default(),
+                uploads: RefCell::default(),
+            }
+        }
+
+        /// Edits the project as someone else would on the website
+        fn edit(&self, key: &str, data: &str) {
+            let mut project = self.project.borrow_mut();
+            project.files.write(key, data.to_owned()).unwrap();
+            project.updated_at += Duration::seconds(1);
+        }
+
+        fn data(&self, key: &str) -> Option<String> {
+            let project = self.project.borrow();
+            let data = project.files.get(key)?.data.as_text()?;
+            Some(data.to_owned())
+        }
+    }
+
+    impl Remote for FakeRemote {
+        fn url(&self) -> &str {
+            "http://localhost:7000"
+        }
+
+        fn get(&self, id: &str) -> Result<ProjectResponse, Error> {
+            let project = self.project.borrow();
+            if id != project.id {
+                return Err(Error::Api(404, "Project not found".to_owned()));
+            }
+            Ok(project.clone())
+        }
+
+        fn upsert(&self, upsert: &ProjectUpsert) -> Result<ProjectResponse, Error> {
+            let mut project = self.project.borrow_mut();
+            if upsert.updated_at.is_some_and(|at| at != project.updated_at) {
+                return Err(Error::RemoteChanged);
+            }
+            project.title = upsert.title.clone();
+            project.files = upsert.files.clone();
+            for (_, file) in project.files.iter_mut() {
+                if let FileData::Bytes { ref bytes } = file.data {
+                    let hash = file.data.hash();
+                    self.assets.borrow_mut().insert(hash.clone(), bytes.clone());
+                    *self.uploads.borrow_mut() += 1;
+                    file.data = FileData::Asset(AssetRef {
+                        id: hash.clone(),
+                        hash,
+                    });
+                }
+            }
+            project.updated_at += Duration::seconds(1);
+            Ok(project.clone())
+        }
+
+        fn asset(&self, id: &str) -> Result<Vec<u8>, Error> {
+            self.assets
+                .borrow()
+                .get(id)
+                .cloned()
+                .ok_or_else(|| Error::Api(404, "Asset not found".to_owned()))
+        }
+    }
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("gputoy-sync-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        root
+    }
+
+    fn read(root: &Path, path: &str) -> Option<String> {
+        std::fs::read_to_string(root.join(path)).ok()
+    }
+
+    #[test]
+    fn test_pull_push() {
+        let root = temp_root("pull-push");
+        let remote = FakeRemote::new(&[("/main.wgsl", "a"), ("/lib.wgsl", "b")]);
+        let mut local = LocalProject::open(&root).unwrap();
+        assert!(matches!(local.status(&remote), Err(Error::NotLinked)));
+
+        local.pull(&remote, Some("toy"), false).unwrap();
+        assert_eq!(read(&root, "main.wgsl").as_deref(), Some("a"));
+        assert!(local.status(&remote).unwrap().is_synced());
+
+        // Reopened from the stored state
+        let mut local = LocalProject::open(&root).unwrap();
+        std::fs::write(root.join("main.wgsl"), "a2").unwrap();
+        std::fs::write(root.join("new.wgsl"), "c").unwrap();
+        let status = local.status(&remote).unwrap();
+        assert_eq!(
+            status.to_string(),
+            "Remote project toy\nLocal changes:\n  M /main.wgsl\n  A /new.wgsl\n"
+        );
+
+        local.push(&remote, false).unwrap();
+        assert_eq!(remote.data("/main.wgsl").as_deref(), Some("a2"));
+        assert_eq!(remote.data("/new.wgsl").as_deref(), Some("c"));
+        assert!(local.status(&remote).unwrap().is_synced());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_merge() {
+        let root = temp_root("merge");
+        let remote = FakeRemote::new(&[("/main.wgsl", "a"), ("/lib.wgsl", "b")]);
+        let mut local = LocalProject::open(&root).unwrap();
+        local.pull(&remote, Some("toy"), false).unwrap();
+
+        // Changes to different files on both sides are merged
+        remote.edit("/lib.wgsl", "b2");
+        std::fs::write(root.join("main.wgsl"), "a2").unwrap();
+        let status = local.pull(&remote, None, false).unwrap();
+        assert!(status.conflicts.is_empty());
+        assert_eq!(read(&root, "lib.wgsl").as_deref(), Some("b2"));
+        assert_eq!(read(&root, "main.wgsl").as_deref(), Some("a2"));
+
+        // Removed remotely
+        remote
+            .project
+            .borrow_mut()
+            .files
+            .remove("/lib.wgsl")
+            .unwrap();
+        local.pull(&remote, None, false).unwrap();
+        assert_eq!(read(&root, "lib.wgsl"), None);
+
+        let status = local.status(&remote).unwrap();
+        assert!(status.remote.is_empty());
+        assert!(!status.local.is_empty());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_conflict() {
+        let root = temp_root("conflict");
+        let remote = FakeRemote::new(&[("/main.wgsl", "a")]);
+        let mut local = LocalProject::open(&root).unwrap();
+        local.pull(&remote, Some("toy"), false).unwrap();
+
+        remote.edit("/main.wgsl", "theirs");
+        std::fs::write(root.join("main.wgsl"), "ours").unwrap();
+        match local.pull(&remote, None, false) {
+            Err(Error::Conflict(status)) => assert_eq!(status.conflicts, ["/main.wgsl"]),
+            res => panic!("expected conflict, got {res:?}"),
+        }
+        assert!(matches!(
+            local.push(&remote, false),
+            Err(Error::Conflict(_))
+        ));
+        // Neither side was touched
+        assert_eq!(read(&root, "main.wgsl").as_deref(), Some("ours"));
+        assert_eq!(remote.data("/main.wgsl").as_deref(), Some("theirs"));
+
+        local.push(&remote, true).unwrap();
+        assert_eq!(remote.data("/main.wgsl").as_deref(), Some("ours"));
+
+        remote.edit("/main.wgsl", "theirs again");
+        std::fs::write(root.join("main.wgsl"), "ours again").unwrap();
+        local.pull(&remote, None, true).unwrap();
+        assert_eq!(read(&root, "main.wgsl").as_deref(), Some("theirs again"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_push_new() {
+        let root = temp_root("new");
+        let project = ProjectDir::from_project(
+            "Local",
+            Files::from_entries([("/main.wgsl", "a")]).unwrap().into(),
+        );
+        project.write(&root).unwrap();
+        let remote = FakeRemote::new(&[]);
+        let mut local = LocalProject::open(&root).unwrap();
+        let status = local.push(&remote, false).unwrap();
+        assert_eq!(status.project_id, None);
+        assert_eq!(local.state().unwrap().project_id, "toy");
+        assert_eq!(remote.project.borrow().title, "Local");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_assets() {
+        let root = temp_root("assets");
+        let mut files = Files::from_entries([("/main.wgsl", "a")]).unwrap();
+        files.create("/noise.png", vec![0, 159, 146]).unwrap();
+        ProjectDir::from_project("Local", files.into())
+            .write(&root)
+            .unwrap();
+        let remote = FakeRemote::new(&[]);
+        let mut local = LocalProject::open(&root).unwrap();
+        local.push(&remote, false).unwrap();
+        assert_eq!(*remote.uploads.borrow(), 1);
+        assert!(local.status(&remote).unwrap().is_synced());
+
+        // Unchanged bytes are not uploaded again
+        std::fs::write(root.join("main.wgsl"), "a2").unwrap();
+        local.push(&remote, false).unwrap();
+        assert_eq!(*remote.uploads.borrow(), 1);
+
+        let other = temp_root("assets-pull");
+        let mut pulled = LocalProject::open(&other).unwrap();
+        pulled.pull(&remote, Some("toy"), false).unwrap();
+        assert_eq!(
+            std::fs::read(other.join("noise.png")).unwrap(),
+            [0, 159, 146]
+        );
+        assert!(pulled.status(&remote).unwrap().is_synced());
+        std::fs::remove_dir_all(&root).unwrap();
+        std::fs::remove_dir_all(&other).unwrap();
+    }
+}
@@ -0,0 +1,101 @@
+use std::io::Read;
+
+use gpu_common::realm::{ProjectResponse, ProjectUpsert};
+use serde::Deserialize;
+
+use crate::Error;
+
+/// Where synced projects are stored, i.e. gpu-back
+pub trait Remote {
+    /// Url of the api, kept with the sync state of a project
+    fn url(&self) -> &str;
+    fn get(&self, id: &str) -> Result<ProjectResponse, Error>;
+    /// Creates the project, or updates it if it has an id. Fails with
+    /// [`Error::RemoteChanged`] if the update is based on an outdated revision.
+    fn upsert(&self, project: &ProjectUpsert) -> Result<ProjectResponse, Error>;
+    /// Bytes of an asset referenced by a file
+    fn asset(&self, id: &str) -> Result<Vec<u8>, Error>;
+}
+
+/// gpu-back's `/project` and `/asset` endpoints. Requests are authenticated by the session
+/// cookie set on [`HttpRemote::login`].
+pub struct HttpRemote {
+    url: String,
+    agent: ureq::Agent,
+}
+
+/// Body of error responses
+#[derive(Deserialize)]
+struct ApiErrorResponse {
+    message: String,
+}
+
+impl HttpRemote {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_owned(),
+            agent: ureq::Agent::new(),
+        }
+    }
+
+    pub fn login(&self, username_or_email: &str, password: &str) -> Result<(), Error> {
+        self.agent
+            .post(&format!("{}/login", self.url))
+            .send_form(&[
+                ("usernameOrEmail", username_or_email),
+                ("password", password),
+            ])
+            .map_err(from_ureq)?;
+        Ok(())
+    }
+}
+
+impl Remote for HttpRemote {
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn get(&self, id: &str) -> Result<ProjectResponse, Error> {
+        self.agent
+            .get(&format!("{}/project/{id}", self.url))
+            .call()
+            .map_err(from_ureq)?
+            .into_json()
+            .map_err(|err| Error::Request(err.to_string()))
+    }
+
+    fn upsert(&self, project: &ProjectUpsert) -> Result<ProjectResponse, Error> {
+        self.agent
+            .post(&format!("{}/project", self.url))
+            .send_json(project)
+            .map_err(from_ureq)?
+            .into_json()
+            .map_err(|err| Error::Request(err.to_string()))
+    }
+
+    fn asset(&self, id: &str) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        self.agent
+            .get(&format!("{}/asset/{id}", self.url))
+            .call()
+            .map_err(from_ureq)?
+            .into_reader()
+            .read_to_end(&mut bytes)
+            .map_err(|err| Error::Request(err.to_string()))?;
+        Ok(bytes)
+    }
+}
+
+fn from_ureq(err: ureq::Error) -> Error {
+    match err {
+        ureq::Error::Status(409, _) => Error::RemoteChanged,
+        ureq::Error::Status(status, response) => {
+            let message = response
+                .into_json::<ApiErrorResponse>()
+                .map(|body| body.message)
+                .unwrap_or_default();
+            Error::Api(status, message)
+        }
+        ureq::Error::Transport(transport) => Error::Request(transport.to_string()),
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use gpu_common::{FileData, ProjectDir, MANIFEST_FILE};
+use serde::{Deserialize, Serialize};
+
+/// Fingerprint of every file of a project, to tell which files changed since
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Hash of the title, description, config and layout
+    pub manifest: u64,
+    /// Hash of every file keyed by its key in `Files`
+    pub files: BTreeMap<String, u64>,
+}
+
+impl Snapshot {
+    pub fn of(dir: &ProjectDir) -> Self {
+        // The runner only exists on disk, so it is left out
+        let manifest = &dir.manifest;
+        let synced = serde_json::json!([
+            manifest.title,
+            manifest.description,
+            manifest.config,
+            manifest.layout,
+        ]);
+        let files = dir
+            .files
+            .iter()
+            .map(|(key, file)| {
+                // Binary files are hashed by their sha256, which bytes share
+                // with the asset they are uploaded as
+                let mut hash = match file.data {
+                    FileData::Text(ref text) => fnv1a(FNV_OFFSET, text.as_bytes()),
+                    ref data => fnv1a(FNV_OFFSET, data.hash().as_bytes()),
+                };
+                if let Some(ref fetch) = file.fetch {
+                    hash = fnv1a(fnv1a(hash, &[0]), fetch.as_bytes());
+                }
+                (key.to_owned(), hash)
+            })
+            .collect();
+        Self {
+            manifest: fnv1a(FNV_OFFSET, synced.to_string().as_bytes()),
+            files,
+        }
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Hash that stays the same across platforms and releases, unlike `DefaultHasher`
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FileChange {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// Files that changed between two snapshots
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Changes {
+    pub files: BTreeMap<String, FileChange>,
+    /// Whether the title, description, config or layout changed
+    pub manifest: bool,
+}
+
+impl Changes {
+    pub fn between(old: &Snapshot, new: &Snapshot) -> Self {
+        let mut files = BTreeMap::new();
+        for (key, hash) in new.files.iter() {
+            match old.files.get(key) {
+                None => {
+                    files.insert(key.clone(), FileChange::Added);
+                }
+                Some(old) if old != hash => {
+                    files.insert(key.clone(), FileChange::Modified);
+                }
+                Some(_) => {}
+            }
+        }
+        for key in old.files.keys() {
+            if !new.files.contains_key(key) {
+                files.insert(key.clone(), FileChange::Removed);
+            }
+        }
+        Self {
+            files,
+            manifest: old.manifest != new.manifest,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && !self.manifest
+    }
+}
+
+/// Every change on its own line, i.e. `M /shaders/main.wgsl`
+impl Display for Changes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.manifest {
+            writeln!(f, "  M {MANIFEST_FILE}")?;
+        }
+        for (key, change) in self.files.iter() {
+            let marker = match change {
+                FileChange::Added => 'A',
+                FileChange::Removed => 'D',
+                FileChange::Modified => 'M',
+            };
+            writeln!(f, "  {marker} {key}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gpu_common::{Files, ProjectDir};
+
+    use super::{Changes, FileChange, Snapshot};
+
+    fn dir(files: &[(&str, &str)]) -> ProjectDir {
+        ProjectDir::from_project(
+            "Snapshot",
+            Files::from_entries(files.iter().copied()).unwrap().into(),
+        )
+    }
+
+    #[test]
+    fn test_changes() {
+        let old = Snapshot::of(&dir(&[("/a.wgsl", "a"), ("/b.wgsl", "b")]));
+        let mut new_dir = dir(&[("/a.wgsl", "a2"), ("/c.wgsl", "c")]);
+        new_dir.manifest.runner = Some("a.wgsl".to_owned());
+        let new = Snapshot::of(&new_dir);
+        let changes = Changes::between(&old, &new);
+        assert_eq!(changes.files["/a.wgsl"], FileChange::Modified);
+        assert_eq!(changes.files["/b.wgsl"], FileChange::Removed);
+        assert_eq!(changes.files["/c.wgsl"], FileChange::Added);
+        // The runner is not synced
+        assert!(!changes.manifest);
+        assert_eq!(
+            changes.to_string(),
+            "  M /a.wgsl\n  D /b.wgsl\n  A /c.wgsl\n"
+        );
+
+        new_dir.manifest.title = "Renamed".to_owned();
+        assert!(Changes::between(&new, &Snapshot::of(&new_dir)).manifest);
+        assert!(Changes::between(&new, &new).is_empty());
+    }
+}
@@ -0,0 +1,542 @@
+//! Render thread owning the gpu context.
+//!
+//! Contexts can not be sent between threads, so one is created on a dedicated
+//! thread and driven by commands from the app's handlers. While running, the
+//! thread renders frames on its own and keeps the last one for the webview to
+//! fetch.
+
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+use futures_channel::oneshot;
+use gpu_client::context::{Context, Error as ContextError};
+use gpu_common::{CaptureOptions, Event, InputEvent, ParamUpdate, Project, ResizeRequest};
+use thiserror::Error;
+
+use crate::pacing::{self, Pacer};
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Context(#[from] ContextError),
+    #[error("Could not spawn render thread: {0}")]
+    Spawn(std::io::Error),
+    #[error("Render thread has stopped")]
+    Stopped,
+}
+
+/// Errors reach the webview as their message
+impl serde::Serialize for Error {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Gpu context driven by the render thread.
+///
+/// Implemented by gpu-client's headless context, and by a fake in tests so the
+/// host can be tested without a gpu.
+pub trait Renderer {
+    /// Builds `project`, reporting the outcome as a build event
+    fn build(&mut self, project: &Project) -> Result<(), ContextError>;
+    fn render(&mut self) -> Result<(), ContextError>;
+    /// Last rendered frame
+    fn read_frame(&mut self) -> Result<Frame, ContextError>;
+    fn resize(&mut self, request: ResizeRequest);
+    fn input(&mut self, event: InputEvent);
+    fn set_param(&mut self, update: &ParamUpdate) -> Result<(), ContextError>;
+    /// Renders a png image
+    fn capture(&mut self, options: &CaptureOptions) -> Result<Vec<u8>, ContextError>;
+    fn set_profiling(&mut self, enabled: bool);
+    /// Restarts time and frame count from zero
+    fn restart(&mut self);
+    /// Minimum time between frames in milliseconds, or 0 if not limited
+    fn frame_interval(&self) -> f64;
+    fn drain_events(&mut self) -> Vec<Event>;
+}
+
+impl Renderer for Context {
+    fn build(&mut self, project: &Project) -> Result<(), ContextError> {
+        pollster::block_on(Context::build(self, project))
+    }
+
+    fn render(&mut self) -> Result<(), ContextError> {
+        pollster::block_on(Context::render(self))
+    }
+
+    fn read_frame(&mut self) -> Result<Frame, ContextError> {
+        let rgba = pollster::block_on(Context::read_frame(self))?;
+        let (width, height) = self.frame_size();
+        Ok(Frame {
+            width,
+            height,
+            rgba,
+        })
+    }
+
+    fn resize(&mut self, request: ResizeRequest) {
+        Context::resize(self, request.width, request.height, request.pixel_ratio)
+    }
+
+    fn input(&mut self, event: InputEvent) {
+        Context::input(self, event)
+    }
+
+    fn set_param(&mut self, update: &ParamUpdate) -> Result<(), ContextError> {
+        Context::set_param(self, update)
+    }
+
+    fn capture(&mut self, options: &CaptureOptions) -> Result<Vec<u8>, ContextError> {
+        pollster::block_on(Context::capture(self, options))
+    }
+
+    fn set_profiling(&mut self, enabled: bool) {
+        Context::set_profiling(self, enabled)
+    }
+
+    fn restart(&mut self) {
+        Context::restart(self)
+    }
+
+    fn frame_interval(&self) -> f64 {
+        Context::frame_interval(self)
+    }
+
+    fn drain_events(&mut self) -> Vec<Event> {
+        Context::drain_events(self)
+    }
+}
+
+/// Rendered frame as rgba, row by row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+type Reply<T> = oneshot::Sender<Result<T, Error>>;
+
+enum Command {
+    Build(Box<Project>, Reply<()>),
+    Render(Reply<()>),
+    SetParam(ParamUpdate, Reply<()>),
+    Capture(CaptureOptions, Reply<Vec<u8>>),
+    Resize(ResizeRequest),
+    Input(InputEvent),
+    SetProfiling(bool),
+    Restart,
+    Start,
+    Stop,
+    Shutdown,
+}
+
+/// Handle to the render thread, which is stopped once this is dropped
+pub struct Host {
+    commands: mpsc::Sender<Command>,
+    frame: Arc<Mutex<Option<Arc<Frame>>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Host {
+    /// Spawns the render thread, creating its renderer with `init`.
+    ///
+    /// Events the renderer emits are passed to `on_event` on the render thread.
+    /// Returns once the renderer was created.
+    pub fn spawn<R, F, E>(init: F, on_event: E) -> Result<Host, Error>
+    where
+        R: Renderer,
+        F: FnOnce() -> Result<R, ContextError> + Send + 'static,
+        E: Fn(Event) + Send + 'static,
+    {
+        let (commands, receiver) = mpsc::channel();
+        let (ready, ready_receiver) = mpsc::channel();
+        let frame = Arc::new(Mutex::new(None));
+        let thread_frame = frame.clone();
+        let thread = std::thread::Builder::new()
+            .name("render".to_owned())
+            .spawn(move || {
+                let renderer = match init() {
+                    Ok(renderer) => renderer,
+                    Err(err) => {
+                        let _ = ready.send(Err(err));
+                        return;
+                    }
+                };
+                let _ = ready.send(Ok(()));
+                RenderThread {
+                    renderer,
+                    on_event,
+                    frame: thread_frame,
+                    running: false,
+                    pacer: Pacer::default(),
+                }
+                .run(receiver)
+            })
+            .map_err(Error::Spawn)?;
+        ready_receiver.recv().map_err(|_| Error::Stopped)??;
+        Ok(Host {
+            commands,
+            frame,
+            thread: Some(thread),
+        })
+    }
+
+    /// Builds project incrementally on top of the current one
+    pub async fn build(&self, project: Project) -> Result<(), Error> {
+        self.request(|reply| Command::Build(Box::new(project), reply))
+            .await
+    }
+
+    /// Renders a single frame, i.e. to step through frames while stopped
+    pub async fn render(&self) -> Result<(), Error> {
+        self.request(Command::Render).await
+    }
+
+    pub async fn set_param(&self, update: ParamUpdate) -> Result<(), Error> {
+        self.request(|reply| Command::SetParam(update, reply)).await
+    }
+
+    /// Renders the current project to a png image
+    pub async fn capture(&self, options: CaptureOptions) -> Result<Vec<u8>, Error> {
+        self.request(|reply| Command::Capture(options, reply)).await
+    }
+
+    pub fn resize(&self, request: ResizeRequest) -> Result<(), Error> {
+        self.send(Command::Resize(request))
+    }
+
+    pub fn input(&self, event: InputEvent) -> Result<(), Error> {
+        self.send(Command::Input(event))
+    }
+
+    pub fn set_profiling(&self, enabled: bool) -> Result<(), Error> {
+        self.send(Command::SetProfiling(enabled))
+    }
+
+    /// Restarts time and frame count from zero
+    pub fn restart(&self) -> Result<(), Error> {
+        self.send(Command::Restart)
+    }
+
+    /// Starts rendering frames paced by the project's frame rate limit
+    pub fn start(&self) -> Result<(), Error> {
+        self.send(Command::Start)
+    }
+
+    pub fn stop(&self) -> Result<(), Error> {
+        self.send(Command::Stop)
+    }
+
+    /// Last frame rendered, if any
+    pub fn frame(&self) -> Option<Arc<Frame>> {
+        self.frame
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn send(&self, command: Command) -> Result<(), Error> {
+        self.commands.send(command).map_err(|_| Error::Stopped)
+    }
+
+    async fn request<T>(&self, command: impl FnOnce(Reply<T>) -> Command) -> Result<T, Error> {
+        let (reply, receiver) = oneshot::channel();
+        self.send(command(reply))?;
+        receiver.await.map_err(|_| Error::Stopped)?
+    }
+}
+
+impl Drop for Host {
+    fn drop(&mut self) {
+        let _ = self.send(Command::Shutdown);
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("Render thread panicked");
+            }
+        }
+    }
+}
+
+struct RenderThread<R, E> {
+    renderer: R,
+    on_event: E,
+    frame: Arc<Mutex<Option<Arc<Frame>>>>,
+    running: bool,
+    pacer: Pacer,
+}
+
+impl<R: Renderer, E: Fn(Event)> RenderThread<R, E> {
+    fn run(mut self, commands: mpsc::Receiver<Command>) {
+        loop {
+            let interval = pacing::interval(self.renderer.frame_interval());
+            let command = if self.running {
+                commands.recv_timeout(self.pacer.wait(Instant::now(), interval))
+            } else {
+                commands.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            };
+            match command {
+                Ok(command) => {
+                    if !self.handle(command) {
+                        return;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+            // Checked after every command too, so a flood of input does not hold up frames
+            let now = Instant::now();
+            if self.running && self.pacer.wait(now, interval).is_zero() {
+                self.pacer.advance(now, interval);
+                match self.render() {
+                    // Already reported as an error event
+                    Ok(()) | Err(ContextError::Gpu(_)) => {}
+                    Err(err) => log::warn!("Frame failed: {err}"),
+                }
+            }
+        }
+    }
+
+    /// Handles `command`, returning false once the thread should stop
+    fn handle(&mut self, command: Command) -> bool {
+        match command {
+            Command::Build(project, reply) => {
+                let res = self.renderer.build(&project);
+                self.dispatch_events();
+                self.pacer.reset();
+                let _ = reply.send(res.map_err(Error::from));
+            }
+            Command::Render(reply) => {
+                let _ = reply.send(self.render().map_err(Error::from));
+            }
+            Command::SetParam(update, reply) => {
+                let _ = reply.send(self.renderer.set_param(&update).map_err(Error::from));
+            }
+            Command::Capture(options, reply) => {
+                let res = self.renderer.capture(&options);
+                self.dispatch_events();
+                let _ = reply.send(res.map_err(Error::from));
+            }
+            Command::Resize(request) => self.renderer.resize(request),
+            Command::Input(event) => self.renderer.input(event),
+            Command::SetProfiling(enabled) => self.renderer.set_profiling(enabled),
+            Command::Restart => self.renderer.restart(),
+            Command::Start => {
+                if !self.running {
+                    self.running = true;
+                    self.pacer.reset();
+                }
+            }
+            Command::Stop => self.running = false,
+            Command::Shutdown => return false,
+        }
+        true
+    }
+
+    fn render(&mut self) -> Result<(), ContextError> {
+        let res = self
+            .renderer
+            .render()
+            .and_then(|()| self.renderer.read_frame());
+        self.dispatch_events();
+        let frame = res?;
+        *self.frame.lock().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(frame));
+        Ok(())
+    }
+
+    fn dispatch_events(&mut self) {
+        for event in self.renderer.drain_events() {
+            (self.on_event)(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use gpu_client::context::Error as ContextError;
+    use gpu_common::{
+        BuildEvent, CaptureOptions, Event, Files, FrameEvent, InputEvent, ParamUpdate, Project,
+        ResizeRequest,
+    };
+
+    use super::{Error, Frame, Host, Renderer};
+
+    /// Renderer filling frames with their frame number
+    #[derive(Default)]
+    struct Fake {
+        frame: u32,
+        size: (u32, u32),
+        events: Vec<Event>,
+    }
+
+    impl Renderer for Fake {
+        fn build(&mut self, _project: &Project) -> Result<(), ContextError> {
+            self.frame = 0;
+            self.events.push(Event::Build(BuildEvent {
+                success: true,
+                diagnostics: Vec::new(),
+            }));
+            Ok(())
+        }
+
+        fn render(&mut self) -> Result<(), ContextError> {
+            self.frame += 1;
+            self.events.push(Event::Frame(FrameEvent {
+                frame: self.frame,
+                time: 0.0,
+                cpu_time: 0.0,
+                width: self.size.0,
+                height: self.size.1,
+            }));
+            Ok(())
+        }
+
+        fn read_frame(&mut self) -> Result<Frame, ContextError> {
+            let (width, height) = self.size;
+            Ok(Frame {
+                width,
+                height,
+                rgba: vec![self.frame as u8; width as usize * height as usize * 4],
+            })
+        }
+
+        fn resize(&mut self, request: ResizeRequest) {
+            self.size = (
+                (request.width * request.pixel_ratio) as u32,
+                (request.height * request.pixel_ratio) as u32,
+            );
+        }
+
+        fn input(&mut self, _event: InputEvent) {}
+
+        fn set_param(&mut self, update: &ParamUpdate) -> Result<(), ContextError> {
+            Err(ContextError::UnknownResource(update.resource.clone()))
+        }
+
+        fn capture(&mut self, options: &CaptureOptions) -> Result<Vec<u8>, ContextError> {
+            Ok(vec![0; (options.width * options.height) as usize])
+        }
+
+        fn set_profiling(&mut self, _enabled: bool) {}
+
+        fn restart(&mut self) {}
+
+        fn frame_interval(&self) -> f64 {
+            1.0
+        }
+
+        fn drain_events(&mut self) -> Vec<Event> {
+            std::mem::take(&mut self.events)
+        }
+    }
+
+    fn spawn() -> (Host, Arc<Mutex<Vec<Event>>>) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let host = Host::spawn(
+            || Ok(Fake::default()),
+            move |event| sink.lock().unwrap().push(event),
+        )
+        .unwrap();
+        (host, events)
+    }
+
+    /// Waits for the render thread to render up to `frame`
+    fn wait_for_frame(host: &Host, frame: u8) -> Arc<Frame> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            match host.frame() {
+                Some(last) if last.rgba.first() >= Some(&frame) => return last,
+                _ if Instant::now() > deadline => panic!("frame {frame} was not rendered"),
+                _ => std::thread::sleep(Duration::from_millis(1)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_requests() {
+        let (host, events) = spawn();
+        pollster::block_on(host.build(Files::new().into())).unwrap();
+        assert!(matches!(
+            events.lock().unwrap().as_slice(),
+            [Event::Build(BuildEvent { success: true, .. })]
+        ));
+        assert!(host.frame().is_none());
+
+        host.resize(ResizeRequest {
+            width: 2.0,
+            height: 1.0,
+            pixel_ratio: 2.0,
+        })
+        .unwrap();
+        pollster::block_on(host.render()).unwrap();
+        let frame = host.frame().unwrap();
+        assert_eq!((frame.width, frame.height), (4, 2));
+        assert_eq!(frame.rgba, vec![1; 4 * 2 * 4]);
+        assert!(matches!(
+            events.lock().unwrap().last(),
+            Some(Event::Frame(FrameEvent { frame: 1, .. }))
+        ));
+
+        let res = pollster::block_on(host.set_param(ParamUpdate {
+            resource: "missing".to_owned(),
+            offset: 0,
+            values: vec![1.0],
+        }));
+        assert!(matches!(
+            res,
+            Err(Error::Context(ContextError::UnknownResource(name))) if name == "missing"
+        ));
+
+        let png = pollster::block_on(host.capture(CaptureOptions {
+            width: 3,
+            height: 2,
+            time: None,
+        }))
+        .unwrap();
+        assert_eq!(png.len(), 6);
+    }
+
+    #[test]
+    fn test_render_loop() {
+        let (host, events) = spawn();
+        host.resize(ResizeRequest {
+            width: 1.0,
+            height: 1.0,
+            pixel_ratio: 1.0,
+        })
+        .unwrap();
+        host.start().unwrap();
+        wait_for_frame(&host, 3);
+        host.stop().unwrap();
+
+        // Round trip through the thread, so the stop was handled
+        pollster::block_on(host.build(Files::new().into())).unwrap();
+        let rendered = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|event| matches!(event, Event::Frame(_)))
+            .count();
+        std::thread::sleep(Duration::from_millis(20));
+        let after = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|event| matches!(event, Event::Frame(_)))
+            .count();
+        assert!(rendered >= 3);
+        assert_eq!(rendered, after);
+    }
+
+    #[test]
+    fn test_init_failure() {
+        let res = Host::spawn(|| Err::<Fake, _>(ContextError::NoAdapter), |_| {});
+        assert!(matches!(res, Err(Error::Context(ContextError::NoAdapter))));
+    }
+}
@@ -0,0 +1,10 @@
+//! Native gputoy client, hosting the web frontend in a Tauri webview while
+//! `gpu-client` renders on the platform's native graphics api.
+//!
+//! Everything but the application itself lives in this library so it can be
+//! built and tested without a display or webview libraries.
+
+pub mod host;
+mod pacing;
+pub mod sync;
+pub mod watch;
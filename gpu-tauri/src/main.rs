@@ -1,3 +1,241 @@
+#![cfg_attr(
+    all(not(debug_assertions), target_os = "windows"),
+    windows_subsystem = "windows"
+)]
+
+use std::path::PathBuf;
+use std::sync::{Mutex, PoisonError};
+
+use gpu_client::context::Context;
+use gpu_common::{
+    CaptureOptions, InputEvent, ParamUpdate, Project, ProjectDir, ProjectDirError, ResizeRequest,
+};
+use gpu_sync::Status;
+use gpu_tauri::host::{Error, Host};
+use gpu_tauri::sync::{self, ProjectSync};
+use gpu_tauri::watch::{self, ProjectWatcher};
+use serde::Serialize;
+use tauri::http::ResponseBuilder;
+use tauri::{AppHandle, Manager, State};
+
+/// Event the webview receives the context's events on
+const EVENT: &str = "gpu:event";
+/// Event the webview receives a project on after its files changed on disk
+const PROJECT_EVENT: &str = "project:update";
+/// Scheme the last frame is fetched from as raw rgba, since command responses
+/// are serialized as json
+const FRAME_SCHEME: &str = "gputoy";
+
+#[tauri::command]
+async fn build(host: State<'_, Host>, project: Project) -> Result<(), Error> {
+    host.build(project).await
+}
+
+#[tauri::command]
+async fn render(host: State<'_, Host>) -> Result<(), Error> {
+    host.render().await
+}
+
+#[tauri::command]
+async fn set_param(host: State<'_, Host>, update: ParamUpdate) -> Result<(), Error> {
+    host.set_param(update).await
+}
+
+#[tauri::command]
+async fn capture(host: State<'_, Host>, options: CaptureOptions) -> Result<Vec<u8>, Error> {
+    host.capture(options).await
+}
+
+#[tauri::command]
+fn resize(host: State<'_, Host>, request: ResizeRequest) -> Result<(), Error> {
+    host.resize(request)
+}
+
+#[tauri::command]
+fn input(host: State<'_, Host>, event: InputEvent) -> Result<(), Error> {
+    host.input(event)
+}
+
+#[tauri::command]
+fn set_profiling(host: State<'_, Host>, enabled: bool) -> Result<(), Error> {
+    host.set_profiling(enabled)
+}
+
+#[tauri::command]
+fn restart(host: State<'_, Host>) -> Result<(), Error> {
+    host.restart()
+}
+
+#[tauri::command]
+fn start(host: State<'_, Host>) -> Result<(), Error> {
+    host.start()
+}
+
+#[tauri::command]
+fn stop(host: State<'_, Host>) -> Result<(), Error> {
+    host.stop()
+}
+
+/// Project read from a directory
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OpenedProject {
+    title: String,
+    description: Option<String>,
+    project: Project,
+}
+
+/// Directory of the open project along with its watcher
+#[derive(Default)]
+struct Watched(Mutex<Option<(PathBuf, ProjectWatcher)>>);
+
+impl Watched {
+    fn root(&self) -> Option<PathBuf> {
+        let watched = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        watched.as_ref().map(|(root, _)| root.clone())
+    }
+}
+
+/// Opens the project in directory `path`, rebuilding it whenever its files change
+#[tauri::command]
+async fn open_project(app: AppHandle, path: String) -> Result<OpenedProject, watch::Error> {
+    open(&app, PathBuf::from(path)).await
+}
+
+/// Opens the project directory given on the command line, if any
+#[tauri::command]
+async fn initial_project(app: AppHandle) -> Result<Option<OpenedProject>, watch::Error> {
+    match std::env::args_os().nth(1) {
+        Some(path) => open(&app, PathBuf::from(path)).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+async fn open(app: &AppHandle, root: PathBuf) -> Result<OpenedProject, watch::Error> {
+    // Watch events carry canonical paths, which relative or symlinked roots
+    // would never prefix
+    let root = root
+        .canonicalize()
+        .map_err(|err| ProjectDirError::Io(root.clone(), err))?;
+    let dir = ProjectDir::read(&root)?;
+    let handle = app.clone();
+    let watcher = ProjectWatcher::new(root.clone(), dir.clone(), move |update| {
+        // Builds are incremental, so resources keep their contents. Failures are
+        // reported through build and error events.
+        let host = handle.state::<Host>();
+        let _ = pollster::block_on(host.build(update.project.clone()));
+        if let Err(err) = handle.emit_all(PROJECT_EVENT, update) {
+            log::error!("Could not emit project update: {err}");
+        }
+    })?;
+    // Stops watching the project open before, if any
+    *app.state::<Watched>()
+        .0
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) = Some((root, watcher));
+
+    let opened = OpenedProject {
+        title: dir.manifest.title.clone(),
+        description: dir.manifest.description.clone(),
+        project: dir.into_project(),
+    };
+    let _ = app.state::<Host>().build(opened.project.clone()).await;
+    Ok(opened)
+}
+
+/// Logs in to the api projects are synced with
+#[tauri::command]
+async fn sync_login(
+    sync: State<'_, ProjectSync>,
+    api: String,
+    username_or_email: String,
+    password: String,
+) -> Result<(), sync::Error> {
+    sync.login(api, username_or_email, password).await
+}
+
+/// Changes to the open project locally and remotely since it was last synced
+#[tauri::command]
+async fn sync_status(
+    sync: State<'_, ProjectSync>,
+    watched: State<'_, Watched>,
+) -> Result<Status, sync::Error> {
+    sync.status(watched.root()).await
+}
+
+/// Applies remote changes to the open project, linking it to project `id` if given
+#[tauri::command]
+async fn sync_pull(
+    sync: State<'_, ProjectSync>,
+    watched: State<'_, Watched>,
+    id: Option<String>,
+    force: bool,
+) -> Result<Status, sync::Error> {
+    sync.pull(watched.root(), id, force).await
+}
+
+/// Uploads the open project
+#[tauri::command]
+async fn sync_push(
+    sync: State<'_, ProjectSync>,
+    watched: State<'_, Watched>,
+    force: bool,
+) -> Result<Status, sync::Error> {
+    sync.push(watched.root(), force).await
+}
+
 fn main() {
-    println!("Hello, world!");
+    pretty_env_logger::init();
+
+    tauri::Builder::default()
+        .setup(|app| {
+            let handle = app.handle();
+            // Sized by the webview's first resize
+            let host = Host::spawn(
+                || pollster::block_on(Context::new_headless(1, 1)),
+                move |event| {
+                    if let Err(err) = handle.emit_all(EVENT, event) {
+                        log::error!("Could not emit event: {err}");
+                    }
+                },
+            )?;
+            app.manage(host);
+            app.manage(Watched::default());
+            app.manage(ProjectSync::default());
+            Ok(())
+        })
+        .register_uri_scheme_protocol(FRAME_SCHEME, |app, _request| {
+            let response = ResponseBuilder::new()
+                .header("Access-Control-Allow-Origin", "*")
+                .header("Access-Control-Expose-Headers", "x-width, x-height");
+            let response = match app.state::<Host>().frame() {
+                Some(frame) => response
+                    .mimetype("application/octet-stream")
+                    .header("x-width", frame.width)
+                    .header("x-height", frame.height)
+                    .body(frame.rgba.clone()),
+                None => response.status(204).body(Vec::new()),
+            }?;
+            Ok(response)
+        })
+        .invoke_handler(tauri::generate_handler![
+            build,
+            render,
+            set_param,
+            capture,
+            resize,
+            input,
+            set_profiling,
+            restart,
+            start,
+            stop,
+            open_project,
+            initial_project,
+            sync_login,
+            sync_status,
+            sync_pull,
+            sync_push
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running gputoy");
 }
@@ -0,0 +1,93 @@
+//! Frame pacing for the render thread, which has no display refresh to wait on.
+
+use std::time::{Duration, Instant};
+
+/// Frame rate used when the project does not limit it
+pub(crate) const DEFAULT_FPS: u32 = 60;
+
+/// Schedules frames at a fixed interval, without drifting when frames are
+/// rendered a little late
+#[derive(Debug, Default)]
+pub(crate) struct Pacer {
+    /// Time the last rendered frame was due at
+    last_frame: Option<Instant>,
+}
+
+impl Pacer {
+    /// Time left until the next frame is due, zero if it is due already
+    pub(crate) fn wait(&self, now: Instant, interval: Duration) -> Duration {
+        match self.last_frame {
+            Some(last) => (last + interval).saturating_duration_since(now),
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Marks a frame as rendered at `now`
+    pub(crate) fn advance(&mut self, now: Instant, interval: Duration) {
+        self.last_frame = Some(match self.last_frame {
+            // Advance by whole intervals so the frame rate does not drift, unless
+            // frames fell far behind, i.e. after a slow build
+            Some(last) if now < last + interval * 2 => last + interval,
+            _ => now,
+        });
+    }
+
+    /// Forgets the last frame, so the next one is due right away
+    pub(crate) fn reset(&mut self) {
+        self.last_frame = None;
+    }
+}
+
+/// Minimum time between frames for a context's frame interval in milliseconds,
+/// where 0 means the frame rate is not limited
+pub(crate) fn interval(frame_interval: f64) -> Duration {
+    if frame_interval > 0.0 {
+        Duration::from_secs_f64(frame_interval / 1000.0)
+    } else {
+        Duration::from_secs(1) / DEFAULT_FPS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{interval, Pacer};
+
+    #[test]
+    fn test_pacer() {
+        let interval = Duration::from_millis(10);
+        let start = Instant::now();
+        let mut pacer = Pacer::default();
+        assert_eq!(pacer.wait(start, interval), Duration::ZERO);
+
+        pacer.advance(start, interval);
+        assert_eq!(
+            pacer.wait(start + Duration::from_millis(4), interval),
+            Duration::from_millis(6)
+        );
+
+        // A late frame does not push back the ones after it
+        pacer.advance(start + Duration::from_millis(13), interval);
+        assert_eq!(
+            pacer.wait(start + Duration::from_millis(13), interval),
+            Duration::from_millis(7)
+        );
+
+        // Far behind, so the schedule restarts
+        pacer.advance(start + Duration::from_millis(100), interval);
+        assert_eq!(
+            pacer.wait(start + Duration::from_millis(100), interval),
+            interval
+        );
+
+        pacer.reset();
+        assert_eq!(pacer.wait(start, interval), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_interval() {
+        assert_eq!(interval(0.0), Duration::from_secs(1) / 60);
+        assert_eq!(interval(50.0), Duration::from_millis(50));
+    }
+}
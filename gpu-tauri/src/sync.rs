@@ -0,0 +1,151 @@
+//! Syncs the open project directory with gpu-back, see `gpu-sync`.
+//!
+//! Requests block, so every operation runs on a blocking thread. Pulled files
+//! reach the webview through the project watcher like any other change on disk.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use gpu_sync::{HttpRemote, LocalProject, Status};
+use serde::ser::SerializeStruct;
+use serde::Serialize;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Sync(#[from] gpu_sync::Error),
+    #[error("Log in to sync projects")]
+    LoggedOut,
+    #[error("Open a project directory to sync it")]
+    NoProject,
+    #[error("Sync was interrupted")]
+    Interrupted,
+}
+
+/// Errors reach the webview as their message, along with the changes on both
+/// sides if they conflict
+impl Serialize for Error {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let status = match self {
+            Error::Sync(gpu_sync::Error::Conflict(status)) => Some(status),
+            _ => None,
+        };
+        let mut state = serializer.serialize_struct("Error", 2)?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("status", &status)?;
+        state.end()
+    }
+}
+
+/// Remote the open project is synced with, once logged in
+#[derive(Default)]
+pub struct ProjectSync {
+    remote: Mutex<Option<Arc<HttpRemote>>>,
+}
+
+impl ProjectSync {
+    pub async fn login(
+        &self,
+        api: String,
+        username_or_email: String,
+        password: String,
+    ) -> Result<(), Error> {
+        let remote = blocking(move || {
+            let remote = HttpRemote::new(&api);
+            remote.login(&username_or_email, &password)?;
+            Ok(remote)
+        })
+        .await?;
+        *self.remote.lock().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(remote));
+        Ok(())
+    }
+
+    pub async fn status(&self, root: Option<PathBuf>) -> Result<Status, Error> {
+        self.run(root, |project, remote| project.status(remote))
+            .await
+    }
+
+    pub async fn pull(
+        &self,
+        root: Option<PathBuf>,
+        id: Option<String>,
+        force: bool,
+    ) -> Result<Status, Error> {
+        self.run(root, move |project, remote| {
+            project.pull(remote, id.as_deref(), force)
+        })
+        .await
+    }
+
+    pub async fn push(&self, root: Option<PathBuf>, force: bool) -> Result<Status, Error> {
+        self.run(root, move |project, remote| project.push(remote, force))
+            .await
+    }
+
+    /// Runs `f` on the project in `root` on a blocking thread
+    async fn run(
+        &self,
+        root: Option<PathBuf>,
+        f: impl FnOnce(&mut LocalProject, &HttpRemote) -> Result<Status, gpu_sync::Error>
+            + Send
+            + 'static,
+    ) -> Result<Status, Error> {
+        let root = root.ok_or(Error::NoProject)?;
+        let remote = self
+            .remote
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+            .ok_or(Error::LoggedOut)?;
+        blocking(move || {
+            let mut project = LocalProject::open(&root)?;
+            f(&mut project, &remote)
+        })
+        .await
+    }
+}
+
+/// Runs `f` on a new thread, answering once it is done
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, gpu_sync::Error> + Send + 'static,
+) -> Result<T, Error> {
+    let (sender, receiver) = futures_channel::oneshot::channel();
+    std::thread::Builder::new()
+        .name("sync".to_owned())
+        .spawn(move || {
+            let _ = sender.send(f());
+        })
+        .map_err(|_| Error::Interrupted)?;
+    Ok(receiver.await.map_err(|_| Error::Interrupted)??)
+}
+
+#[cfg(test)]
+mod tests {
+    use gpu_sync::{Changes, Status};
+
+    use super::Error;
+
+    #[test]
+    fn test_serialize_error() {
+        let json = serde_json::to_value(Error::LoggedOut).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "message": "Log in to sync projects", "status": null })
+        );
+
+        let status = Status {
+            project_id: Some("toy".to_owned()),
+            local: Changes::default(),
+            remote: Changes::default(),
+            conflicts: vec!["/main.wgsl".to_owned()],
+        };
+        let err = Error::Sync(gpu_sync::Error::Conflict(Box::new(status)));
+        let json = serde_json::to_value(err).unwrap();
+        assert_eq!(json["status"]["conflicts"][0], "/main.wgsl");
+        assert!(json["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Both sides changed"));
+    }
+}
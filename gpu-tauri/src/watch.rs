@@ -0,0 +1,336 @@
+//! Watches a project directory, so projects can be edited in any editor and
+//! rebuilt as they are saved.
+//!
+//! Events are only used to tell when something changed. Once they settle, the
+//! whole directory is read again and compared with the last read, which copes
+//! with editors that save by renaming over the original, and with entire
+//! directories being moved.
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use gpu_common::{Project, ProjectDir, ProjectDirError};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use thiserror::Error;
+
+/// Time without events before the directory is read again
+const DEBOUNCE: Duration = Duration::from_millis(100);
+/// Longest time changes are held back while events keep coming
+const MAX_DELAY: Duration = Duration::from_millis(1000);
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Project(#[from] ProjectDirError),
+    #[error("Could not watch project directory: {0}")]
+    Watch(#[from] notify::Error),
+    #[error("Could not spawn watcher thread: {0}")]
+    Spawn(std::io::Error),
+}
+
+/// Errors reach the webview as their message
+impl Serialize for Error {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Files that changed between two reads of a project, as keys of `Files`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+    /// Whether the manifest changed, i.e. the project's config
+    pub manifest: bool,
+}
+
+impl FileChanges {
+    pub fn between(old: &ProjectDir, new: &ProjectDir) -> Self {
+        let mut changes = FileChanges::default();
+        for (key, file) in new.files.iter() {
+            match old.files.get(key) {
+                None => changes.added.push(key.to_owned()),
+                Some(old) if old.data != file.data || old.fetch != file.fetch => {
+                    changes.modified.push(key.to_owned())
+                }
+                Some(_) => {}
+            }
+        }
+        changes.removed = old
+            .files
+            .keys()
+            .filter(|key| !new.files.contains(key))
+            .map(str::to_owned)
+            .collect();
+        changes.added.sort();
+        changes.removed.sort();
+        changes.modified.sort();
+        changes.manifest =
+            serde_json::to_value(&old.manifest).ok() != serde_json::to_value(&new.manifest).ok();
+        changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.modified.is_empty()
+            && !self.manifest
+    }
+}
+
+/// Project after files changed on disk
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectUpdate {
+    pub changes: FileChanges,
+    pub project: Project,
+}
+
+/// Watches a project directory until dropped
+pub struct ProjectWatcher {
+    /// Dropped first to disconnect the thread's channel, which stops it
+    watcher: Option<RecommendedWatcher>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ProjectWatcher {
+    /// Watches `root`, whose last read is `project`. Once files change, `on_update`
+    /// is called on the watcher thread with the project read again.
+    pub fn new(
+        root: PathBuf,
+        project: ProjectDir,
+        on_update: impl Fn(ProjectUpdate) + Send + 'static,
+    ) -> Result<Self, Error> {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(&root, RecursiveMode::Recursive)?;
+        let thread = std::thread::Builder::new()
+            .name("watcher".to_owned())
+            .spawn(move || watch(root, project, receiver, on_update))
+            .map_err(Error::Spawn)?;
+        Ok(Self {
+            watcher: Some(watcher),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for ProjectWatcher {
+    fn drop(&mut self) {
+        self.watcher = None;
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("Watcher thread panicked");
+            }
+        }
+    }
+}
+
+fn watch(
+    root: PathBuf,
+    mut project: ProjectDir,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+    on_update: impl Fn(ProjectUpdate),
+) {
+    let mut debouncer = Debouncer::default();
+    loop {
+        let event = match debouncer.wait(Instant::now()) {
+            Some(wait) => events.recv_timeout(wait),
+            None => events.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match event {
+            Ok(Ok(event)) => {
+                if event.paths.iter().any(|path| is_relevant(&root, path)) {
+                    debouncer.push(Instant::now());
+                }
+            }
+            Ok(Err(err)) => log::warn!("Watch error: {err}"),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+        if !debouncer.take(Instant::now()) {
+            continue;
+        }
+        let read = match ProjectDir::read(&root) {
+            Ok(read) => read,
+            // Likely caught halfway through a save, another event follows once it is done
+            Err(err) => {
+                log::warn!("Could not read project after it changed: {err}");
+                continue;
+            }
+        };
+        let changes = FileChanges::between(&project, &read);
+        project = read;
+        if !changes.is_empty() {
+            on_update(ProjectUpdate {
+                changes,
+                project: project.clone().into_project(),
+            });
+        }
+    }
+}
+
+/// Whether a change to `path` can change the project. Hidden files are never
+/// read, which also skips `.git` and the swap files of most editors.
+fn is_relevant(root: &Path, path: &Path) -> bool {
+    let relative = match path.strip_prefix(root) {
+        Ok(relative) => relative,
+        Err(_) => return false,
+    };
+    let hidden = relative.components().any(|component| match component {
+        Component::Normal(name) => name.to_string_lossy().starts_with('.'),
+        _ => false,
+    });
+    let backup = relative.to_string_lossy().ends_with('~');
+    !hidden && !backup
+}
+
+/// Holds back changes until events stop coming for a moment
+#[derive(Debug, Default)]
+struct Debouncer {
+    /// Time of the first event that was held back
+    first: Option<Instant>,
+    /// Time changes are passed on at
+    due: Option<Instant>,
+}
+
+impl Debouncer {
+    fn push(&mut self, now: Instant) {
+        let first = *self.first.get_or_insert(now);
+        self.due = Some((now + DEBOUNCE).min(first + MAX_DELAY));
+    }
+
+    /// Time until changes are due, or `None` if there are none
+    fn wait(&self, now: Instant) -> Option<Duration> {
+        self.due.map(|due| due.saturating_duration_since(now))
+    }
+
+    /// Whether changes are due, clearing them if so
+    fn take(&mut self, now: Instant) -> bool {
+        match self.due {
+            Some(due) if due <= now => {
+                *self = Self::default();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    use gpu_common::{Files, ProjectDir};
+
+    use super::{is_relevant, Debouncer, FileChanges, ProjectWatcher, DEBOUNCE, MAX_DELAY};
+
+    fn project(files: &[(&str, &str)]) -> ProjectDir {
+        ProjectDir::from_project(
+            "Watched",
+            Files::from_entries(files.iter().copied()).unwrap().into(),
+        )
+    }
+
+    #[test]
+    fn test_changes() {
+        let old = project(&[("/main.wgsl", "a"), ("/lib/noise.wgsl", "b")]);
+        let new = project(&[("/main.wgsl", "a2"), ("/lib/util.wgsl", "c")]);
+        let changes = FileChanges::between(&old, &new);
+        assert_eq!(
+            changes,
+            FileChanges {
+                added: vec!["/lib/util.wgsl".to_owned()],
+                removed: vec!["/lib/noise.wgsl".to_owned()],
+                modified: vec!["/main.wgsl".to_owned()],
+                manifest: false,
+            }
+        );
+        assert!(FileChanges::between(&new, &new).is_empty());
+
+        let mut renamed = project(&[("/main.wgsl", "a")]);
+        renamed.manifest.title = "Renamed".to_owned();
+        let changes = FileChanges::between(&project(&[("/main.wgsl", "a")]), &renamed);
+        assert!(changes.manifest);
+    }
+
+    #[test]
+    fn test_is_relevant() {
+        let root = Path::new("/toys/noise");
+        assert!(is_relevant(root, &root.join("shaders/main.wgsl")));
+        assert!(is_relevant(root, &root.join("gputoy.toml")));
+        assert!(is_relevant(root, &root.join("shaders")));
+        assert!(!is_relevant(root, &root.join(".git/index")));
+        assert!(!is_relevant(root, &root.join("shaders/.main.wgsl.swp")));
+        assert!(!is_relevant(root, &root.join("shaders/main.wgsl~")));
+        assert!(!is_relevant(root, Path::new("/toys/other/main.wgsl")));
+    }
+
+    #[test]
+    fn test_debouncer() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::default();
+        assert_eq!(debouncer.wait(start), None);
+        assert!(!debouncer.take(start));
+
+        debouncer.push(start);
+        assert_eq!(debouncer.wait(start), Some(DEBOUNCE));
+        // Every event pushes changes back
+        let later = start + DEBOUNCE / 2;
+        debouncer.push(later);
+        assert!(!debouncer.take(start + DEBOUNCE));
+        assert!(debouncer.take(later + DEBOUNCE));
+        assert_eq!(debouncer.wait(later + DEBOUNCE), None);
+
+        // Though not forever
+        let mut now = start;
+        while now < start + MAX_DELAY {
+            debouncer.push(now);
+            now += DEBOUNCE / 2;
+        }
+        assert!(debouncer.take(start + MAX_DELAY));
+    }
+
+    #[test]
+    fn test_watch_dir() {
+        let root = std::env::temp_dir().join(format!("gputoy-watch-{}", std::process::id()));
+        let dir = project(&[("/main.wgsl", "a")]);
+        dir.write(&root).unwrap();
+        let (sender, receiver) = mpsc::channel();
+        let watcher = ProjectWatcher::new(
+            root.clone(),
+            ProjectDir::read(&root).unwrap(),
+            move |update| {
+                let _ = sender.send(update);
+            },
+        )
+        .unwrap();
+
+        std::fs::write(root.join("main.wgsl"), "a2").unwrap();
+        std::fs::create_dir_all(root.join("lib")).unwrap();
+        std::fs::write(root.join("lib/noise.wgsl"), "b").unwrap();
+        std::fs::write(root.join(".notes.wgsl"), "hidden").unwrap();
+
+        // Events may be split across updates depending on timing
+        let mut added = Vec::new();
+        let mut modified = Vec::new();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while added.is_empty() || modified.is_empty() {
+            let wait = deadline.saturating_duration_since(Instant::now());
+            let update = receiver.recv_timeout(wait).expect("no update was reported");
+            added.extend(update.changes.added);
+            modified.extend(update.changes.modified);
+            assert!(update.project.files.contains("/main.wgsl"));
+        }
+        drop(watcher);
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(added, ["/lib/noise.wgsl"]);
+        assert_eq!(modified, ["/main.wgsl"]);
+    }
+}
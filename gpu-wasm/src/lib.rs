@@ -1,7 +1,27 @@
 // #![cfg(target_arch = "wasm32")]
 
+mod render_loop;
+mod types;
+mod worker;
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use futures_util::lock::Mutex;
+use gpu_client::record::{Recorder, Recording};
+use gpu_common::{
+    CaptureOptions, Event, FilteredAction, GpuError, ParamUpdate, Project, WorkerResponse,
+};
+use serde::Serialize;
 use thiserror::Error;
-use wasm_bindgen::{prelude::*, JsValue};
+use tsify::Tsify;
+use wasm_bindgen::{prelude::*, JsCast, JsValue};
+
+use render_loop::RenderLoop;
+use types::*;
+
+pub use worker::WorkerContext;
 
 #[wasm_bindgen(start)]
 pub fn __init() -> Result<(), JsValue> {
@@ -19,21 +39,215 @@ pub enum Error {
     ContextBuild(gpu_client::context::Error),
     #[error("Context render failed: {0}")]
     ContextRender(gpu_client::context::Error),
-    #[error("Could not serialize from JsonValue: {0}")]
+    #[error("Could not convert between JsValue and Rust: {0}")]
     SerdeWasmBindgen(serde_wasm_bindgen::Error),
+    #[error("Context capture failed: {0}")]
+    ContextCapture(gpu_client::context::Error),
+    #[error("Context recording failed: {0}")]
+    ContextRecord(gpu_client::context::Error),
+    #[error("No recording in progress")]
+    NotRecording,
+    #[error("Could not set param: {0}")]
+    SetParam(gpu_client::context::Error),
+    #[error("Could not run render loop: {0:?}")]
+    RenderLoop(JsValue),
+    #[error("Could not create blob: {0:?}")]
+    Blob(JsValue),
     #[error("Could not initialize logger")]
     LoggerInit,
+    #[error("Could not import shader: {0}")]
+    Import(gpu_common::ShadertoyError),
+    #[error("Invalid keybind: {0}")]
+    Keybind(gpu_common::KeybindError),
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::ContextInit(_) => ErrorKind::ContextInit,
+            Error::ContextBuild(_) => ErrorKind::ContextBuild,
+            Error::ContextRender(_) => ErrorKind::ContextRender,
+            Error::SerdeWasmBindgen(_) => ErrorKind::InvalidValue,
+            Error::ContextCapture(_) => ErrorKind::ContextCapture,
+            Error::ContextRecord(_) => ErrorKind::ContextRecord,
+            Error::NotRecording => ErrorKind::NotRecording,
+            Error::SetParam(_) => ErrorKind::SetParam,
+            Error::RenderLoop(_) => ErrorKind::RenderLoop,
+            Error::Blob(_) => ErrorKind::Blob,
+            Error::LoggerInit => ErrorKind::LoggerInit,
+            Error::Import(_) => ErrorKind::Import,
+            Error::Keybind(_) => ErrorKind::Keybind,
+        }
+    }
+
+    fn context_error(&self) -> Option<&gpu_client::context::Error> {
+        match self {
+            Error::ContextInit(err)
+            | Error::ContextBuild(err)
+            | Error::ContextRender(err)
+            | Error::ContextCapture(err)
+            | Error::ContextRecord(err)
+            | Error::SetParam(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// What failed, matching the variants of `Error`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Tsify)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorKind {
+    ContextInit,
+    ContextBuild,
+    ContextRender,
+    /// An argument did not match its declared type, or a result could not be converted
+    InvalidValue,
+    ContextCapture,
+    ContextRecord,
+    NotRecording,
+    SetParam,
+    RenderLoop,
+    Blob,
+    LoggerInit,
+    Import,
+    Keybind,
+}
+
+/// Error every method of the wasm api throws, or rejects with
+#[derive(Debug, Serialize, Tsify)]
+#[serde(rename_all = "camelCase")]
+pub struct WasmError {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// Set if the gpu raised the error, to point at the offending pass
+    pub gpu: Option<GpuError>,
 }
 
 impl From<Error> for JsValue {
     fn from(err: Error) -> Self {
-        JsValue::from_str(&err.to_string())
+        use gpu_client::context::Error as ContextError;
+        let gpu = match err.context_error() {
+            Some(ContextError::Gpu(gpu_err)) => Some(gpu_err.clone()),
+            _ => None,
+        };
+        let wasm_err = WasmError {
+            kind: err.kind(),
+            message: err.to_string(),
+            gpu,
+        };
+        serde_wasm_bindgen::to_value(&wasm_err)
+            .unwrap_or_else(|_| JsValue::from_str(&wasm_err.message))
     }
 }
 
+/// Gpu context, driven either by its own render loop with `start` or by calling
+/// `render` every frame.
+///
+/// Methods that touch the gpu wait for each other, so a build requested mid-frame
+/// runs once the frame is done.
 #[wasm_bindgen]
 #[derive(Debug)]
-pub struct Context(gpu_client::context::Context);
+pub struct Context {
+    shared: Rc<Shared>,
+    render_loop: Rc<RefCell<RenderLoop>>,
+}
+
+/// State shared between the context and its render loop
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    callbacks: RefCell<Callbacks>,
+    /// Minimum time between frames of the render loop in milliseconds, updated on build
+    frame_interval: Cell<f64>,
+    /// Set when running in a worker, which posts events to the main thread instead
+    /// of calling callbacks
+    post_events: bool,
+}
+
+#[derive(Debug)]
+struct State {
+    inner: gpu_client::context::Context,
+    /// Recording in progress, if any
+    recorder: Option<Recorder>,
+}
+
+/// Functions subscribed to context events, called with the event's payload
+#[derive(Debug, Default)]
+struct Callbacks {
+    frame: Option<js_sys::Function>,
+    build: Option<js_sys::Function>,
+    error: Option<js_sys::Function>,
+    stats: Option<js_sys::Function>,
+}
+
+impl Callbacks {
+    fn get(&self, event: &Event) -> Option<js_sys::Function> {
+        match event {
+            Event::Frame(_) => self.frame.clone(),
+            Event::Build(_) => self.build.clone(),
+            Event::Error(_) => self.error.clone(),
+            Event::Stats(_) => self.stats.clone(),
+        }
+    }
+}
+
+impl Shared {
+    fn dispatch_events(&self, state: &State) {
+        for event in state.inner.drain_events() {
+            if self.post_events {
+                worker::post(&WorkerResponse::Event(event));
+                continue;
+            }
+            // Cloned out so callbacks can subscribe and unsubscribe while being called
+            let callback = match self.callbacks.borrow().get(&event) {
+                Some(callback) => callback,
+                None => continue,
+            };
+            let payload = match event {
+                Event::Frame(ref payload) => serde_wasm_bindgen::to_value(payload),
+                Event::Build(ref payload) => serde_wasm_bindgen::to_value(payload),
+                Event::Error(ref payload) => serde_wasm_bindgen::to_value(payload),
+                Event::Stats(ref payload) => serde_wasm_bindgen::to_value(payload),
+            };
+            let res = payload
+                .map_err(|err| JsValue::from_str(&err.to_string()))
+                .and_then(|payload| callback.call1(&JsValue::NULL, &payload));
+            if let Err(err) = res {
+                log::error!("Event callback failed: {err:?}");
+            }
+        }
+    }
+
+    async fn build(&self, project: &Project) -> Result<(), Error> {
+        log::debug!("Received project with {} files", project.files.len());
+        let mut state = self.state.lock().await;
+        let res = state.inner.build(project).await;
+        self.frame_interval.set(state.inner.frame_interval());
+        self.dispatch_events(&state);
+        res.map_err(Error::ContextBuild)
+    }
+
+    async fn render(&self) -> Result<(), Error> {
+        let mut state = self.state.lock().await;
+        let res = state.inner.render().await;
+        self.dispatch_events(&state);
+        res.map_err(Error::ContextRender)
+    }
+
+    async fn capture(&self, options: &CaptureOptions) -> Result<Vec<u8>, Error> {
+        let mut state = self.state.lock().await;
+        state
+            .inner
+            .capture(options)
+            .await
+            .map_err(Error::ContextCapture)
+    }
+
+    async fn set_param(&self, update: &ParamUpdate) -> Result<(), Error> {
+        let mut state = self.state.lock().await;
+        state.inner.set_param(update).map_err(Error::SetParam)
+    }
+}
 
 #[wasm_bindgen]
 impl Context {
@@ -42,39 +256,313 @@ impl Context {
         let inner = gpu_client::context::Context::new()
             .await
             .map_err(Error::ContextInit)?;
-        Ok(Context(inner))
+        Ok(Context::from_inner(inner, false))
     }
 
     #[wasm_bindgen(js_name = debug)]
-    pub fn debug_to_console(&self) {
-        log::info!("{:#?}", &self);
+    pub fn debug_to_console(&self) -> VoidPromise {
+        let shared = self.shared.clone();
+        let render_loop = self.render_loop.clone();
+        promise(async move {
+            let state = shared.state.lock().await;
+            log::info!("{:#?}\n{:#?}", &state, &render_loop.borrow());
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Starts rendering every animation frame, paced to the frame rate limit of the
+    /// project config. Frames are skipped while the page is hidden, and results are
+    /// reported through the `on*` callbacks.
+    #[wasm_bindgen]
+    pub fn start(&self) -> Result<(), Error> {
+        RenderLoop::start(&self.render_loop, self.shared.clone()).map_err(Error::RenderLoop)
+    }
+
+    /// Stops the render loop started with `start`
+    #[wasm_bindgen]
+    pub fn stop(&self) {
+        self.render_loop.borrow_mut().stop();
+    }
+
+    #[wasm_bindgen(js_name = isRunning)]
+    pub fn is_running(&self) -> bool {
+        self.render_loop.borrow().is_running()
     }
 
+    /// Resizes the surface to a canvas of `width` by `height` css pixels, given the
+    /// device pixel ratio, which is passed in since workers have no access to it
     #[wasm_bindgen]
-    pub fn resize(&self, width: i32, height: i32) {
-        log::info!("Resizing to: {} {}", width, height);
+    pub fn resize(&self, width: f64, height: f64, pixel_ratio: f64) -> VoidPromise {
+        let shared = self.shared.clone();
+        promise(async move {
+            let mut state = shared.state.lock().await;
+            state.inner.resize(width, height, pixel_ratio);
+            Ok(JsValue::UNDEFINED)
+        })
     }
 
     #[wasm_bindgen]
-    pub async fn build(&mut self, project: JsValue) -> Result<(), Error> {
-        let project = serde_wasm_bindgen::from_value(project).map_err(Error::SerdeWasmBindgen)?;
-        log::info!("Recieved project: {:?}", project);
-        self.0.build(&project).await.map_err(Error::ContextBuild)
+    pub fn build(&self, project: ProjectArg) -> VoidPromise {
+        let shared = self.shared.clone();
+        promise(async move {
+            let project = from_js(project)?;
+            shared.build(&project).await?;
+            Ok(JsValue::UNDEFINED)
+        })
     }
 
+    /// Renders a single frame, for driving the context without the render loop
     #[wasm_bindgen]
-    pub async fn render(&self) -> Result<(), Error> {
-        self.0.render().await.map_err(Error::ContextRender)
+    pub fn render(&self) -> VoidPromise {
+        let shared = self.shared.clone();
+        promise(async move {
+            shared.render().await?;
+            Ok(JsValue::UNDEFINED)
+        })
     }
+
+    /// Handles input on the canvas
+    #[wasm_bindgen]
+    pub fn input(&self, event: InputEventArg) -> VoidPromise {
+        let shared = self.shared.clone();
+        promise(async move {
+            let event = from_js(event)?;
+            shared.state.lock().await.inner.input(event);
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Writes values into the resource named by `update`
+    #[wasm_bindgen(js_name = setParam)]
+    pub fn set_param(&self, update: ParamUpdateArg) -> VoidPromise {
+        let shared = self.shared.clone();
+        promise(async move {
+            let update = from_js(update)?;
+            shared.set_param(&update).await?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Subscribes to frame events, emitted after every rendered frame.
+    /// Pass `undefined` to unsubscribe.
+    #[wasm_bindgen(js_name = onFrame)]
+    pub fn on_frame(&self, callback: Option<FrameCallback>) {
+        self.shared.callbacks.borrow_mut().frame = callback.map(JsCast::unchecked_into);
+    }
+
+    /// Subscribes to build events, emitted after every build with its diagnostics
+    #[wasm_bindgen(js_name = onBuild)]
+    pub fn on_build(&self, callback: Option<BuildCallback>) {
+        self.shared.callbacks.borrow_mut().build = callback.map(JsCast::unchecked_into);
+    }
+
+    /// Subscribes to gpu errors, including ones raised outside of build and render
+    #[wasm_bindgen(js_name = onError)]
+    pub fn on_error(&self, callback: Option<ErrorCallback>) {
+        self.shared.callbacks.borrow_mut().error = callback.map(JsCast::unchecked_into);
+    }
+
+    /// Subscribes to profiler snapshots, emitted every second while profiling is enabled
+    #[wasm_bindgen(js_name = onStats)]
+    pub fn on_stats(&self, callback: Option<StatsCallback>) {
+        self.shared.callbacks.borrow_mut().stats = callback.map(JsCast::unchecked_into);
+    }
+
+    /// Renders the current project to a png, with the size of the image in pixels,
+    /// and optionally the time to render at.
+    #[wasm_bindgen]
+    pub fn capture(&self, options: CaptureOptionsArg) -> BlobPromise {
+        let shared = self.shared.clone();
+        promise(async move {
+            let options = from_js(options)?;
+            let png = shared.capture(&options).await?;
+            Ok(to_blob(&png, "image/png")?.into())
+        })
+    }
+
+    /// Starts rendering a time range offline, resetting resources so recordings are
+    /// reproducible.
+    ///
+    /// The render loop should be stopped while recording, since its frames would
+    /// advance resources between recorded frames.
+    #[wasm_bindgen(js_name = startRecording)]
+    pub fn start_recording(&self, options: RecordOptionsArg) -> RecordProgressPromise {
+        let shared = self.shared.clone();
+        promise(async move {
+            let options = from_js(options)?;
+            let mut state = shared.state.lock().await;
+            let recorder = state.inner.start_recording(options).await;
+            shared.dispatch_events(&state);
+            let recorder = recorder.map_err(Error::ContextRecord)?;
+            let progress = recorder.progress();
+            state.recorder = Some(recorder);
+            to_js(&progress)
+        })
+    }
+
+    /// Renders the next frame of the recording. For webm recordings, resolves to the
+    /// raw rgba frame, which must be encoded to VP8 and passed to `pushEncodedFrame`.
+    #[wasm_bindgen(js_name = recordFrame)]
+    pub fn record_frame(&self) -> FramePromise {
+        let shared = self.shared.clone();
+        promise(async move {
+            let mut state = shared.state.lock().await;
+            let State { inner, recorder } = &mut *state;
+            let recorder = recorder.as_mut().ok_or(Error::NotRecording)?;
+            let res = inner.record_frame(recorder).await;
+            shared.dispatch_events(&state);
+            let frame = res.map_err(Error::ContextRecord)?;
+            Ok(frame.map_or(JsValue::UNDEFINED, |rgba| {
+                js_sys::Uint8Array::from(rgba.as_slice()).into()
+            }))
+        })
+    }
+
+    /// Progress of the recording in progress
+    #[wasm_bindgen(js_name = recordingProgress)]
+    pub fn recording_progress(&self) -> RecordProgressPromise {
+        let shared = self.shared.clone();
+        promise(async move {
+            let state = shared.state.lock().await;
+            let recorder = state.recorder.as_ref().ok_or(Error::NotRecording)?;
+            to_js(&recorder.progress())
+        })
+    }
+
+    #[wasm_bindgen(js_name = pushEncodedFrame)]
+    pub fn push_encoded_frame(&self, data: Vec<u8>, keyframe: bool) -> VoidPromise {
+        let shared = self.shared.clone();
+        promise(async move {
+            let mut state = shared.state.lock().await;
+            let recorder = state.recorder.as_mut().ok_or(Error::NotRecording)?;
+            recorder
+                .push_encoded(&data, keyframe)
+                .map_err(Error::ContextRecord)?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Finishes the recording, resolving to a `Blob` for gif and webm recordings, or
+    /// an array of png `Blob`s for png sequences
+    #[wasm_bindgen(js_name = finishRecording)]
+    pub fn finish_recording(&self) -> RecordingPromise {
+        let shared = self.shared.clone();
+        promise(async move {
+            let recorder = shared.state.lock().await.recorder.take();
+            let recorder = recorder.ok_or(Error::NotRecording)?;
+            Ok(match recorder.finish().map_err(Error::ContextRecord)? {
+                Recording::PngSequence(frames) => frames
+                    .iter()
+                    .map(|png| to_blob(png, "image/png"))
+                    .collect::<Result<js_sys::Array, _>>()?
+                    .into(),
+                Recording::Gif(gif) => to_blob(&gif, "image/gif")?.into(),
+                Recording::Webm(webm) => to_blob(&webm, "video/webm")?.into(),
+            })
+        })
+    }
+
+    /// Drops the recording in progress, if any
+    #[wasm_bindgen(js_name = cancelRecording)]
+    pub fn cancel_recording(&self) -> VoidPromise {
+        let shared = self.shared.clone();
+        promise(async move {
+            shared.state.lock().await.recorder = None;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Restarts time and frame count from zero, which builds keep running
+    #[wasm_bindgen]
+    pub fn restart(&self) -> VoidPromise {
+        let shared = self.shared.clone();
+        promise(async move {
+            shared.state.lock().await.inner.restart();
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    #[wasm_bindgen(js_name = setProfiling)]
+    pub fn set_profiling(&self, enabled: bool) -> VoidPromise {
+        let shared = self.shared.clone();
+        promise(async move {
+            shared.state.lock().await.inner.set_profiling(enabled);
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Timings collected while profiling is enabled
+    #[wasm_bindgen]
+    pub fn profile(&self) -> ProfileSnapshotPromise {
+        let shared = self.shared.clone();
+        promise(async move {
+            let snapshot = shared.state.lock().await.inner.profile();
+            to_js(&snapshot)
+        })
+    }
+}
+
+/// Creates projects from shaders exported from Shadertoy as json, needs no
+/// context
+#[wasm_bindgen(js_name = importShadertoy)]
+pub fn import_shadertoy(json: &str) -> Result<ShadertoyImportsResult, Error> {
+    let imports = gpu_common::import_shadertoy(json).map_err(Error::Import)?;
+    Ok(to_js(&imports)?.unchecked_into())
 }
 
+/// Writes a keybind in canonical form, i.e. `C-S-x` as `ctrl+shift+x`
+#[wasm_bindgen(js_name = normalizeKeybind)]
+pub fn normalize_keybind(keybind: &str) -> Result<String, Error> {
+    gpu_common::Keybind::normalize(keybind).map_err(Error::Keybind)
+}
+
+/// Default keymap with `overrides` applied. Given a platform, keybinds are
+/// written as pressed on it, for looking up keyboard events.
 #[wasm_bindgen]
-pub enum FrameResult {
-    Placeholder,
+pub fn keymap(
+    overrides: KeybindsArg,
+    platform: Option<PlatformArg>,
+) -> Result<KeymapResult, Error> {
+    let overrides: HashMap<String, FilteredAction> = from_js(overrides)?;
+    let mut keymap = gpu_common::merge_keymap(&gpu_common::default_keymap(), &overrides);
+    if let Some(platform) = platform {
+        keymap = gpu_common::resolve_keymap(&keymap, from_js(platform)?);
+    }
+    // Keymaps are records on the typescript side rather than `Map`s
+    let keymap = keymap
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(Error::SerdeWasmBindgen)?;
+    Ok(keymap.unchecked_into())
 }
 
-impl From<FrameResult> for JsValue {
-    fn from(_: FrameResult) -> Self {
-        JsValue::from_str("Placeholder")
+/// Keybinds that trigger different actions on the same keys in some context
+#[wasm_bindgen(js_name = keybindConflicts)]
+pub fn keybind_conflicts(keybinds: KeybindsArg) -> Result<KeybindConflictsResult, Error> {
+    let keybinds: HashMap<String, FilteredAction> = from_js(keybinds)?;
+    let conflicts = gpu_common::find_conflicts(&keybinds);
+    Ok(to_js(&conflicts)?.unchecked_into())
+}
+
+impl Context {
+    fn from_inner(inner: gpu_client::context::Context, post_events: bool) -> Self {
+        Context {
+            shared: Rc::new(Shared {
+                state: Mutex::new(State {
+                    inner,
+                    recorder: None,
+                }),
+                callbacks: RefCell::default(),
+                frame_interval: Cell::new(0.0),
+                post_events,
+            }),
+            render_loop: Rc::default(),
+        }
     }
 }
+
+fn to_blob(data: &[u8], mime: &str) -> Result<web_sys::Blob, Error> {
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(data));
+    let mut props = web_sys::BlobPropertyBag::new();
+    props.type_(mime);
+    web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &props).map_err(Error::Blob)
+}
@@ -0,0 +1,211 @@
+//! Render loop scheduled with `requestAnimationFrame`, owned by the context so
+//! that frames are paced and rendered without a round trip through js.
+
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use wasm_bindgen::{prelude::*, JsCast};
+
+use crate::Shared;
+
+/// Animation frame timestamps jitter around the display's refresh interval, so a
+/// frame this many milliseconds early still counts as on time when pacing
+const PACING_TOLERANCE_MS: f64 = 2.0;
+
+#[derive(Default)]
+pub(crate) struct RenderLoop {
+    running: bool,
+    /// Whether the page is hidden, in which case no frames are requested
+    hidden: bool,
+    /// Id of the pending animation frame request
+    request: Option<i32>,
+    /// Timestamp the last rendered frame was due at
+    last_frame: Option<f64>,
+    tick: Option<Closure<dyn FnMut(f64)>>,
+    on_visibility_change: Option<Closure<dyn FnMut()>>,
+}
+
+impl std::fmt::Debug for RenderLoop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RenderLoop")
+            .field("running", &self.running)
+            .field("hidden", &self.hidden)
+            .field("request", &self.request)
+            .field("last_frame", &self.last_frame)
+            .finish()
+    }
+}
+
+impl RenderLoop {
+    pub(crate) fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Starts requesting animation frames, doing nothing if already running.
+    ///
+    /// Closures only hold a weak reference to the loop, so dropping it stops the loop.
+    pub(crate) fn start(this: &Rc<RefCell<Self>>, shared: Rc<Shared>) -> Result<(), JsValue> {
+        let mut render_loop = this.borrow_mut();
+        if render_loop.running {
+            return Ok(());
+        }
+        if let Some(document) = document() {
+            render_loop.hidden = document.hidden();
+        }
+
+        let weak = Rc::downgrade(this);
+        let tick =
+            Closure::<dyn FnMut(f64)>::new(move |timestamp| Self::tick(&weak, &shared, timestamp));
+        // Workers have no document, their page's visibility is passed to `set_hidden`
+        if let Some(document) = document() {
+            let weak = Rc::downgrade(this);
+            let page = document.clone();
+            let on_visibility_change = Closure::<dyn FnMut()>::new(move || {
+                if let Some(this) = weak.upgrade() {
+                    this.borrow_mut().set_hidden(page.hidden());
+                }
+            });
+            document.add_event_listener_with_callback(
+                "visibilitychange",
+                on_visibility_change.as_ref().unchecked_ref(),
+            )?;
+            render_loop.on_visibility_change = Some(on_visibility_change);
+        }
+
+        render_loop.running = true;
+        render_loop.last_frame = None;
+        render_loop.tick = Some(tick);
+        render_loop.request_frame();
+        Ok(())
+    }
+
+    /// Cancels the pending frame and removes listeners. A frame already being
+    /// rendered still finishes and reports its events.
+    pub(crate) fn stop(&mut self) {
+        self.running = false;
+        self.cancel_frame();
+        if let Some(listener) = self.on_visibility_change.take() {
+            let res = document().map(|document| {
+                document.remove_event_listener_with_callback(
+                    "visibilitychange",
+                    listener.as_ref().unchecked_ref(),
+                )
+            });
+            if let Some(Err(err)) = res {
+                log::warn!("Could not remove visibility listener: {err:?}");
+            }
+        }
+        self.tick = None;
+    }
+
+    fn tick(this: &Weak<RefCell<Self>>, shared: &Rc<Shared>, timestamp: f64) {
+        let this = match this.upgrade() {
+            Some(this) => this,
+            None => return,
+        };
+        let mut render_loop = this.borrow_mut();
+        render_loop.request = None;
+        if !render_loop.running {
+            return;
+        }
+        // Requested before rendering so a slow frame does not push back the next one
+        render_loop.request_frame();
+        if !render_loop.pace(timestamp, shared.frame_interval.get()) {
+            return;
+        }
+        drop(render_loop);
+
+        let shared = shared.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            // Skip the frame entirely if the previous one, or a build, is still in flight
+            // rather than queueing frames behind it
+            let mut state = match shared.state.try_lock() {
+                Some(state) => state,
+                None => return,
+            };
+            let res = state.inner.render().await;
+            shared.dispatch_events(&state);
+            match res {
+                // Already reported through `onError`
+                Ok(()) | Err(gpu_client::context::Error::Gpu(_)) => {}
+                Err(err) => log::warn!("Frame failed: {err}"),
+            }
+        });
+    }
+
+    /// Whether a frame should be rendered at `timestamp`, given the minimum
+    /// interval between frames
+    fn pace(&mut self, timestamp: f64, interval: f64) -> bool {
+        let last = match self.last_frame {
+            Some(last) if interval > 0.0 => last,
+            _ => {
+                self.last_frame = Some(timestamp);
+                return true;
+            }
+        };
+        let elapsed = timestamp - last;
+        if elapsed + PACING_TOLERANCE_MS < interval {
+            return false;
+        }
+        // Advance by whole intervals so the frame rate does not drift with jitter,
+        // unless frames fell far behind, i.e. after the tab was hidden
+        self.last_frame = Some(if elapsed < interval * 2.0 {
+            last + interval
+        } else {
+            timestamp
+        });
+        true
+    }
+
+    /// Pauses the loop while the page is hidden, resuming it once visible again
+    pub(crate) fn set_hidden(&mut self, hidden: bool) {
+        self.hidden = hidden;
+        if !self.running {
+            return;
+        }
+        if hidden {
+            self.cancel_frame();
+        } else if self.request.is_none() {
+            self.last_frame = None;
+            self.request_frame();
+        }
+    }
+
+    fn request_frame(&mut self) {
+        let tick = match self.tick {
+            Some(ref tick) if !self.hidden => tick,
+            _ => return,
+        };
+        match request_animation_frame(tick.as_ref().unchecked_ref()) {
+            Ok(request) => self.request = Some(request),
+            Err(err) => log::error!("Could not request animation frame: {err:?}"),
+        }
+    }
+
+    fn cancel_frame(&mut self) {
+        if let Some(request) = self.request.take() {
+            cancel_animation_frame(request);
+        }
+    }
+}
+
+impl Drop for RenderLoop {
+    fn drop(&mut self) {
+        // Pending callbacks would otherwise call into closures that were freed
+        self.stop();
+    }
+}
+
+#[wasm_bindgen]
+extern "C" {
+    // Bound as globals rather than through `web_sys::Window`, since dedicated
+    // workers have them too
+    #[wasm_bindgen(catch, js_name = requestAnimationFrame)]
+    fn request_animation_frame(callback: &js_sys::Function) -> Result<i32, JsValue>;
+    #[wasm_bindgen(js_name = cancelAnimationFrame)]
+    fn cancel_animation_frame(handle: i32);
+}
+
+fn document() -> Option<web_sys::Document> {
+    web_sys::window()?.document()
+}
@@ -0,0 +1,76 @@
+//! Typescript types of values crossing the wasm boundary.
+//!
+//! Declarations of the types themselves are generated from gpu-common by tsify.
+//! The externs here only give arguments, results and callbacks of exported
+//! methods their names in the declarations, values are converted with serde.
+
+use std::future::Future;
+
+use serde::{de::DeserializeOwned, Serialize};
+use wasm_bindgen::{prelude::*, JsCast};
+
+use crate::Error;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "Project")]
+    pub type ProjectArg;
+    #[wasm_bindgen(typescript_type = "CaptureOptions")]
+    pub type CaptureOptionsArg;
+    #[wasm_bindgen(typescript_type = "RecordOptions")]
+    pub type RecordOptionsArg;
+    #[wasm_bindgen(typescript_type = "InputEvent")]
+    pub type InputEventArg;
+    #[wasm_bindgen(typescript_type = "ParamUpdate")]
+    pub type ParamUpdateArg;
+    #[wasm_bindgen(typescript_type = "WorkerRequest")]
+    pub type WorkerRequestArg;
+    #[wasm_bindgen(typescript_type = "Record<string, FilteredAction>")]
+    pub type KeybindsArg;
+    #[wasm_bindgen(typescript_type = "Platform")]
+    pub type PlatformArg;
+
+    #[wasm_bindgen(typescript_type = "ShadertoyImport[]")]
+    pub type ShadertoyImportsResult;
+    #[wasm_bindgen(typescript_type = "KeybindConflict[]")]
+    pub type KeybindConflictsResult;
+    #[wasm_bindgen(typescript_type = "Record<string, FilteredAction>")]
+    pub type KeymapResult;
+
+    #[wasm_bindgen(typescript_type = "Promise<void>")]
+    pub type VoidPromise;
+    #[wasm_bindgen(typescript_type = "Promise<Blob>")]
+    pub type BlobPromise;
+    #[wasm_bindgen(typescript_type = "Promise<RecordProgress>")]
+    pub type RecordProgressPromise;
+    #[wasm_bindgen(typescript_type = "Promise<Uint8Array | undefined>")]
+    pub type FramePromise;
+    #[wasm_bindgen(typescript_type = "Promise<Blob | Blob[]>")]
+    pub type RecordingPromise;
+    #[wasm_bindgen(typescript_type = "Promise<ProfileSnapshot>")]
+    pub type ProfileSnapshotPromise;
+
+    #[wasm_bindgen(typescript_type = "(event: FrameEvent) => void")]
+    pub type FrameCallback;
+    #[wasm_bindgen(typescript_type = "(event: BuildEvent) => void")]
+    pub type BuildCallback;
+    #[wasm_bindgen(typescript_type = "(error: GpuError) => void")]
+    pub type ErrorCallback;
+    #[wasm_bindgen(typescript_type = "(snapshot: ProfileSnapshot) => void")]
+    pub type StatsCallback;
+}
+
+pub(crate) fn from_js<T: DeserializeOwned>(value: impl Into<JsValue>) -> Result<T, Error> {
+    serde_wasm_bindgen::from_value(value.into()).map_err(Error::SerdeWasmBindgen)
+}
+
+pub(crate) fn to_js<T: Serialize>(value: &T) -> Result<JsValue, Error> {
+    serde_wasm_bindgen::to_value(value).map_err(Error::SerdeWasmBindgen)
+}
+
+/// Runs `future` as a promise of type `P`, rejecting with a `WasmError` on failure
+pub(crate) fn promise<P: JsCast>(
+    future: impl Future<Output = Result<JsValue, Error>> + 'static,
+) -> P {
+    wasm_bindgen_futures::future_to_promise(async move { Ok(future.await?) }).unchecked_into()
+}
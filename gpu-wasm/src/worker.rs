@@ -0,0 +1,105 @@
+//! Entry point for running the context in a dedicated worker, so heavy shaders
+//! do not block the page.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gpu_common::{WorkerRequest, WorkerResponse};
+use wasm_bindgen::{prelude::*, JsCast};
+
+use crate::render_loop::RenderLoop;
+use crate::types::{from_js, promise, VoidPromise, WorkerRequestArg};
+use crate::{Context, Error, Shared};
+
+/// Gpu context running in a dedicated worker, rendering to a canvas transferred
+/// from the main thread.
+///
+/// `WorkerRequest`s posted to the worker are passed to `handleMessage`, and
+/// everything the context reports is posted back as a `WorkerResponse`.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct WorkerContext {
+    context: Context,
+}
+
+#[wasm_bindgen]
+impl WorkerContext {
+    #[wasm_bindgen(constructor)]
+    pub async fn new(canvas: web_sys::OffscreenCanvas) -> Result<WorkerContext, Error> {
+        let inner = gpu_client::context::Context::new_offscreen(canvas)
+            .await
+            .map_err(Error::ContextInit)?;
+        let worker = WorkerContext {
+            context: Context::from_inner(inner, true),
+        };
+        post(&WorkerResponse::Ready);
+        Ok(worker)
+    }
+
+    /// Handles a request posted from the main thread. Failures are posted back
+    /// rather than rejected with, since nothing on the main thread awaits this.
+    #[wasm_bindgen(js_name = handleMessage)]
+    pub fn handle_message(&self, message: WorkerRequestArg) -> VoidPromise {
+        let shared = self.context.shared.clone();
+        let render_loop = self.context.render_loop.clone();
+        promise(async move {
+            if let Err(err) = handle(&shared, &render_loop, message).await {
+                fail(None, err);
+            }
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+}
+
+async fn handle(
+    shared: &Rc<Shared>,
+    render_loop: &Rc<RefCell<RenderLoop>>,
+    message: WorkerRequestArg,
+) -> Result<(), Error> {
+    match from_js(message)? {
+        WorkerRequest::Build(project) => {
+            // Failures are reported through build and error events
+            let _ = shared.build(&project).await;
+        }
+        WorkerRequest::Resize(resize) => {
+            let mut state = shared.state.lock().await;
+            state
+                .inner
+                .resize(resize.width, resize.height, resize.pixel_ratio);
+        }
+        WorkerRequest::Input(event) => shared.state.lock().await.inner.input(event),
+        WorkerRequest::SetParam(update) => shared.set_param(&update).await?,
+        WorkerRequest::Capture { id, options } => match shared.capture(&options).await {
+            Ok(png) => post(&WorkerResponse::Captured { id, png }),
+            Err(err) => fail(Some(id), err),
+        },
+        WorkerRequest::Start => {
+            RenderLoop::start(render_loop, shared.clone()).map_err(Error::RenderLoop)?
+        }
+        WorkerRequest::Stop => render_loop.borrow_mut().stop(),
+        WorkerRequest::Visibility { hidden } => render_loop.borrow_mut().set_hidden(hidden),
+        WorkerRequest::SetProfiling(enabled) => {
+            shared.state.lock().await.inner.set_profiling(enabled)
+        }
+        WorkerRequest::Restart => shared.state.lock().await.inner.restart(),
+    }
+    Ok(())
+}
+
+/// Posts a response to the main thread
+pub(crate) fn post(response: &WorkerResponse) {
+    let scope = js_sys::global().unchecked_into::<web_sys::DedicatedWorkerGlobalScope>();
+    let res = serde_wasm_bindgen::to_value(response)
+        .map_err(|err| JsValue::from_str(&err.to_string()))
+        .and_then(|message| scope.post_message(&message));
+    if let Err(err) = res {
+        log::error!("Could not post worker response: {err:?}");
+    }
+}
+
+fn fail(id: Option<u32>, err: Error) {
+    post(&WorkerResponse::Failed {
+        id,
+        message: err.to_string(),
+    });
+}
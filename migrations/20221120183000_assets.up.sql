@@ -0,0 +1,7 @@
+-- Add up migration script here
+CREATE TABLE assets (
+    id uuid default uuid_generate_v4() PRIMARY KEY,
+    hash CHAR(64) UNIQUE NOT NULL,
+    data BYTEA NOT NULL,
+    created_at TIMESTAMP(3) NOT NULL DEFAULT current_timestamp
+);
\ No newline at end of file
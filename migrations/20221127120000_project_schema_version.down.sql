@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE projects
+DROP schema_version;
\ No newline at end of file
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE projects
+ADD schema_version INT NOT NULL DEFAULT 0;
\ No newline at end of file
@@ -0,0 +1,16 @@
+-- Add up migration script here
+CREATE TABLE project_assets (
+    project_id uuid NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    asset_id uuid NOT NULL REFERENCES assets(id) ON DELETE CASCADE,
+    PRIMARY KEY (project_id, asset_id)
+);
+
+-- Asset ids in files are url safe base64 without padding
+INSERT INTO project_assets (project_id, asset_id)
+SELECT DISTINCT projects.id, assets.id
+FROM projects, json_each(projects.files->'map') AS file, assets
+WHERE json_typeof(file.value->'data') = 'object'
+    AND assets.id = encode(
+        decode(translate(file.value->'data'->>'id', '-_', '+/') || '==', 'base64'),
+        'hex'
+    )::uuid;